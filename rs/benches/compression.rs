@@ -0,0 +1,259 @@
+//! Throughput and compression ratio of every codec, level and shuffle filter over
+//! synthetic time series, measured on the Rust API without any P/Invoke overhead.
+//!
+//! Raw codecs get the shuffle applied as a separate pass before compression and
+//! reverted after decompression, both of which are part of the measured time. Blosc
+//! containers shuffle internally. Ratios are printed before each benchmark, the
+//! throughput is in uncompressed bytes per second for both directions.
+//!
+//! The full matrix takes a while, select a subset with a filter, e.g.
+//! `cargo bench --bench compression -- 'codec-compress/timestamps/zstd'`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use spreads_native::compression::blosc::{self, BloscCompressor, BloscParams, Shuffle};
+use spreads_native::compression::{self, Codec};
+use std::time::Duration;
+
+/// 1 MB of 8 bytes values per dataset, a multiple of 8 elements for bitshuffle.
+const ELEMENTS: usize = 128 * 1024;
+const TYPESIZE: usize = 8;
+
+const CODECS: [Codec; 7] = [
+    Codec::Lz4,
+    Codec::Zstd,
+    Codec::Zlib,
+    Codec::Deflate,
+    Codec::Gzip,
+    Codec::Lz4Hc,
+    Codec::Snappy,
+];
+
+const BLOSC_COMPRESSORS: [BloscCompressor; 6] = [
+    BloscCompressor::BloscLz,
+    BloscCompressor::Lz4,
+    BloscCompressor::Lz4Hc,
+    BloscCompressor::Snappy,
+    BloscCompressor::Zlib,
+    BloscCompressor::Zstd,
+];
+
+const SHUFFLES: [Shuffle; 3] = [Shuffle::NoShuffle, Shuffle::Shuffle, Shuffle::BitShuffle];
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Prices moving by up to 1 cent per step.
+fn random_walk() -> Vec<u8> {
+    let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+    let mut price = 100.0f64;
+    (0..ELEMENTS)
+        .flat_map(|_| {
+            price += (rng.next_f64() - 0.5) * 0.02;
+            price.to_le_bytes()
+        })
+        .collect()
+}
+
+/// Nanosecond timestamps about 1 ms apart with jitter.
+fn timestamps() -> Vec<u8> {
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+    let mut timestamp = 1_600_000_000_000_000_000i64;
+    (0..ELEMENTS)
+        .flat_map(|_| {
+            timestamp += 1_000_000 + (rng.next() % 1000) as i64;
+            timestamp.to_le_bytes()
+        })
+        .collect()
+}
+
+/// Mostly zero integers, one in 16 is a small positive value.
+fn sparse_ints() -> Vec<u8> {
+    let mut rng = XorShift(0xD1B5_4A32_D192_ED03);
+    (0..ELEMENTS)
+        .flat_map(|_| {
+            let value = rng.next();
+            let value = if value.is_multiple_of(16) {
+                (value >> 32) % 1000
+            } else {
+                0
+            };
+            (value as i64).to_le_bytes()
+        })
+        .collect()
+}
+
+fn datasets() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("random_walk_f64", random_walk()),
+        ("timestamps", timestamps()),
+        ("sparse_ints", sparse_ints()),
+    ]
+}
+
+fn shuffle_name(shuffle: Shuffle) -> &'static str {
+    match shuffle {
+        Shuffle::NoShuffle => "noshuffle",
+        Shuffle::Shuffle => "shuffle",
+        Shuffle::BitShuffle => "bitshuffle",
+    }
+}
+
+fn config() -> Criterion {
+    Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(200))
+        .measurement_time(Duration::from_millis(500))
+}
+
+fn print_ratio(group: &str, name: &str, level: i32, original: usize, compressed: usize) {
+    println!(
+        "{}/{}/{}: ratio {:.3} ({} -> {} bytes)",
+        group,
+        name,
+        level,
+        original as f64 / compressed as f64,
+        original,
+        compressed
+    );
+}
+
+/// Shuffles `data` into `scratch` unless the filter is `NoShuffle`, then compresses
+/// the result into `dest`.
+fn encode(
+    codec: Codec,
+    shuffle: Shuffle,
+    level: i32,
+    data: &[u8],
+    scratch: &mut [u8],
+    dest: &mut [u8],
+) -> usize {
+    let input = match shuffle {
+        Shuffle::NoShuffle => data,
+        Shuffle::Shuffle => {
+            compression::shuffle(TYPESIZE, data, scratch).unwrap();
+            &scratch[..]
+        }
+        Shuffle::BitShuffle => {
+            compression::bitshuffle(TYPESIZE, data, scratch).unwrap();
+            &scratch[..]
+        }
+    };
+    codec.compress(input, dest, level).unwrap()
+}
+
+/// Reverts `encode`.
+fn decode(codec: Codec, shuffle: Shuffle, src: &[u8], scratch: &mut [u8], dest: &mut [u8]) {
+    match shuffle {
+        Shuffle::NoShuffle => {
+            codec.decompress(src, dest).unwrap();
+        }
+        Shuffle::Shuffle => {
+            let len = codec.decompress(src, scratch).unwrap();
+            compression::unshuffle(TYPESIZE, &scratch[..len], dest).unwrap();
+        }
+        Shuffle::BitShuffle => {
+            let len = codec.decompress(src, scratch).unwrap();
+            compression::bitunshuffle(TYPESIZE, &scratch[..len], dest).unwrap();
+        }
+    }
+}
+
+fn codecs(c: &mut Criterion) {
+    for (dataset, data) in datasets().iter() {
+        let compress_group = format!("codec-compress/{}", dataset);
+        let decompress_group = format!("codec-decompress/{}", dataset);
+        let mut scratch = vec![0u8; data.len()];
+        let mut decompressed = vec![0u8; data.len()];
+        for codec in CODECS.iter().filter(|codec| codec.is_compiled()) {
+            let mut compressed = vec![0u8; codec.compress_bound(data.len()).unwrap()];
+            for shuffle in SHUFFLES.iter() {
+                for level in codec.levels() {
+                    let name = format!("{}/{}", codec.name(), shuffle_name(*shuffle));
+                    let len = encode(*codec, *shuffle, level, data, &mut scratch, &mut compressed);
+                    print_ratio(&compress_group, &name, level, data.len(), len);
+                    let id = BenchmarkId::new(name, level);
+                    let stored = compressed[..len].to_vec();
+
+                    let mut group = c.benchmark_group(&compress_group);
+                    group.throughput(Throughput::Bytes(data.len() as u64));
+                    group.bench_with_input(id.clone(), data, |b, data| {
+                        b.iter(|| {
+                            encode(*codec, *shuffle, level, data, &mut scratch, &mut compressed)
+                        })
+                    });
+                    group.finish();
+
+                    let mut group = c.benchmark_group(&decompress_group);
+                    group.throughput(Throughput::Bytes(data.len() as u64));
+                    group.bench_with_input(id, &stored, |b, stored| {
+                        b.iter(|| decode(*codec, *shuffle, stored, &mut scratch, &mut decompressed))
+                    });
+                    group.finish();
+                    assert_eq!(data, &decompressed);
+                }
+            }
+        }
+    }
+}
+
+fn blosc_containers(c: &mut Criterion) {
+    for (dataset, data) in datasets().iter() {
+        let compress_group = format!("blosc-compress/{}", dataset);
+        let decompress_group = format!("blosc-decompress/{}", dataset);
+        let mut compressed = vec![0u8; blosc::compress_bound(data.len()).unwrap()];
+        let mut decompressed = vec![0u8; data.len()];
+        for compressor in BLOSC_COMPRESSORS.iter().filter(|c| c.is_compiled()) {
+            for shuffle in SHUFFLES.iter() {
+                for level in 0..=9 {
+                    let params = BloscParams {
+                        level,
+                        shuffle: *shuffle,
+                        typesize: TYPESIZE,
+                        compressor: *compressor,
+                        ..BloscParams::default()
+                    };
+                    let name = format!("{}/{}", compressor.name(), shuffle_name(*shuffle));
+                    let len = blosc::compress(&params, data, &mut compressed).unwrap();
+                    print_ratio(&compress_group, &name, level, data.len(), len);
+                    let id = BenchmarkId::new(name, level);
+                    let stored = compressed[..len].to_vec();
+
+                    let mut group = c.benchmark_group(&compress_group);
+                    group.throughput(Throughput::Bytes(data.len() as u64));
+                    group.bench_with_input(id.clone(), data, |b, data| {
+                        b.iter(|| blosc::compress(&params, data, &mut compressed).unwrap())
+                    });
+                    group.finish();
+
+                    let mut group = c.benchmark_group(&decompress_group);
+                    group.throughput(Throughput::Bytes(data.len() as u64));
+                    group.bench_with_input(id, &stored, |b, stored| {
+                        b.iter(|| blosc::decompress(stored, &mut decompressed, 1).unwrap())
+                    });
+                    group.finish();
+                    assert_eq!(data, &decompressed);
+                }
+            }
+        }
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = codecs, blosc_containers
+}
+criterion_main!(benches);
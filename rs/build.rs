@@ -0,0 +1,8 @@
+fn main() {
+    // spreads-blosc-sys reports whether c-blosc compiled its AVX2 shuffle kernels.
+    println!("cargo:rustc-check-cfg=cfg(blosc_avx2)");
+    println!("cargo:rerun-if-env-changed=DEP_BLOSC_AVX2");
+    if std::env::var_os("DEP_BLOSC_AVX2").is_some() {
+        println!("cargo:rustc-cfg=blosc_avx2");
+    }
+}
@@ -1,4 +1,30 @@
 #include "./c-blosc/blosc/blosc-export.h"
+
+/* Version numbers */
+#define BLOSC_VERSION_FORMAT    2   /* format version */
+
+#define BLOSC_MIN_HEADER_LENGTH 16  /* minimum header length */
+#define BLOSC_MAX_OVERHEAD BLOSC_MIN_HEADER_LENGTH
+#define BLOSC_MAX_BUFFERSIZE (INT_MAX - BLOSC_MAX_OVERHEAD)
+#define BLOSC_MAX_TYPESIZE 255
+
+/* Codes for shuffling (see blosc_compress) */
+#define BLOSC_NOSHUFFLE   0  /* no shuffle */
+#define BLOSC_SHUFFLE     1  /* byte-wise shuffle */
+#define BLOSC_BITSHUFFLE  2  /* bit-wise shuffle */
+
+/* Codes for internal flags (see blosc_cbuffer_metainfo) */
+#define BLOSC_DOSHUFFLE    0x1  /* byte-wise shuffle */
+#define BLOSC_MEMCPYED     0x2  /* plain copy */
+#define BLOSC_DOBITSHUFFLE 0x4  /* bit-wise shuffle */
+
+/* Codes for the different compressors shipped with Blosc */
+#define BLOSC_BLOSCLZ   0
+#define BLOSC_LZ4       1
+#define BLOSC_LZ4HC     2
+#define BLOSC_SNAPPY    3
+#define BLOSC_ZLIB      4
+#define BLOSC_ZSTD      5
 // #include <stdlib.h>
 
 /**
@@ -445,4 +471,315 @@ blosc_internal_unshuffle(const size_t bytesoftype, const size_t blocksize,
 BLOSC_EXPORT int
 blosc_internal_bitunshuffle(const size_t bytesoftype, const size_t blocksize,
              const char *const _src, const char *_dest,
-             const char *_tmp);
\ No newline at end of file
+             const char *_tmp);
+
+/**
+  Per-architecture shuffle and bitshuffle routines used by the dispatching ones
+  above. They are internal to the static library and are bound only to force a
+  specific implementation for benchmarking and testing. The SSE2 and AVX2 ones
+  exist only on x86 and must not be called on hosts lacking the instructions.
+  The bitshuffle `size` is the number of elements and must be a multiple of 8.
+*/
+void
+blosc_internal_shuffle_generic(const size_t bytesoftype, const size_t blocksize,
+        const char *_src, const char *_dest);
+
+void
+blosc_internal_unshuffle_generic(const size_t bytesoftype, const size_t blocksize,
+          const char *_src, const char *_dest);
+
+void
+blosc_internal_shuffle_sse2(const size_t bytesoftype, const size_t blocksize,
+        const char *_src, const char *_dest);
+
+void
+blosc_internal_unshuffle_sse2(const size_t bytesoftype, const size_t blocksize,
+          const char *_src, const char *_dest);
+
+void
+blosc_internal_shuffle_avx2(const size_t bytesoftype, const size_t blocksize,
+        const char *_src, const char *_dest);
+
+void
+blosc_internal_unshuffle_avx2(const size_t bytesoftype, const size_t blocksize,
+          const char *_src, const char *_dest);
+
+int64_t
+blosc_internal_bshuf_trans_bit_elem_scal(const void *in, void *out,
+        const size_t size, const size_t elem_size, void *tmp_buf);
+
+int64_t
+blosc_internal_bshuf_untrans_bit_elem_scal(const void *in, void *out,
+        const size_t size, const size_t elem_size, void *tmp_buf);
+
+int64_t
+blosc_internal_bshuf_trans_bit_elem_sse2(const void *in, void *out,
+        const size_t size, const size_t elem_size, void *tmp_buf);
+
+int64_t
+blosc_internal_bshuf_untrans_bit_elem_sse2(const void *in, void *out,
+        const size_t size, const size_t elem_size, void *tmp_buf);
+
+int64_t
+blosc_internal_bshuf_trans_bit_elem_avx(const void *in, void *out,
+        const size_t size, const size_t elem_size, void *tmp_buf);
+
+int64_t
+blosc_internal_bshuf_untrans_bit_elem_avx(const void *in, void *out,
+        const size_t size, const size_t elem_size, void *tmp_buf);
+/*********************************************************************
+
+  Internal compression libraries (compiled into c-blosc).
+
+*********************************************************************/
+
+#define ZSTD_CONTENTSIZE_UNKNOWN (0ULL - 1)
+#define ZSTD_CONTENTSIZE_ERROR   (0ULL - 2)
+
+/**
+  `src` should point to the start of a ZSTD encoded frame.
+  `srcSize` must be at least as large as the frame header.
+  Returns the decompressed size of the `src` frame if known,
+  ZSTD_CONTENTSIZE_UNKNOWN if the size cannot be determined and
+  ZSTD_CONTENTSIZE_ERROR if an error occurred (e.g. invalid magic number).
+  */
+BLOSC_EXPORT unsigned long long ZSTD_getFrameContentSize(const void *src, size_t srcSize);
+
+#define LZ4_ACCELERATION_DEFAULT 1
+#define LZ4_ACCELERATION_MAX     65537
+#define LZ4HC_CLEVEL_MIN         3
+#define LZ4HC_CLEVEL_DEFAULT     9
+#define LZ4HC_CLEVEL_MAX         12
+
+/**
+  Compress `srcSize` bytes into an LZ4 block of at most `dstCapacity` bytes.
+  Larger `acceleration` values are faster and compress less, values below 1
+  are replaced by LZ4_ACCELERATION_DEFAULT. Returns the compressed size or 0
+  if the output does not fit.
+  */
+BLOSC_EXPORT int LZ4_compress_fast(const char *src, char *dst, int srcSize,
+                                   int dstCapacity, int acceleration);
+
+/**
+  Compress `srcSize` bytes into an LZ4 block with the high compression
+  algorithm. The output is decoded by the regular LZ4 decoder. `compressionLevel`
+  ranges from LZ4HC_CLEVEL_MIN to LZ4HC_CLEVEL_MAX. Returns the compressed size
+  or 0 if the output does not fit.
+  */
+BLOSC_EXPORT int LZ4_compress_HC(const char *src, char *dst, int srcSize,
+                                 int dstCapacity, int compressionLevel);
+
+/**
+  Decompress an LZ4 block, stopping as soon as `targetOutputSize` bytes
+  have been decoded. Returns the number of decoded bytes or a negative
+  value if the input is malformed.
+  */
+BLOSC_EXPORT int LZ4_decompress_safe_partial(const char *src, char *dst, int srcSize,
+                                             int targetOutputSize, int dstCapacity);
+
+/**
+  Decompress an LZ4 block that may reference `dictSize` bytes of previously
+  decoded data at `dictStart`, as produced for linked blocks of the LZ4 frame
+  format. Returns the number of decoded bytes or a negative value if the input
+  is malformed or does not fit into `dstCapacity`.
+  */
+BLOSC_EXPORT int LZ4_decompress_safe_usingDict(const char *src, char *dst, int srcSize,
+                                               int dstCapacity, const char *dictStart,
+                                               int dictSize);
+
+typedef enum {
+  SNAPPY_OK = 0,
+  SNAPPY_INVALID_INPUT = 1,
+  SNAPPY_BUFFER_TOO_SMALL = 2
+} snappy_status;
+
+/**
+  Compress `input_length` bytes into a raw Snappy block. `*compressed_length`
+  is the capacity of `compressed` on input, which must be at least
+  snappy_max_compressed_length(input_length), and the compressed size on output.
+  */
+BLOSC_EXPORT snappy_status snappy_compress(const char *input, size_t input_length,
+                                           char *compressed, size_t *compressed_length);
+
+/**
+  Decompress a raw Snappy block. `*uncompressed_length` is the capacity of
+  `uncompressed` on input and the decompressed size on output.
+  */
+BLOSC_EXPORT snappy_status snappy_uncompress(const char *compressed, size_t compressed_length,
+                                             char *uncompressed, size_t *uncompressed_length);
+
+/**
+  Maximum compressed size of `source_length` bytes.
+  */
+BLOSC_EXPORT size_t snappy_max_compressed_length(size_t source_length);
+
+/**
+  Read the decompressed size stored at the start of a raw Snappy block.
+  */
+BLOSC_EXPORT snappy_status snappy_uncompressed_length(const char *compressed,
+                                                      size_t compressed_length,
+                                                      size_t *result);
+
+#define Z_NO_FLUSH      0
+#define Z_FINISH        4
+
+#define Z_OK            0
+#define Z_STREAM_END    1
+#define Z_NEED_DICT     2
+#define Z_ERRNO        (-1)
+#define Z_STREAM_ERROR (-2)
+#define Z_DATA_ERROR   (-3)
+#define Z_MEM_ERROR    (-4)
+#define Z_BUF_ERROR    (-5)
+
+typedef void *(*alloc_func)(void *opaque, unsigned int items, unsigned int size);
+typedef void (*free_func)(void *opaque, void *address);
+
+typedef struct z_stream_s {
+    const unsigned char *next_in;
+    unsigned int avail_in;
+    unsigned long total_in;
+    unsigned char *next_out;
+    unsigned int avail_out;
+    unsigned long total_out;
+    const char *msg;
+    struct internal_state *state;
+    alloc_func zalloc;
+    free_func zfree;
+    void *opaque;
+    int data_type;
+    unsigned long adler;
+    unsigned long reserved;
+} z_stream;
+
+BLOSC_EXPORT const char *zlibVersion(void);
+
+BLOSC_EXPORT int inflateInit2_(z_stream *strm, int windowBits,
+                               const char *version, int stream_size);
+
+BLOSC_EXPORT int inflate(z_stream *strm, int flush);
+
+BLOSC_EXPORT int inflateEnd(z_stream *strm);
+
+BLOSC_EXPORT int inflateReset(z_stream *strm);
+
+#define Z_DEFLATED          8
+#define Z_DEFAULT_STRATEGY  0
+#define OS_UNKNOWN          255
+
+/*
+  gzip header information passed to and from zlib routines. See RFC 1952
+  for more details on the meanings of these fields.
+*/
+typedef struct gz_header_s {
+    int           text;       /* true if compressed data believed to be text */
+    unsigned long time;       /* modification time */
+    int           xflags;     /* extra flags (not used when writing a gzip file) */
+    int           os;         /* operating system */
+    unsigned char *extra;     /* pointer to extra field or Z_NULL if none */
+    unsigned int  extra_len;  /* extra field length (valid if extra != Z_NULL) */
+    unsigned int  extra_max;  /* space at extra (only when reading header) */
+    unsigned char *name;      /* pointer to zero-terminated file name or Z_NULL */
+    unsigned int  name_max;   /* space at name (only when reading header) */
+    unsigned char *comment;   /* pointer to zero-terminated comment or Z_NULL */
+    unsigned int  comm_max;   /* space at comment (only when reading header) */
+    int           hcrc;       /* true if there was or will be a header crc */
+    int           done;       /* true when done reading gzip header (not used
+                                 when writing a gzip file) */
+} gz_header;
+
+BLOSC_EXPORT int deflateInit2_(z_stream *strm, int level, int method,
+                               int windowBits, int memLevel, int strategy,
+                               const char *version, int stream_size);
+
+BLOSC_EXPORT int deflate(z_stream *strm, int flush);
+
+BLOSC_EXPORT int deflateEnd(z_stream *strm);
+
+/*
+  Provides gzip header information for when a gzip stream is requested by
+  deflateInit2(). The header is written on the first call of deflate()
+  and must remain valid until then.
+*/
+BLOSC_EXPORT int deflateSetHeader(z_stream *strm, gz_header *head);
+
+/*
+  Requests that gzip header information be stored in the provided gz_header
+  structure. Must be called after inflateInit2() or inflateReset() and before
+  any call of inflate(). `head->done` is set to 1 once the header is read.
+*/
+BLOSC_EXPORT int inflateGetHeader(z_stream *strm, gz_header *head);
+
+/* Zstandard streaming API */
+
+typedef struct ZSTD_CCtx_s ZSTD_CCtx;
+typedef struct ZSTD_DCtx_s ZSTD_DCtx;
+typedef ZSTD_CCtx ZSTD_CStream;
+typedef ZSTD_DCtx ZSTD_DStream;
+
+typedef struct ZSTD_inBuffer_s {
+  const void* src;    /**< start of input buffer */
+  size_t size;        /**< size of input buffer */
+  size_t pos;         /**< position where reading stopped. Will be updated. Necessarily 0 <= pos <= size */
+} ZSTD_inBuffer;
+
+typedef struct ZSTD_outBuffer_s {
+  void*  dst;         /**< start of output buffer */
+  size_t size;        /**< size of output buffer */
+  size_t pos;         /**< position where writing stopped. Will be updated. Necessarily 0 <= pos <= size */
+} ZSTD_outBuffer;
+
+BLOSC_EXPORT unsigned ZSTD_isError(size_t code);
+BLOSC_EXPORT const char* ZSTD_getErrorName(size_t code);
+BLOSC_EXPORT int ZSTD_getErrorCode(size_t functionResult);
+BLOSC_EXPORT int ZSTD_maxCLevel(void);
+
+BLOSC_EXPORT ZSTD_CStream* ZSTD_createCStream(void);
+BLOSC_EXPORT size_t ZSTD_freeCStream(ZSTD_CStream* zcs);
+BLOSC_EXPORT size_t ZSTD_initCStream(ZSTD_CStream* zcs, int compressionLevel);
+BLOSC_EXPORT size_t ZSTD_compressStream(ZSTD_CStream* zcs, ZSTD_outBuffer* output, ZSTD_inBuffer* input);
+BLOSC_EXPORT size_t ZSTD_flushStream(ZSTD_CStream* zcs, ZSTD_outBuffer* output);
+BLOSC_EXPORT size_t ZSTD_endStream(ZSTD_CStream* zcs, ZSTD_outBuffer* output);
+BLOSC_EXPORT size_t ZSTD_CStreamInSize(void);
+BLOSC_EXPORT size_t ZSTD_CStreamOutSize(void);
+
+BLOSC_EXPORT ZSTD_DStream* ZSTD_createDStream(void);
+BLOSC_EXPORT size_t ZSTD_freeDStream(ZSTD_DStream* zds);
+BLOSC_EXPORT size_t ZSTD_initDStream(ZSTD_DStream* zds);
+BLOSC_EXPORT size_t ZSTD_decompressStream(ZSTD_DStream* zds, ZSTD_outBuffer* output, ZSTD_inBuffer* input);
+BLOSC_EXPORT size_t ZSTD_DStreamInSize(void);
+BLOSC_EXPORT size_t ZSTD_DStreamOutSize(void);
+
+/* Zstandard dictionaries */
+
+typedef struct ZSTD_CDict_s ZSTD_CDict;
+typedef struct ZSTD_DDict_s ZSTD_DDict;
+
+BLOSC_EXPORT size_t ZSTD_compressBound(size_t srcSize);
+BLOSC_EXPORT ZSTD_CCtx* ZSTD_createCCtx(void);
+BLOSC_EXPORT size_t ZSTD_freeCCtx(ZSTD_CCtx* cctx);
+BLOSC_EXPORT ZSTD_DCtx* ZSTD_createDCtx(void);
+BLOSC_EXPORT size_t ZSTD_freeDCtx(ZSTD_DCtx* dctx);
+
+BLOSC_EXPORT ZSTD_CDict* ZSTD_createCDict(const void* dictBuffer, size_t dictSize, int compressionLevel);
+BLOSC_EXPORT size_t ZSTD_freeCDict(ZSTD_CDict* CDict);
+BLOSC_EXPORT size_t ZSTD_compress_usingCDict(ZSTD_CCtx* cctx, void* dst, size_t dstCapacity,
+                                             const void* src, size_t srcSize, const ZSTD_CDict* cdict);
+
+BLOSC_EXPORT ZSTD_DDict* ZSTD_createDDict(const void* dictBuffer, size_t dictSize);
+BLOSC_EXPORT size_t ZSTD_freeDDict(ZSTD_DDict* ddict);
+BLOSC_EXPORT size_t ZSTD_decompress_usingDDict(ZSTD_DCtx* dctx, void* dst, size_t dstCapacity,
+                                               const void* src, size_t srcSize, const ZSTD_DDict* ddict);
+
+/**
+  Train a dictionary from an array of samples. Samples must be stored
+  concatenated in a single flat buffer `samplesBuffer`, supplied with an
+  array of sizes `samplesSizes`, providing the size of each sample, in order.
+  Returns the size of the dictionary stored into `dictBuffer` (<= `dictBufferCapacity`)
+  or an error code, which can be tested with ZDICT_isError().
+  */
+BLOSC_EXPORT size_t ZDICT_trainFromBuffer(void* dictBuffer, size_t dictBufferCapacity,
+                                          const void* samplesBuffer,
+                                          const size_t* samplesSizes, unsigned nbSamples);
+BLOSC_EXPORT unsigned ZDICT_isError(size_t errorCode);
+BLOSC_EXPORT unsigned ZDICT_getDictID(const void* dictBuffer, size_t dictSize);
@@ -1,6 +1,69 @@
 // extern crate bindgen;
+extern crate cc;
 extern crate cmake;
 
+use std::path::{Path, PathBuf};
+
+/// CMake `DEACTIVATE_*` value for a codec enabled by the cargo feature `feature`.
+fn deactivate(feature: &str) -> &'static str {
+    let var = format!("CARGO_FEATURE_{}", feature.to_uppercase());
+    if std::env::var_os(var).is_some() {
+        "OFF"
+    } else {
+        "ON"
+    }
+}
+
+/// Files in `dir` with the extension `extension`.
+fn files_with_extension(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let msg = format!("Cannot read {}", dir.display());
+    std::fs::read_dir(dir)
+        .expect(&msg)
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+        .collect()
+}
+
+/// c-blosc only compiles the zstd `common`, `compress` and `decompress` sources.
+/// Build the dictionary builder behind `ZDICT_trainFromBuffer` from the same bundled
+/// zstd, so that `spreads_zstd_train_dictionary` and `spreads_zstd_dict_id` link.
+fn build_zstd_dict_builder() {
+    let complibs = Path::new("c-blosc/internal-complibs");
+    let zstd = std::fs::read_dir(complibs)
+        .expect("Cannot read c-blosc/internal-complibs")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("zstd"))
+        })
+        .expect("No bundled zstd in c-blosc/internal-complibs");
+    cc::Build::new()
+        .files(files_with_extension(&zstd.join("dictBuilder"), "c"))
+        .include(&zstd)
+        .include(zstd.join("common"))
+        .include(zstd.join("compress"))
+        .static_crt(true)
+        .warnings(false)
+        .compile("blosc_zdict");
+}
+
+/// Whether the c-blosc build tree `dir` holds an object of the AVX2 shuffle kernels.
+/// c-blosc's CMake script only compiles them when the C compiler can target AVX2.
+fn compiled_avx2(dir: &Path) -> bool {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .any(|path| {
+            let object = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("shuffle-avx2."));
+            object || (path.is_dir() && compiled_avx2(&path))
+        })
+}
+
 fn main() {
     // let bindings = bindgen::Builder::default()
     //     .header("blosc_wrapper.h")
@@ -36,10 +99,10 @@ fn main() {
         .define("BUILD_TESTS", "OFF")
         .define("BUILD_BENCHMARKS", "OFF")
         .define("DEACTIVATE_AVX2", "OFF")
-        .define("DEACTIVATE_LZ4", "OFF")
-        .define("DEACTIVATE_SNAPPY", "ON")
-        .define("DEACTIVATE_ZLIB", "OFF")
-        .define("DEACTIVATE_ZSTD", "OFF")
+        .define("DEACTIVATE_LZ4", deactivate("lz4"))
+        .define("DEACTIVATE_SNAPPY", deactivate("snappy"))
+        .define("DEACTIVATE_ZLIB", deactivate("zlib"))
+        .define("DEACTIVATE_ZSTD", deactivate("zstd"))
         .define("PREFER_EXTERNAL_LZ4", "OFF")
         .define("PREFER_EXTERNAL_SNAPPY", "OFF")
         .define("PREFER_EXTERNAL_ZLIB", "OFF")
@@ -57,6 +120,19 @@ fn main() {
 
     let dir = format!("{}/lib", dst.display());
 
+    // The AVX2 kernels are only bound, here and in dependents, when they were built.
+    println!("cargo:rustc-check-cfg=cfg(blosc_avx2)");
+    if compiled_avx2(&dst.join("build").join("blosc")) {
+        println!("cargo:rustc-cfg=blosc_avx2");
+        println!("cargo:avx2=1");
+    }
+
+    // Emits its link directive first, the static blosc library after it resolves the
+    // zstd symbols the dictionary builder uses.
+    if deactivate("zstd") == "OFF" {
+        build_zstd_dict_builder();
+    }
+
     let mut libname = "blosc";
 
     if cfg!(windows) {
@@ -69,4 +145,14 @@ fn main() {
     
     println!("cargo:rustc-link-search=native={}", dir);
     println!("cargo:rustc-link-lib={}", libname);
+
+    // The bundled Snappy is C++.
+    if deactivate("snappy") == "OFF" {
+        let target = std::env::var("TARGET").unwrap();
+        if target.contains("apple") {
+            println!("cargo:rustc-link-lib=c++");
+        } else if !target.contains("msvc") {
+            println!("cargo:rustc-link-lib=stdc++");
+        }
+    }
 }
@@ -1,5 +1,27 @@
 /* automatically generated by rust-bindgen */
 
+#![allow(non_camel_case_types)]
+#![allow(non_upper_case_globals)]
+#![allow(non_snake_case)]
+
+pub const BLOSC_VERSION_FORMAT: u32 = 2;
+pub const BLOSC_MIN_HEADER_LENGTH: u32 = 16;
+pub const BLOSC_MAX_OVERHEAD: u32 = 16;
+pub const BLOSC_MAX_BUFFERSIZE: u32 = 2147483631;
+pub const BLOSC_MAX_TYPESIZE: u32 = 255;
+pub const BLOSC_NOSHUFFLE: u32 = 0;
+pub const BLOSC_SHUFFLE: u32 = 1;
+pub const BLOSC_BITSHUFFLE: u32 = 2;
+pub const BLOSC_DOSHUFFLE: u32 = 1;
+pub const BLOSC_MEMCPYED: u32 = 2;
+pub const BLOSC_DOBITSHUFFLE: u32 = 4;
+pub const BLOSC_BLOSCLZ: u32 = 0;
+pub const BLOSC_LZ4: u32 = 1;
+pub const BLOSC_LZ4HC: u32 = 2;
+pub const BLOSC_SNAPPY: u32 = 3;
+pub const BLOSC_ZLIB: u32 = 4;
+pub const BLOSC_ZSTD: u32 = 5;
+
 extern "C" {
     #[doc = "Compress a block of data in the `src` buffer and returns the size of"]
     #[doc = "the compressed block.  The size of `src` buffer is specified by"]
@@ -173,6 +195,20 @@ extern "C" {
         numinternalthreads: libc::c_int,
     ) -> libc::c_int;
 }
+extern "C" {
+    #[doc = "Get `nitems` (of typesize size) in `src` buffer starting in `start`."]
+    #[doc = "The items are returned in `dest` buffer, which has to have enough"]
+    #[doc = "space for storing all items."]
+    #[doc = ""]
+    #[doc = "Returns the number of bytes copied to `dest` or a negative value if"]
+    #[doc = "some error happens."]
+    pub fn blosc_getitem(
+        src: *const libc::c_void,
+        start: libc::c_int,
+        nitems: libc::c_int,
+        dest: *mut libc::c_void,
+    ) -> libc::c_int;
+}
 extern "C" {
     #[doc = "Returns the current number of threads that are used for"]
     #[doc = "compression/decompression."]
@@ -213,6 +249,68 @@ extern "C" {
     #[doc = "This function should always succeed."]
     pub fn blosc_list_compressors() -> *const libc::c_char;
 }
+extern "C" {
+    #[doc = "Return information about a compressed buffer, namely the number of"]
+    #[doc = "uncompressed bytes (`nbytes`) and compressed (`cbytes`).  It also"]
+    #[doc = "returns the `blocksize` (which is used internally for doing the"]
+    #[doc = "compression by blocks)."]
+    #[doc = ""]
+    #[doc = "You only need to pass the first BLOSC_MIN_HEADER_LENGTH bytes of a"]
+    #[doc = "compressed buffer for this call to work."]
+    #[doc = ""]
+    #[doc = "If the format is not supported by the library, all output arguments will be"]
+    #[doc = "filled with zeros."]
+    pub fn blosc_cbuffer_sizes(
+        cbuffer: *const libc::c_void,
+        nbytes: *mut usize,
+        cbytes: *mut usize,
+        blocksize: *mut usize,
+    );
+}
+extern "C" {
+    #[doc = "Return meta-information about a compressed buffer, namely the type size"]
+    #[doc = "(`typesize`), as well as some internal `flags`."]
+    #[doc = ""]
+    #[doc = "The `flags` is a set of bits, where the used ones are:"]
+    #[doc = "* bit 0: whether the shuffle filter has been applied or not"]
+    #[doc = "* bit 1: whether the internal buffer is a pure memcpy or not"]
+    #[doc = "* bit 2: whether the bit shuffle filter has been applied or not"]
+    #[doc = ""]
+    #[doc = "You can use the `BLOSC_DOSHUFFLE`, `BLOSC_DOBITSHUFFLE` and"]
+    #[doc = "`BLOSC_MEMCPYED` symbols for extracting the interesting bits"]
+    #[doc = "(e.g. ``flags & BLOSC_DOSHUFFLE`` says whether the buffer is"]
+    #[doc = "byte-shuffled or not)."]
+    #[doc = ""]
+    #[doc = "You only need to pass the first BLOSC_MIN_HEADER_LENGTH bytes of a"]
+    #[doc = "compressed buffer for this call to work."]
+    #[doc = ""]
+    #[doc = "If the format is not supported by the library, all output arguments will be"]
+    #[doc = "filled with zeros."]
+    pub fn blosc_cbuffer_metainfo(
+        cbuffer: *const libc::c_void,
+        typesize: *mut usize,
+        flags: *mut libc::c_int,
+    );
+}
+extern "C" {
+    #[doc = "Return information about a compressed buffer, namely the internal"]
+    #[doc = "Blosc format version (`version`) and the format for the internal"]
+    #[doc = "compressor used (`compversion`)."]
+    #[doc = ""]
+    #[doc = "This function should always succeed."]
+    pub fn blosc_cbuffer_versions(
+        cbuffer: *const libc::c_void,
+        version: *mut libc::c_int,
+        compversion: *mut libc::c_int,
+    );
+}
+extern "C" {
+    #[doc = "Return the compressor library/format used in a compressed buffer."]
+    #[doc = ""]
+    #[doc = "This function should always succeed."]
+    pub fn blosc_cbuffer_complib(cbuffer: *const libc::c_void) -> *const libc::c_char;
+}
+#[cfg(feature = "lz4")]
 extern "C" {
     pub fn compress_lz4(
         input: *const libc::c_char,
@@ -222,6 +320,7 @@ extern "C" {
         clevel: libc::c_int,
     ) -> libc::c_int;
 }
+#[cfg(feature = "lz4")]
 extern "C" {
     pub fn decompress_lz4(
         input: *const libc::c_char,
@@ -230,6 +329,7 @@ extern "C" {
         maxout: usize,
     ) -> libc::c_int;
 }
+#[cfg(feature = "zstd")]
 extern "C" {
     pub fn compress_zstd(
         input: *const libc::c_char,
@@ -239,6 +339,7 @@ extern "C" {
         clevel: libc::c_int,
     ) -> libc::c_int;
 }
+#[cfg(feature = "zstd")]
 extern "C" {
     pub fn decompress_zstd(
         input: *const libc::c_char,
@@ -247,6 +348,7 @@ extern "C" {
         maxout: usize,
     ) -> libc::c_int;
 }
+#[cfg(feature = "zlib")]
 extern "C" {
     pub fn compress_zlib(
         input: *const libc::c_char,
@@ -256,6 +358,7 @@ extern "C" {
         clevel: libc::c_int,
     ) -> libc::c_int;
 }
+#[cfg(feature = "zlib")]
 extern "C" {
     pub fn decompress_zlib(
         input: *const libc::c_char,
@@ -264,6 +367,7 @@ extern "C" {
         maxout: usize,
     ) -> libc::c_int;
 }
+#[cfg(feature = "zlib")]
 extern "C" {
     pub fn compress_deflate(
         input: *const libc::c_char,
@@ -273,6 +377,7 @@ extern "C" {
         clevel: libc::c_int,
     ) -> libc::c_int;
 }
+#[cfg(feature = "zlib")]
 extern "C" {
     pub fn decompress_deflate(
         input: *const libc::c_char,
@@ -281,6 +386,7 @@ extern "C" {
         maxout: usize,
     ) -> libc::c_int;
 }
+#[cfg(feature = "zlib")]
 extern "C" {
     pub fn compress_gzip(
         input: *const libc::c_char,
@@ -290,6 +396,7 @@ extern "C" {
         clevel: libc::c_int,
     ) -> libc::c_int;
 }
+#[cfg(feature = "zlib")]
 extern "C" {
     pub fn decompress_gzip(
         input: *const libc::c_char,
@@ -365,3 +472,608 @@ extern "C" {
         _tmp: *const libc::c_char,
     ) -> libc::c_int;
 }
+extern "C" {
+    #[doc = "Generic (non-hardware-accelerated) shuffle routine."]
+    pub fn blosc_internal_shuffle_generic(
+        bytesoftype: usize,
+        blocksize: usize,
+        _src: *const libc::c_char,
+        _dest: *const libc::c_char,
+    );
+}
+extern "C" {
+    #[doc = "Generic (non-hardware-accelerated) unshuffle routine."]
+    pub fn blosc_internal_unshuffle_generic(
+        bytesoftype: usize,
+        blocksize: usize,
+        _src: *const libc::c_char,
+        _dest: *const libc::c_char,
+    );
+}
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+extern "C" {
+    #[doc = "SSE2-accelerated shuffle routine."]
+    pub fn blosc_internal_shuffle_sse2(
+        bytesoftype: usize,
+        blocksize: usize,
+        _src: *const libc::c_char,
+        _dest: *const libc::c_char,
+    );
+}
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+extern "C" {
+    #[doc = "SSE2-accelerated unshuffle routine."]
+    pub fn blosc_internal_unshuffle_sse2(
+        bytesoftype: usize,
+        blocksize: usize,
+        _src: *const libc::c_char,
+        _dest: *const libc::c_char,
+    );
+}
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+extern "C" {
+    #[doc = "AVX2-accelerated shuffle routine."]
+    pub fn blosc_internal_shuffle_avx2(
+        bytesoftype: usize,
+        blocksize: usize,
+        _src: *const libc::c_char,
+        _dest: *const libc::c_char,
+    );
+}
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+extern "C" {
+    #[doc = "AVX2-accelerated unshuffle routine."]
+    pub fn blosc_internal_unshuffle_avx2(
+        bytesoftype: usize,
+        blocksize: usize,
+        _src: *const libc::c_char,
+        _dest: *const libc::c_char,
+    );
+}
+extern "C" {
+    #[doc = "Transpose bits within elements, scalar version. `size` is the number of"]
+    #[doc = "elements and must be a multiple of 8."]
+    pub fn blosc_internal_bshuf_trans_bit_elem_scal(
+        in_: *const libc::c_void,
+        out: *mut libc::c_void,
+        size: usize,
+        elem_size: usize,
+        tmp_buf: *mut libc::c_void,
+    ) -> i64;
+}
+extern "C" {
+    #[doc = "Reverses `blosc_internal_bshuf_trans_bit_elem_scal`."]
+    pub fn blosc_internal_bshuf_untrans_bit_elem_scal(
+        in_: *const libc::c_void,
+        out: *mut libc::c_void,
+        size: usize,
+        elem_size: usize,
+        tmp_buf: *mut libc::c_void,
+    ) -> i64;
+}
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+extern "C" {
+    #[doc = "Transpose bits within elements, SSE2 version."]
+    pub fn blosc_internal_bshuf_trans_bit_elem_sse2(
+        in_: *const libc::c_void,
+        out: *mut libc::c_void,
+        size: usize,
+        elem_size: usize,
+        tmp_buf: *mut libc::c_void,
+    ) -> i64;
+}
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+extern "C" {
+    #[doc = "Reverses `blosc_internal_bshuf_trans_bit_elem_sse2`."]
+    pub fn blosc_internal_bshuf_untrans_bit_elem_sse2(
+        in_: *const libc::c_void,
+        out: *mut libc::c_void,
+        size: usize,
+        elem_size: usize,
+        tmp_buf: *mut libc::c_void,
+    ) -> i64;
+}
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+extern "C" {
+    #[doc = "Transpose bits within elements, AVX2 version."]
+    pub fn blosc_internal_bshuf_trans_bit_elem_avx(
+        in_: *const libc::c_void,
+        out: *mut libc::c_void,
+        size: usize,
+        elem_size: usize,
+        tmp_buf: *mut libc::c_void,
+    ) -> i64;
+}
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+extern "C" {
+    #[doc = "Reverses `blosc_internal_bshuf_trans_bit_elem_avx`."]
+    pub fn blosc_internal_bshuf_untrans_bit_elem_avx(
+        in_: *const libc::c_void,
+        out: *mut libc::c_void,
+        size: usize,
+        elem_size: usize,
+        tmp_buf: *mut libc::c_void,
+    ) -> i64;
+}
+pub const ZSTD_CONTENTSIZE_UNKNOWN: u64 = 18446744073709551615;
+pub const ZSTD_CONTENTSIZE_ERROR: u64 = 18446744073709551614;
+#[cfg(feature = "zstd")]
+extern "C" {
+    #[doc = "`src` should point to the start of a ZSTD encoded frame."]
+    #[doc = "`srcSize` must be at least as large as the frame header."]
+    #[doc = "Returns the decompressed size of the `src` frame if known,"]
+    #[doc = "ZSTD_CONTENTSIZE_UNKNOWN if the size cannot be determined and"]
+    #[doc = "ZSTD_CONTENTSIZE_ERROR if an error occurred (e.g. invalid magic number)."]
+    pub fn ZSTD_getFrameContentSize(src: *const libc::c_void, srcSize: usize) -> u64;
+}
+pub const LZ4_ACCELERATION_DEFAULT: u32 = 1;
+pub const LZ4_ACCELERATION_MAX: u32 = 65537;
+pub const LZ4HC_CLEVEL_MIN: u32 = 3;
+pub const LZ4HC_CLEVEL_DEFAULT: u32 = 9;
+pub const LZ4HC_CLEVEL_MAX: u32 = 12;
+#[cfg(feature = "lz4")]
+extern "C" {
+    #[doc = "Compress `srcSize` bytes into an LZ4 block of at most `dstCapacity` bytes."]
+    #[doc = "Larger `acceleration` values are faster and compress less, values below 1"]
+    #[doc = "are replaced by LZ4_ACCELERATION_DEFAULT. Returns the compressed size or 0"]
+    #[doc = "if the output does not fit."]
+    pub fn LZ4_compress_fast(
+        src: *const libc::c_char,
+        dst: *mut libc::c_char,
+        srcSize: libc::c_int,
+        dstCapacity: libc::c_int,
+        acceleration: libc::c_int,
+    ) -> libc::c_int;
+}
+#[cfg(feature = "lz4")]
+extern "C" {
+    #[doc = "Compress `srcSize` bytes into an LZ4 block with the high compression"]
+    #[doc = "algorithm. The output is decoded by the regular LZ4 decoder. `compressionLevel`"]
+    #[doc = "ranges from LZ4HC_CLEVEL_MIN to LZ4HC_CLEVEL_MAX. Returns the compressed size"]
+    #[doc = "or 0 if the output does not fit."]
+    pub fn LZ4_compress_HC(
+        src: *const libc::c_char,
+        dst: *mut libc::c_char,
+        srcSize: libc::c_int,
+        dstCapacity: libc::c_int,
+        compressionLevel: libc::c_int,
+    ) -> libc::c_int;
+}
+#[cfg(feature = "lz4")]
+extern "C" {
+    #[doc = "Decompress an LZ4 block, stopping as soon as `targetOutputSize` bytes"]
+    #[doc = "have been decoded. Returns the number of decoded bytes or a negative"]
+    #[doc = "value if the input is malformed."]
+    pub fn LZ4_decompress_safe_partial(
+        src: *const libc::c_char,
+        dst: *mut libc::c_char,
+        srcSize: libc::c_int,
+        targetOutputSize: libc::c_int,
+        dstCapacity: libc::c_int,
+    ) -> libc::c_int;
+}
+#[cfg(feature = "lz4")]
+extern "C" {
+    #[doc = "Decompress an LZ4 block that may reference `dictSize` bytes of previously"]
+    #[doc = "decoded data at `dictStart`, as produced for linked blocks of the LZ4 frame"]
+    #[doc = "format. Returns the number of decoded bytes or a negative value if the input"]
+    #[doc = "is malformed or does not fit into `dstCapacity`."]
+    pub fn LZ4_decompress_safe_usingDict(
+        src: *const libc::c_char,
+        dst: *mut libc::c_char,
+        srcSize: libc::c_int,
+        dstCapacity: libc::c_int,
+        dictStart: *const libc::c_char,
+        dictSize: libc::c_int,
+    ) -> libc::c_int;
+}
+pub const SNAPPY_OK: snappy_status = 0;
+pub const SNAPPY_INVALID_INPUT: snappy_status = 1;
+pub const SNAPPY_BUFFER_TOO_SMALL: snappy_status = 2;
+pub type snappy_status = libc::c_int;
+#[cfg(feature = "snappy")]
+extern "C" {
+    #[doc = "Compress `input_length` bytes into a raw Snappy block. `*compressed_length`"]
+    #[doc = "is the capacity of `compressed` on input, which must be at least"]
+    #[doc = "snappy_max_compressed_length(input_length), and the compressed size on output."]
+    pub fn snappy_compress(
+        input: *const libc::c_char,
+        input_length: usize,
+        compressed: *mut libc::c_char,
+        compressed_length: *mut usize,
+    ) -> snappy_status;
+}
+#[cfg(feature = "snappy")]
+extern "C" {
+    #[doc = "Decompress a raw Snappy block. `*uncompressed_length` is the capacity of"]
+    #[doc = "`uncompressed` on input and the decompressed size on output."]
+    pub fn snappy_uncompress(
+        compressed: *const libc::c_char,
+        compressed_length: usize,
+        uncompressed: *mut libc::c_char,
+        uncompressed_length: *mut usize,
+    ) -> snappy_status;
+}
+#[cfg(feature = "snappy")]
+extern "C" {
+    #[doc = "Maximum compressed size of `source_length` bytes."]
+    pub fn snappy_max_compressed_length(source_length: usize) -> usize;
+}
+#[cfg(feature = "snappy")]
+extern "C" {
+    #[doc = "Read the decompressed size stored at the start of a raw Snappy block."]
+    pub fn snappy_uncompressed_length(
+        compressed: *const libc::c_char,
+        compressed_length: usize,
+        result: *mut usize,
+    ) -> snappy_status;
+}
+pub const Z_NO_FLUSH: u32 = 0;
+pub const Z_FINISH: u32 = 4;
+pub const Z_OK: u32 = 0;
+pub const Z_STREAM_END: u32 = 1;
+pub const Z_NEED_DICT: u32 = 2;
+pub const Z_ERRNO: i32 = -1;
+pub const Z_STREAM_ERROR: i32 = -2;
+pub const Z_DATA_ERROR: i32 = -3;
+pub const Z_MEM_ERROR: i32 = -4;
+pub const Z_BUF_ERROR: i32 = -5;
+pub type alloc_func = ::std::option::Option<
+    unsafe extern "C" fn(
+        opaque: *mut libc::c_void,
+        items: libc::c_uint,
+        size: libc::c_uint,
+    ) -> *mut libc::c_void,
+>;
+pub type free_func = ::std::option::Option<
+    unsafe extern "C" fn(opaque: *mut libc::c_void, address: *mut libc::c_void),
+>;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct internal_state {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct z_stream_s {
+    pub next_in: *const libc::c_uchar,
+    pub avail_in: libc::c_uint,
+    pub total_in: libc::c_ulong,
+    pub next_out: *mut libc::c_uchar,
+    pub avail_out: libc::c_uint,
+    pub total_out: libc::c_ulong,
+    pub msg: *const libc::c_char,
+    pub state: *mut internal_state,
+    pub zalloc: alloc_func,
+    pub zfree: free_func,
+    pub opaque: *mut libc::c_void,
+    pub data_type: libc::c_int,
+    pub adler: libc::c_ulong,
+    pub reserved: libc::c_ulong,
+}
+pub type z_stream = z_stream_s;
+#[cfg(feature = "zlib")]
+extern "C" {
+    pub fn zlibVersion() -> *const libc::c_char;
+}
+#[cfg(feature = "zlib")]
+extern "C" {
+    pub fn inflateInit2_(
+        strm: *mut z_stream,
+        windowBits: libc::c_int,
+        version: *const libc::c_char,
+        stream_size: libc::c_int,
+    ) -> libc::c_int;
+}
+#[cfg(feature = "zlib")]
+extern "C" {
+    pub fn inflate(strm: *mut z_stream, flush: libc::c_int) -> libc::c_int;
+}
+#[cfg(feature = "zlib")]
+extern "C" {
+    pub fn inflateEnd(strm: *mut z_stream) -> libc::c_int;
+}
+#[cfg(feature = "zlib")]
+extern "C" {
+    pub fn inflateReset(strm: *mut z_stream) -> libc::c_int;
+}
+pub const Z_DEFLATED: u32 = 8;
+pub const Z_DEFAULT_STRATEGY: u32 = 0;
+pub const OS_UNKNOWN: u32 = 255;
+#[doc = "gzip header information passed to and from zlib routines. See RFC 1952"]
+#[doc = "for more details on the meanings of these fields."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct gz_header_s {
+    #[doc = "true if compressed data believed to be text"]
+    pub text: libc::c_int,
+    #[doc = "modification time"]
+    pub time: libc::c_ulong,
+    #[doc = "extra flags (not used when writing a gzip file)"]
+    pub xflags: libc::c_int,
+    #[doc = "operating system"]
+    pub os: libc::c_int,
+    #[doc = "pointer to extra field or Z_NULL if none"]
+    pub extra: *mut libc::c_uchar,
+    #[doc = "extra field length (valid if extra != Z_NULL)"]
+    pub extra_len: libc::c_uint,
+    #[doc = "space at extra (only when reading header)"]
+    pub extra_max: libc::c_uint,
+    #[doc = "pointer to zero-terminated file name or Z_NULL"]
+    pub name: *mut libc::c_uchar,
+    #[doc = "space at name (only when reading header)"]
+    pub name_max: libc::c_uint,
+    #[doc = "pointer to zero-terminated comment or Z_NULL"]
+    pub comment: *mut libc::c_uchar,
+    #[doc = "space at comment (only when reading header)"]
+    pub comm_max: libc::c_uint,
+    #[doc = "true if there was or will be a header crc"]
+    pub hcrc: libc::c_int,
+    #[doc = "true when done reading gzip header (not used when writing a gzip file)"]
+    pub done: libc::c_int,
+}
+pub type gz_header = gz_header_s;
+#[cfg(feature = "zlib")]
+extern "C" {
+    pub fn deflateInit2_(
+        strm: *mut z_stream,
+        level: libc::c_int,
+        method: libc::c_int,
+        windowBits: libc::c_int,
+        memLevel: libc::c_int,
+        strategy: libc::c_int,
+        version: *const libc::c_char,
+        stream_size: libc::c_int,
+    ) -> libc::c_int;
+}
+#[cfg(feature = "zlib")]
+extern "C" {
+    pub fn deflate(strm: *mut z_stream, flush: libc::c_int) -> libc::c_int;
+}
+#[cfg(feature = "zlib")]
+extern "C" {
+    pub fn deflateEnd(strm: *mut z_stream) -> libc::c_int;
+}
+#[cfg(feature = "zlib")]
+extern "C" {
+    #[doc = "Provides gzip header information for when a gzip stream is requested by"]
+    #[doc = "deflateInit2(). The header is written on the first call of deflate()"]
+    #[doc = "and must remain valid until then."]
+    pub fn deflateSetHeader(strm: *mut z_stream, head: *mut gz_header) -> libc::c_int;
+}
+#[cfg(feature = "zlib")]
+extern "C" {
+    #[doc = "Requests that gzip header information be stored in the provided gz_header"]
+    #[doc = "structure. Must be called after inflateInit2() or inflateReset() and before"]
+    #[doc = "any call of inflate(). `head->done` is set to 1 once the header is read."]
+    pub fn inflateGetHeader(strm: *mut z_stream, head: *mut gz_header) -> libc::c_int;
+}
+pub const ZSTD_error_no_error: libc::c_int = 0;
+pub const ZSTD_error_GENERIC: libc::c_int = 1;
+pub const ZSTD_error_prefix_unknown: libc::c_int = 10;
+pub const ZSTD_error_version_unsupported: libc::c_int = 12;
+pub const ZSTD_error_frameParameter_unsupported: libc::c_int = 14;
+pub const ZSTD_error_frameParameter_windowTooLarge: libc::c_int = 16;
+pub const ZSTD_error_corruption_detected: libc::c_int = 20;
+pub const ZSTD_error_checksum_wrong: libc::c_int = 22;
+pub const ZSTD_error_dictionary_corrupted: libc::c_int = 30;
+pub const ZSTD_error_dictionary_wrong: libc::c_int = 32;
+pub const ZSTD_error_dictionaryCreation_failed: libc::c_int = 34;
+pub const ZSTD_error_parameter_unsupported: libc::c_int = 40;
+pub const ZSTD_error_parameter_outOfBound: libc::c_int = 42;
+pub const ZSTD_error_memory_allocation: libc::c_int = 64;
+pub const ZSTD_error_dstSize_tooSmall: libc::c_int = 70;
+pub const ZSTD_error_srcSize_wrong: libc::c_int = 72;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ZSTD_CCtx_s {
+    _unused: [u8; 0],
+}
+pub type ZSTD_CCtx = ZSTD_CCtx_s;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ZSTD_DCtx_s {
+    _unused: [u8; 0],
+}
+pub type ZSTD_DCtx = ZSTD_DCtx_s;
+pub type ZSTD_CStream = ZSTD_CCtx;
+pub type ZSTD_DStream = ZSTD_DCtx;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ZSTD_inBuffer_s {
+    #[doc = "< start of input buffer"]
+    pub src: *const libc::c_void,
+    #[doc = "< size of input buffer"]
+    pub size: usize,
+    #[doc = "< position where reading stopped. Will be updated. Necessarily 0 <= pos <= size"]
+    pub pos: usize,
+}
+pub type ZSTD_inBuffer = ZSTD_inBuffer_s;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ZSTD_outBuffer_s {
+    #[doc = "< start of output buffer"]
+    pub dst: *mut libc::c_void,
+    #[doc = "< size of output buffer"]
+    pub size: usize,
+    #[doc = "< position where writing stopped. Will be updated. Necessarily 0 <= pos <= size"]
+    pub pos: usize,
+}
+pub type ZSTD_outBuffer = ZSTD_outBuffer_s;
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_isError(code: usize) -> libc::c_uint;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_getErrorName(code: usize) -> *const libc::c_char;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_getErrorCode(functionResult: usize) -> libc::c_int;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_maxCLevel() -> libc::c_int;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_createCStream() -> *mut ZSTD_CStream;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_freeCStream(zcs: *mut ZSTD_CStream) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_initCStream(zcs: *mut ZSTD_CStream, compressionLevel: libc::c_int) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_compressStream(
+        zcs: *mut ZSTD_CStream,
+        output: *mut ZSTD_outBuffer,
+        input: *mut ZSTD_inBuffer,
+    ) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_flushStream(zcs: *mut ZSTD_CStream, output: *mut ZSTD_outBuffer) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_endStream(zcs: *mut ZSTD_CStream, output: *mut ZSTD_outBuffer) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_CStreamInSize() -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_CStreamOutSize() -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_createDStream() -> *mut ZSTD_DStream;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_freeDStream(zds: *mut ZSTD_DStream) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_initDStream(zds: *mut ZSTD_DStream) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_decompressStream(
+        zds: *mut ZSTD_DStream,
+        output: *mut ZSTD_outBuffer,
+        input: *mut ZSTD_inBuffer,
+    ) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_DStreamInSize() -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_DStreamOutSize() -> usize;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ZSTD_CDict_s {
+    _unused: [u8; 0],
+}
+pub type ZSTD_CDict = ZSTD_CDict_s;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ZSTD_DDict_s {
+    _unused: [u8; 0],
+}
+pub type ZSTD_DDict = ZSTD_DDict_s;
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_compressBound(srcSize: usize) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_createCCtx() -> *mut ZSTD_CCtx;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_freeCCtx(cctx: *mut ZSTD_CCtx) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_createDCtx() -> *mut ZSTD_DCtx;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_freeDCtx(dctx: *mut ZSTD_DCtx) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_createCDict(
+        dictBuffer: *const libc::c_void,
+        dictSize: usize,
+        compressionLevel: libc::c_int,
+    ) -> *mut ZSTD_CDict;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_freeCDict(CDict: *mut ZSTD_CDict) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_compress_usingCDict(
+        cctx: *mut ZSTD_CCtx,
+        dst: *mut libc::c_void,
+        dstCapacity: usize,
+        src: *const libc::c_void,
+        srcSize: usize,
+        cdict: *const ZSTD_CDict,
+    ) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_createDDict(dictBuffer: *const libc::c_void, dictSize: usize) -> *mut ZSTD_DDict;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_freeDDict(ddict: *mut ZSTD_DDict) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZSTD_decompress_usingDDict(
+        dctx: *mut ZSTD_DCtx,
+        dst: *mut libc::c_void,
+        dstCapacity: usize,
+        src: *const libc::c_void,
+        srcSize: usize,
+        ddict: *const ZSTD_DDict,
+    ) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    #[doc = "Train a dictionary from an array of samples. Samples must be stored"]
+    #[doc = "concatenated in a single flat buffer `samplesBuffer`, supplied with an"]
+    #[doc = "array of sizes `samplesSizes`, providing the size of each sample, in order."]
+    #[doc = "Returns the size of the dictionary stored into `dictBuffer` (<= `dictBufferCapacity`)"]
+    #[doc = "or an error code, which can be tested with ZDICT_isError()."]
+    pub fn ZDICT_trainFromBuffer(
+        dictBuffer: *mut libc::c_void,
+        dictBufferCapacity: usize,
+        samplesBuffer: *const libc::c_void,
+        samplesSizes: *const usize,
+        nbSamples: libc::c_uint,
+    ) -> usize;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZDICT_isError(errorCode: usize) -> libc::c_uint;
+}
+#[cfg(feature = "zstd")]
+extern "C" {
+    pub fn ZDICT_getDictID(dictBuffer: *const libc::c_void, dictSize: usize) -> libc::c_uint;
+}
@@ -1,6 +1,796 @@
+// The exports stay safe `extern "C" fn`s like the first ones, they check the raw
+// pointers they get from C before building slices from them.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 extern crate libc;
 extern crate spreads_blosc_sys;
 
+use std::fmt;
+use std::ops::RangeInclusive;
+
+pub mod auto;
+pub mod bitpack;
+pub mod blosc;
+pub mod buffer;
+pub mod chunked;
+pub mod delta;
+pub mod dispatch;
+pub mod envelope;
+pub mod gorilla;
+#[cfg(feature = "zlib")]
+pub mod gzip;
+#[cfg(feature = "lz4")]
+pub mod lz4frame;
+#[cfg(feature = "snappy")]
+pub mod snappyframe;
+#[cfg(test)]
+mod test_data;
+mod xxhash;
+#[cfg(feature = "zlib")]
+mod zlib;
+#[cfg(feature = "zstd")]
+pub mod zstd;
+
+/// Codecs exposed through the `spreads_compress_*`/`spreads_decompress_*` exports.
+/// The discriminants are stable ids used by the exports that take a codec argument.
+/// Every codec has a cargo feature, see `Codec::is_compiled`.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Lz4 = 1,
+    Zstd = 2,
+    Zlib = 3,
+    Deflate = 4,
+    Gzip = 5,
+    /// LZ4 high compression: slower to compress, same block format and decoding
+    /// speed as `Lz4`, so that either codec decompresses the output of the other.
+    Lz4Hc = 6,
+    /// Raw Snappy blocks, see the `snappyframe` module for the framing format.
+    Snappy = 7,
+}
+
+/// Errors returned by the safe compression API. The discriminants are stable and are
+/// returned as negative codes from every `spreads_compress_*`/`spreads_decompress_*` export.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionError {
+    /// The output buffer is too small to hold the result, grow it and retry.
+    BufferTooSmall = -1,
+    /// The compressed input is malformed or truncated.
+    CorruptInput = -2,
+    /// The codec is unknown or not compiled into this build.
+    UnsupportedCodec = -3,
+    /// The compression level is out of the range accepted by the codec.
+    InvalidLevel = -4,
+    /// A buffer length does not fit into the `int` return value of the C API.
+    LengthOverflow = -5,
+    /// The codec failed for a reason not covered above. This should never happen.
+    Internal = -6,
+    /// An argument other than the level is out of range, e.g. a zero type size.
+    InvalidArgument = -7,
+    /// The decompressed data exceeds the caller limit, see `Codec::decompress_with_limit`.
+    OutputLimitExceeded = -8,
+    /// The checksum of a checksummed envelope does not match its contents.
+    ChecksumMismatch = -9,
+}
+
+impl CompressionError {
+    /// Stable negative code of this error.
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Converts a negative code back to the error, `None` for unknown codes.
+    pub fn from_code(code: i32) -> Option<CompressionError> {
+        match code {
+            -1 => Some(CompressionError::BufferTooSmall),
+            -2 => Some(CompressionError::CorruptInput),
+            -3 => Some(CompressionError::UnsupportedCodec),
+            -4 => Some(CompressionError::InvalidLevel),
+            -5 => Some(CompressionError::LengthOverflow),
+            -6 => Some(CompressionError::Internal),
+            -7 => Some(CompressionError::InvalidArgument),
+            -8 => Some(CompressionError::OutputLimitExceeded),
+            -9 => Some(CompressionError::ChecksumMismatch),
+            _ => None,
+        }
+    }
+
+    /// Nul-terminated description, suitable for returning over FFI.
+    fn message_with_nul(self) -> &'static str {
+        match self {
+            CompressionError::BufferTooSmall => "output buffer is too small\0",
+            CompressionError::CorruptInput => "compressed input is corrupt or truncated\0",
+            CompressionError::UnsupportedCodec => "codec is not supported\0",
+            CompressionError::InvalidLevel => "compression level is out of range\0",
+            CompressionError::LengthOverflow => "buffer length overflows the return type\0",
+            CompressionError::Internal => "internal codec error\0",
+            CompressionError::InvalidArgument => "argument is out of range\0",
+            CompressionError::OutputLimitExceeded => "output exceeds the size limit\0",
+            CompressionError::ChecksumMismatch => "checksum does not match the data\0",
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        let message = self.message_with_nul();
+        &message[..message.len() - 1]
+    }
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for CompressionError {}
+
+impl From<CompressionError> for std::io::Error {
+    fn from(err: CompressionError) -> Self {
+        let kind = match err {
+            CompressionError::CorruptInput | CompressionError::ChecksumMismatch => {
+                std::io::ErrorKind::InvalidData
+            }
+            CompressionError::InvalidLevel | CompressionError::InvalidArgument => {
+                std::io::ErrorKind::InvalidInput
+            }
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+/// Largest buffer length the `int`-returning codec wrappers can handle.
+const MAX_INPUT_LENGTH: usize = i32::MAX as usize;
+
+/// LZ4_MAX_INPUT_SIZE
+const LZ4_MAX_INPUT_SIZE: usize = 0x7E00_0000;
+
+type CompressFn = unsafe extern "C" fn(
+    *const libc::c_char,
+    usize,
+    *mut libc::c_char,
+    usize,
+    libc::c_int,
+) -> libc::c_int;
+
+type DecompressFn =
+    unsafe extern "C" fn(*const libc::c_char, usize, *mut libc::c_char, usize) -> libc::c_int;
+
+/// `CompressFn` over `LZ4_compress_HC`, which takes `int` lengths. Levels 0 to 9 map
+/// onto the native LZ4HC levels 3 to 12, so 6 is the LZ4 default of 9 and 7 to 9 are
+/// the much slower optimal parsing.
+#[cfg(feature = "lz4")]
+unsafe extern "C" fn compress_lz4hc(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    clevel: libc::c_int,
+) -> libc::c_int {
+    spreads_blosc_sys::LZ4_compress_HC(
+        input,
+        output,
+        input_length as libc::c_int,
+        maxout as libc::c_int,
+        spreads_blosc_sys::LZ4HC_CLEVEL_MIN as libc::c_int + clevel,
+    )
+}
+
+/// `CompressFn` over `snappy_compress`, which has no levels. Snappy refuses outputs
+/// below its worst case even when the result would fit, so smaller outputs are
+/// compressed into a scratch buffer first.
+#[cfg(feature = "snappy")]
+unsafe extern "C" fn compress_snappy(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    _clevel: libc::c_int,
+) -> libc::c_int {
+    let bound = spreads_blosc_sys::snappy_max_compressed_length(input_length);
+    if maxout >= bound {
+        let mut len = maxout;
+        return match spreads_blosc_sys::snappy_compress(input, input_length, output, &mut len) {
+            spreads_blosc_sys::SNAPPY_OK => len as libc::c_int,
+            _ => -1,
+        };
+    }
+    let mut scratch = vec![0u8; bound];
+    let mut len = bound;
+    let scratch_ptr = scratch.as_mut_ptr() as *mut libc::c_char;
+    match spreads_blosc_sys::snappy_compress(input, input_length, scratch_ptr, &mut len) {
+        spreads_blosc_sys::SNAPPY_OK if len <= maxout => {
+            std::ptr::copy_nonoverlapping(scratch_ptr, output, len);
+            len as libc::c_int
+        }
+        spreads_blosc_sys::SNAPPY_OK => 0,
+        _ => -1,
+    }
+}
+
+/// `DecompressFn` over `snappy_uncompress`.
+#[cfg(feature = "snappy")]
+unsafe extern "C" fn decompress_snappy(
+    input: *const libc::c_char,
+    compressed_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+) -> libc::c_int {
+    let mut len = maxout;
+    match spreads_blosc_sys::snappy_uncompress(input, compressed_length, output, &mut len) {
+        spreads_blosc_sys::SNAPPY_OK => len as libc::c_int,
+        _ => -1,
+    }
+}
+
+impl Codec {
+    /// Stable id of this codec.
+    pub fn id(self) -> i32 {
+        self as i32
+    }
+
+    /// Looks up a codec by its stable id. Codecs that are not compiled into this build
+    /// are `UnsupportedCodec`.
+    pub fn from_id(id: i32) -> Result<Codec, CompressionError> {
+        let codec = match id {
+            1 => Codec::Lz4,
+            2 => Codec::Zstd,
+            3 => Codec::Zlib,
+            4 => Codec::Deflate,
+            5 => Codec::Gzip,
+            6 => Codec::Lz4Hc,
+            7 => Codec::Snappy,
+            _ => return Err(CompressionError::UnsupportedCodec),
+        };
+        if !codec.is_compiled() {
+            return Err(CompressionError::UnsupportedCodec);
+        }
+        Ok(codec)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Lz4 => "lz4",
+            Codec::Zstd => "zstd",
+            Codec::Zlib => "zlib",
+            Codec::Deflate => "deflate",
+            Codec::Gzip => "gzip",
+            Codec::Lz4Hc => "lz4hc",
+            Codec::Snappy => "snappy",
+        }
+    }
+
+    /// Whether the cargo feature of the codec is enabled. The other methods of a codec
+    /// that is not compiled in fail with `UnsupportedCodec`.
+    pub fn is_compiled(self) -> bool {
+        match self {
+            Codec::Lz4 | Codec::Lz4Hc => cfg!(feature = "lz4"),
+            Codec::Zstd => cfg!(feature = "zstd"),
+            Codec::Zlib | Codec::Deflate | Codec::Gzip => cfg!(feature = "zlib"),
+            Codec::Snappy => cfg!(feature = "snappy"),
+        }
+    }
+
+    fn compress_fn(self) -> Result<CompressFn, CompressionError> {
+        match self {
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 => Ok(spreads_blosc_sys::compress_lz4),
+            #[cfg(feature = "lz4")]
+            Codec::Lz4Hc => Ok(compress_lz4hc),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Ok(spreads_blosc_sys::compress_zstd),
+            #[cfg(feature = "zlib")]
+            Codec::Zlib => Ok(spreads_blosc_sys::compress_zlib),
+            #[cfg(feature = "zlib")]
+            Codec::Deflate => Ok(spreads_blosc_sys::compress_deflate),
+            #[cfg(feature = "zlib")]
+            Codec::Gzip => Ok(spreads_blosc_sys::compress_gzip),
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => Ok(compress_snappy),
+            #[cfg(not(all(
+                feature = "lz4",
+                feature = "zstd",
+                feature = "zlib",
+                feature = "snappy"
+            )))]
+            _ => Err(CompressionError::UnsupportedCodec),
+        }
+    }
+
+    fn decompress_fn(self) -> Result<DecompressFn, CompressionError> {
+        match self {
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 | Codec::Lz4Hc => Ok(spreads_blosc_sys::decompress_lz4),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Ok(spreads_blosc_sys::decompress_zstd),
+            #[cfg(feature = "zlib")]
+            Codec::Zlib => Ok(spreads_blosc_sys::decompress_zlib),
+            #[cfg(feature = "zlib")]
+            Codec::Deflate => Ok(spreads_blosc_sys::decompress_deflate),
+            #[cfg(feature = "zlib")]
+            Codec::Gzip => Ok(spreads_blosc_sys::decompress_gzip),
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => Ok(decompress_snappy),
+            #[cfg(not(all(
+                feature = "lz4",
+                feature = "zstd",
+                feature = "zlib",
+                feature = "snappy"
+            )))]
+            _ => Err(CompressionError::UnsupportedCodec),
+        }
+    }
+
+    /// Compression levels accepted by the codec. All codecs follow the Blosc convention
+    /// of 0 (no or minimal compression) to 9 (maximum compression), which `Snappy`
+    /// accepts and ignores and `Lz4Hc` maps onto the native LZ4HC levels 3 to 12. Use
+    /// `lz4_compress_fast` to trade ratio for speed below the `Lz4` levels.
+    pub fn levels(self) -> RangeInclusive<i32> {
+        0..=9
+    }
+
+    /// Largest input length the codec accepts.
+    fn max_input_len(self) -> usize {
+        match self {
+            Codec::Lz4 | Codec::Lz4Hc => LZ4_MAX_INPUT_SIZE,
+            _ => MAX_INPUT_LENGTH,
+        }
+    }
+
+    /// Worst-case compressed size of `input_len` bytes, i.e. an output buffer of this
+    /// size is always large enough for `compress` to succeed.
+    pub fn compress_bound(self, input_len: usize) -> Result<usize, CompressionError> {
+        if input_len > self.max_input_len() {
+            return Err(CompressionError::LengthOverflow);
+        }
+        let bound = match self {
+            // LZ4_COMPRESSBOUND
+            Codec::Lz4 | Codec::Lz4Hc => input_len + input_len / 255 + 16,
+            // ZSTD_COMPRESSBOUND
+            Codec::Zstd => {
+                let small_input_margin = if input_len < (128 << 10) {
+                    ((128 << 10) - input_len) >> 11
+                } else {
+                    0
+                };
+                input_len + (input_len >> 8) + small_input_margin
+            }
+            // Conservative deflateBound that holds for any window and memory level, plus
+            // the format wrapper: 2 bytes header + 4 bytes Adler-32 for zlib, 10 bytes
+            // header + 8 bytes trailer for gzip.
+            Codec::Zlib | Codec::Deflate | Codec::Gzip => {
+                let wrapper = match self {
+                    Codec::Zlib => 6,
+                    Codec::Gzip => 18,
+                    _ => 0,
+                };
+                input_len + ((input_len + 7) >> 3) + ((input_len + 63) >> 6) + 5 + wrapper
+            }
+            // snappy_max_compressed_length
+            Codec::Snappy => 32 + input_len + input_len / 6,
+        };
+        // The codecs cannot report more than an `int` worth of output.
+        if bound > MAX_INPUT_LENGTH {
+            return Err(CompressionError::LengthOverflow);
+        }
+        Ok(bound)
+    }
+
+    /// Upper bound of the size that `compressed_len` bytes of valid input decompress
+    /// to, from the densest encoding of each format. Declared sizes above it come from
+    /// corrupt or hostile input.
+    pub(crate) fn max_decompressed_len(self, compressed_len: usize) -> usize {
+        let ratio = match self {
+            // A run of 0xFF length bytes adds 255 bytes per byte.
+            Codec::Lz4 | Codec::Lz4Hc => 255,
+            // 3 bytes copy tags of 64 bytes.
+            Codec::Snappy => 22,
+            Codec::Zlib | Codec::Deflate | Codec::Gzip => 1032,
+            // 4 bytes RLE blocks of 128 KB.
+            Codec::Zstd => 32 * 1024,
+        };
+        compressed_len.saturating_mul(ratio)
+    }
+
+    /// The C API reports lengths as `int`, so larger outputs are capped: the codecs
+    /// never write more than they can report.
+    fn clamp_output(output: &mut [u8]) -> &mut [u8] {
+        let len = output.len().min(MAX_INPUT_LENGTH);
+        &mut output[..len]
+    }
+
+    /// Compresses `input` into `output` and returns the number of bytes written.
+    pub fn compress(
+        self,
+        input: &[u8],
+        output: &mut [u8],
+        level: i32,
+    ) -> Result<usize, CompressionError> {
+        let compress_fn = self.compress_fn()?;
+        if !self.levels().contains(&level) {
+            return Err(CompressionError::InvalidLevel);
+        }
+        if input.len() > self.max_input_len() {
+            return Err(CompressionError::LengthOverflow);
+        }
+        let output = Self::clamp_output(output);
+        let compressed = unsafe {
+            compress_fn(
+                input.as_ptr() as *const libc::c_char,
+                input.len(),
+                output.as_mut_ptr() as *mut libc::c_char,
+                output.len(),
+                level,
+            )
+        };
+        match compressed {
+            // The wrappers return 0 when the result does not fit into `output`,
+            // which is also how incompressible input manifests itself.
+            0 => Err(CompressionError::BufferTooSmall),
+            n if n < 0 => Err(CompressionError::Internal),
+            n => Ok(n as usize),
+        }
+    }
+
+    /// Decompresses `input` into `output` and returns the number of bytes written.
+    pub fn decompress(self, input: &[u8], output: &mut [u8]) -> Result<usize, CompressionError> {
+        let decompress_fn = self.decompress_fn()?;
+        if input.len() > MAX_INPUT_LENGTH {
+            return Err(CompressionError::LengthOverflow);
+        }
+        let output = Self::clamp_output(output);
+        if input.is_empty() {
+            return Err(CompressionError::CorruptInput);
+        }
+        #[cfg(feature = "zlib")]
+        {
+            if self == Codec::Gzip {
+                // The wrapper stops after the first member, while `gzip -d` decodes
+                // concatenated members.
+                return zlib::inflate_exact(input, output, zlib::WINDOW_BITS_GZIP);
+            }
+        }
+        let decompressed = unsafe {
+            decompress_fn(
+                input.as_ptr() as *const libc::c_char,
+                input.len(),
+                output.as_mut_ptr() as *mut libc::c_char,
+                output.len(),
+            )
+        };
+        if decompressed > 0 {
+            return Ok(decompressed as usize);
+        }
+        #[cfg(feature = "lz4")]
+        {
+            // `LZ4_decompress_safe` returns 0 only for an empty block.
+            if decompressed == 0 && (self == Codec::Lz4 || self == Codec::Lz4Hc) {
+                return Ok(0);
+            }
+        }
+        // The wrappers return zero or a negative value for both a short output buffer
+        // and malformed input, as well as zero for an empty payload. Ask the codec
+        // directly to tell these cases apart.
+        #[cfg(any(
+            feature = "lz4",
+            feature = "zstd",
+            feature = "zlib",
+            feature = "snappy"
+        ))]
+        {
+            self.decompress_fallback(input, output)
+        }
+        #[cfg(not(any(
+            feature = "lz4",
+            feature = "zstd",
+            feature = "zlib",
+            feature = "snappy"
+        )))]
+        {
+            Err(CompressionError::UnsupportedCodec)
+        }
+    }
+
+    #[cfg(any(
+        feature = "lz4",
+        feature = "zstd",
+        feature = "zlib",
+        feature = "snappy"
+    ))]
+    fn decompress_fallback(
+        self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, CompressionError> {
+        match self {
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 | Codec::Lz4Hc => {
+                // The block was rejected, either because it is corrupt or because it
+                // does not fit. A partial decode that stops short of the output
+                // capacity means that the block is truncated or corrupt.
+                let capacity = output.len() as libc::c_int;
+                let decoded = unsafe {
+                    spreads_blosc_sys::LZ4_decompress_safe_partial(
+                        input.as_ptr() as *const libc::c_char,
+                        output.as_mut_ptr() as *mut libc::c_char,
+                        input.len() as libc::c_int,
+                        capacity,
+                        capacity,
+                    )
+                };
+                if decoded == capacity {
+                    Err(CompressionError::BufferTooSmall)
+                } else {
+                    Err(CompressionError::CorruptInput)
+                }
+            }
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => {
+                let content_size = unsafe {
+                    spreads_blosc_sys::ZSTD_getFrameContentSize(
+                        input.as_ptr() as *const libc::c_void,
+                        input.len(),
+                    )
+                };
+                match content_size {
+                    spreads_blosc_sys::ZSTD_CONTENTSIZE_ERROR
+                    | spreads_blosc_sys::ZSTD_CONTENTSIZE_UNKNOWN => {
+                        Err(CompressionError::CorruptInput)
+                    }
+                    0 => Ok(0),
+                    n if n > output.len() as u64 => Err(CompressionError::BufferTooSmall),
+                    _ => Err(CompressionError::CorruptInput),
+                }
+            }
+            #[cfg(feature = "zlib")]
+            Codec::Zlib => zlib::inflate_exact(input, output, zlib::WINDOW_BITS_ZLIB),
+            #[cfg(feature = "zlib")]
+            Codec::Deflate => zlib::inflate_exact(input, output, zlib::WINDOW_BITS_DEFLATE),
+            #[cfg(feature = "zlib")]
+            Codec::Gzip => zlib::inflate_exact(input, output, zlib::WINDOW_BITS_GZIP),
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => {
+                let mut len = 0;
+                let status = unsafe {
+                    spreads_blosc_sys::snappy_uncompressed_length(
+                        input.as_ptr() as *const libc::c_char,
+                        input.len(),
+                        &mut len,
+                    )
+                };
+                match (status, len) {
+                    (spreads_blosc_sys::SNAPPY_OK, 0) => Ok(0),
+                    (spreads_blosc_sys::SNAPPY_OK, n) if n > output.len() => {
+                        Err(CompressionError::BufferTooSmall)
+                    }
+                    _ => Err(CompressionError::CorruptInput),
+                }
+            }
+            #[cfg(not(all(
+                feature = "lz4",
+                feature = "zstd",
+                feature = "zlib",
+                feature = "snappy"
+            )))]
+            _ => Err(CompressionError::UnsupportedCodec),
+        }
+    }
+
+    /// Compresses `input` into a new vector that is exactly as long as the compressed data.
+    pub fn compress_to_vec(self, input: &[u8], level: i32) -> Result<Vec<u8>, CompressionError> {
+        let mut output = vec![0u8; self.compress_bound(input.len())?];
+        let len = self.compress(input, &mut output, level)?;
+        output.truncate(len);
+        output.shrink_to_fit();
+        Ok(output)
+    }
+
+    /// Decompresses `input` whose decompressed size is unknown into a buffer allocated
+    /// with `spreads_mem_malloc`, growing it as needed. Fails with `OutputLimitExceeded`
+    /// instead of allocating more than `limit` bytes.
+    pub fn decompress_with_limit(
+        self,
+        input: &[u8],
+        limit: usize,
+    ) -> Result<buffer::MemBuffer, CompressionError> {
+        buffer::decompress(self, input, limit)
+    }
+
+    /// Decompresses `input` into a new vector. `max_len` is the upper bound of the
+    /// decompressed size, the returned vector is truncated to the actual size.
+    pub fn decompress_to_vec(
+        self,
+        input: &[u8],
+        max_len: usize,
+    ) -> Result<Vec<u8>, CompressionError> {
+        let mut output = vec![0u8; max_len];
+        let len = self.decompress(input, &mut output)?;
+        output.truncate(len);
+        output.shrink_to_fit();
+        Ok(output)
+    }
+}
+
+/// Compresses `input` into an LZ4 block with an explicit LZ4 `acceleration` from 1
+/// (the LZ4 default) to 65537. Each step trades a few percent of ratio for speed. The
+/// output is decompressed by `Codec::Lz4`.
+#[cfg(feature = "lz4")]
+pub fn lz4_compress_fast(
+    input: &[u8],
+    output: &mut [u8],
+    acceleration: i32,
+) -> Result<usize, CompressionError> {
+    if acceleration < 1 || acceleration > spreads_blosc_sys::LZ4_ACCELERATION_MAX as i32 {
+        return Err(CompressionError::InvalidArgument);
+    }
+    if input.len() > LZ4_MAX_INPUT_SIZE {
+        return Err(CompressionError::LengthOverflow);
+    }
+    let output = Codec::clamp_output(output);
+    let compressed = unsafe {
+        spreads_blosc_sys::LZ4_compress_fast(
+            input.as_ptr() as *const libc::c_char,
+            output.as_mut_ptr() as *mut libc::c_char,
+            input.len() as libc::c_int,
+            output.len() as libc::c_int,
+            acceleration,
+        )
+    };
+    match compressed {
+        0 => Err(CompressionError::BufferTooSmall),
+        n if n < 0 => Err(CompressionError::Internal),
+        n => Ok(n as usize),
+    }
+}
+
+/// Builds a slice from FFI arguments, tolerating a null pointer for an empty buffer.
+/// A null pointer with a nonzero length fails with `InvalidArgument`.
+unsafe fn ffi_slice<'a>(
+    ptr: *const libc::c_char,
+    len: usize,
+) -> Result<&'a [u8], CompressionError> {
+    if len == 0 {
+        Ok(&[])
+    } else if ptr.is_null() {
+        Err(CompressionError::InvalidArgument)
+    } else {
+        Ok(std::slice::from_raw_parts(ptr as *const u8, len))
+    }
+}
+
+unsafe fn ffi_slice_mut<'a>(
+    ptr: *mut libc::c_char,
+    len: usize,
+) -> Result<&'a mut [u8], CompressionError> {
+    if len == 0 {
+        Ok(&mut [])
+    } else if ptr.is_null() {
+        Err(CompressionError::InvalidArgument)
+    } else {
+        Ok(std::slice::from_raw_parts_mut(ptr as *mut u8, len))
+    }
+}
+
+/// Builds the input and output slices of an FFI call, see `ffi_slice`.
+unsafe fn ffi_buffers<'a>(
+    src: *const libc::c_char,
+    src_len: usize,
+    dest: *mut libc::c_char,
+    dest_len: usize,
+) -> Result<(&'a [u8], &'a mut [u8]), CompressionError> {
+    Ok((ffi_slice(src, src_len)?, ffi_slice_mut(dest, dest_len)?))
+}
+
+/// Converts a result to the C contract: the number of written bytes on success or
+/// a negative `CompressionError` code.
+fn to_c_result(result: Result<usize, CompressionError>) -> libc::c_int {
+    match result {
+        Ok(len) => len as libc::c_int,
+        Err(err) => err.code(),
+    }
+}
+
+#[cfg(any(
+    feature = "lz4",
+    feature = "zstd",
+    feature = "zlib",
+    feature = "snappy"
+))]
+fn ffi_compress(
+    codec: Codec,
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    clevel: libc::c_int,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(input, input_length, output, maxout) }
+        .and_then(|(input, output)| codec.compress(input, output, clevel));
+    to_c_result(result)
+}
+
+#[cfg(any(
+    feature = "lz4",
+    feature = "zstd",
+    feature = "zlib",
+    feature = "snappy"
+))]
+fn ffi_decompress(
+    codec: Codec,
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(input, input_length, output, maxout) }
+        .and_then(|(input, output)| codec.decompress(input, output));
+    to_c_result(result)
+}
+
+/// Returns a static nul-terminated description of an error code returned by the
+/// `spreads_compress_*`/`spreads_decompress_*` functions, or NULL for unknown codes.
+#[no_mangle]
+pub extern "C" fn spreads_compression_error_message(code: libc::c_int) -> *const libc::c_char {
+    match CompressionError::from_code(code) {
+        Some(err) => err.message_with_nul().as_ptr() as *const libc::c_char,
+        None => std::ptr::null(),
+    }
+}
+
+/// Returns the worst-case compressed size of `input_length` bytes for the codec with
+/// the given id, or a negative error code for an unknown codec or too large input.
+/// A destination buffer of this size always suffices for `spreads_compress_*`.
+#[no_mangle]
+pub extern "C" fn spreads_compress_bound(codec: libc::c_int, input_length: usize) -> isize {
+    match Codec::from_id(codec).and_then(|codec| codec.compress_bound(input_length)) {
+        Ok(bound) => bound as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// All codecs in the order of their ids.
+const CODECS: [Codec; 7] = [
+    Codec::Lz4,
+    Codec::Zstd,
+    Codec::Zlib,
+    Codec::Deflate,
+    Codec::Gzip,
+    Codec::Lz4Hc,
+    Codec::Snappy,
+];
+
+/// Names of the codecs compiled into this build: the `Codec` names in the order of
+/// their ids, followed by the compressors only available inside Blosc containers.
+pub fn compiled_codecs() -> Vec<&'static str> {
+    let blosc_only = [blosc::BloscCompressor::BloscLz];
+    CODECS
+        .iter()
+        .filter(|codec| codec.is_compiled())
+        .map(|codec| codec.name())
+        .chain(
+            blosc_only
+                .iter()
+                .filter(|compressor| compressor.is_compiled())
+                .map(|compressor| compressor.name()),
+        )
+        .collect()
+}
+
+/// Returns a static nul-terminated, comma-separated list of the codecs compiled into
+/// this build, see `compiled_codecs`, e.g. "lz4,zstd,zlib,deflate,gzip,lz4hc,blosclz".
+#[no_mangle]
+pub extern "C" fn spreads_list_codecs() -> *const libc::c_char {
+    static LIST: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    let list = LIST.get_or_init(|| {
+        let mut list = compiled_codecs().join(",");
+        list.push('\0');
+        list
+    });
+    list.as_ptr() as *const libc::c_char
+}
+
+#[cfg(feature = "lz4")]
 #[no_mangle]
 pub extern "C" fn spreads_compress_lz4(
     input: *const libc::c_char,
@@ -9,9 +799,10 @@ pub extern "C" fn spreads_compress_lz4(
     maxout: usize,
     clevel: libc::c_int,
 ) -> libc::c_int {
-    return unsafe { spreads_blosc_sys::compress_lz4(input, input_length, output, maxout, clevel) };
+    ffi_compress(Codec::Lz4, input, input_length, output, maxout, clevel)
 }
 
+#[cfg(feature = "lz4")]
 #[no_mangle]
 pub extern "C" fn spreads_decompress_lz4(
     input: *const libc::c_char,
@@ -19,9 +810,10 @@ pub extern "C" fn spreads_decompress_lz4(
     output: *mut libc::c_char,
     maxout: usize,
 ) -> libc::c_int {
-    return unsafe { spreads_blosc_sys::decompress_lz4(input, input_length, output, maxout) };
+    ffi_decompress(Codec::Lz4, input, input_length, output, maxout)
 }
 
+#[cfg(feature = "zstd")]
 #[no_mangle]
 pub extern "C" fn spreads_compress_zstd(
     input: *const libc::c_char,
@@ -30,11 +822,10 @@ pub extern "C" fn spreads_compress_zstd(
     maxout: usize,
     clevel: libc::c_int,
 ) -> libc::c_int {
-    return unsafe {
-        spreads_blosc_sys::compress_zstd(input, input_length, output, maxout, clevel)
-    };
+    ffi_compress(Codec::Zstd, input, input_length, output, maxout, clevel)
 }
 
+#[cfg(feature = "zstd")]
 #[no_mangle]
 pub extern "C" fn spreads_decompress_zstd(
     input: *const libc::c_char,
@@ -42,9 +833,10 @@ pub extern "C" fn spreads_decompress_zstd(
     output: *mut libc::c_char,
     maxout: usize,
 ) -> libc::c_int {
-    return unsafe { spreads_blosc_sys::decompress_zstd(input, input_length, output, maxout) };
+    ffi_decompress(Codec::Zstd, input, input_length, output, maxout)
 }
 
+#[cfg(feature = "zlib")]
 #[no_mangle]
 pub extern "C" fn spreads_compress_zlib(
     input: *const libc::c_char,
@@ -53,11 +845,10 @@ pub extern "C" fn spreads_compress_zlib(
     maxout: usize,
     clevel: libc::c_int,
 ) -> libc::c_int {
-    return unsafe {
-        spreads_blosc_sys::compress_zlib(input, input_length, output, maxout, clevel)
-    };
+    ffi_compress(Codec::Zlib, input, input_length, output, maxout, clevel)
 }
 
+#[cfg(feature = "zlib")]
 #[no_mangle]
 pub extern "C" fn spreads_decompress_zlib(
     input: *const libc::c_char,
@@ -65,9 +856,10 @@ pub extern "C" fn spreads_decompress_zlib(
     output: *mut libc::c_char,
     maxout: usize,
 ) -> libc::c_int {
-    return unsafe { spreads_blosc_sys::decompress_zlib(input, input_length, output, maxout) };
+    ffi_decompress(Codec::Zlib, input, input_length, output, maxout)
 }
 
+#[cfg(feature = "zlib")]
 #[no_mangle]
 pub extern "C" fn spreads_compress_deflate(
     input: *const libc::c_char,
@@ -76,11 +868,10 @@ pub extern "C" fn spreads_compress_deflate(
     maxout: usize,
     clevel: libc::c_int,
 ) -> libc::c_int {
-    return unsafe {
-        spreads_blosc_sys::compress_deflate(input, input_length, output, maxout, clevel)
-    };
+    ffi_compress(Codec::Deflate, input, input_length, output, maxout, clevel)
 }
 
+#[cfg(feature = "zlib")]
 #[no_mangle]
 pub extern "C" fn spreads_decompress_deflate(
     input: *const libc::c_char,
@@ -88,9 +879,10 @@ pub extern "C" fn spreads_decompress_deflate(
     output: *mut libc::c_char,
     maxout: usize,
 ) -> libc::c_int {
-    return unsafe { spreads_blosc_sys::decompress_deflate(input, input_length, output, maxout) };
+    ffi_decompress(Codec::Deflate, input, input_length, output, maxout)
 }
 
+#[cfg(feature = "zlib")]
 #[no_mangle]
 pub extern "C" fn spreads_compress_gzip(
     input: *const libc::c_char,
@@ -99,11 +891,10 @@ pub extern "C" fn spreads_compress_gzip(
     maxout: usize,
     clevel: libc::c_int,
 ) -> libc::c_int {
-    return unsafe {
-        spreads_blosc_sys::compress_gzip(input, input_length, output, maxout, clevel)
-    };
+    ffi_compress(Codec::Gzip, input, input_length, output, maxout, clevel)
 }
 
+#[cfg(feature = "zlib")]
 #[no_mangle]
 pub extern "C" fn spreads_decompress_gzip(
     input: *const libc::c_char,
@@ -111,9 +902,76 @@ pub extern "C" fn spreads_decompress_gzip(
     output: *mut libc::c_char,
     maxout: usize,
 ) -> libc::c_int {
-    return unsafe { spreads_blosc_sys::decompress_gzip(input, input_length, output, maxout) };
+    ffi_decompress(Codec::Gzip, input, input_length, output, maxout)
+}
+
+/// LZ4HC with levels from 0 to 9, see `Codec::levels`. The output is also decompressed by
+/// `spreads_decompress_lz4`.
+#[cfg(feature = "lz4")]
+#[no_mangle]
+pub extern "C" fn spreads_compress_lz4hc(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    clevel: libc::c_int,
+) -> libc::c_int {
+    ffi_compress(Codec::Lz4Hc, input, input_length, output, maxout, clevel)
 }
 
+#[cfg(feature = "lz4")]
+#[no_mangle]
+pub extern "C" fn spreads_decompress_lz4hc(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+) -> libc::c_int {
+    ffi_decompress(Codec::Lz4Hc, input, input_length, output, maxout)
+}
+
+/// Raw Snappy block; the level is ignored. See `spreads_snappyframe_compress` for the
+/// framing format.
+#[cfg(feature = "snappy")]
+#[no_mangle]
+pub extern "C" fn spreads_compress_snappy(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    clevel: libc::c_int,
+) -> libc::c_int {
+    ffi_compress(Codec::Snappy, input, input_length, output, maxout, clevel)
+}
+
+#[cfg(feature = "snappy")]
+#[no_mangle]
+pub extern "C" fn spreads_decompress_snappy(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+) -> libc::c_int {
+    ffi_decompress(Codec::Snappy, input, input_length, output, maxout)
+}
+
+/// Fast LZ4 with an explicit acceleration from 1 to 65537, see `lz4_compress_fast`.
+/// The output is decompressed by `spreads_decompress_lz4`.
+#[cfg(feature = "lz4")]
+#[no_mangle]
+pub extern "C" fn spreads_compress_lz4_fast(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    acceleration: libc::c_int,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(input, input_length, output, maxout) }
+        .and_then(|(input, output)| lz4_compress_fast(input, output, acceleration));
+    to_c_result(result)
+}
+
+/// Unchecked Blosc byte shuffle, see `spreads_shuffle_checked` for the validated version.
 #[no_mangle]
 pub extern "C" fn spreads_shuffle(
     bytesoftype: usize,
@@ -121,9 +979,10 @@ pub extern "C" fn spreads_shuffle(
     src: *const libc::c_char,
     dest: *const libc::c_char,
 ) {
-    return unsafe { spreads_blosc_sys::blosc_internal_shuffle(bytesoftype, blocksize, src, dest) };
+    run_unchecked_shuffle(dispatch::shuffle_fn(), bytesoftype, blocksize, src, dest)
 }
 
+/// Unchecked Blosc byte unshuffle, see `spreads_unshuffle_checked`.
 #[no_mangle]
 pub extern "C" fn spreads_unshuffle(
     bytesoftype: usize,
@@ -131,9 +990,321 @@ pub extern "C" fn spreads_unshuffle(
     src: *const libc::c_char,
     dest: *const libc::c_char,
 ) {
-    return unsafe {
-        spreads_blosc_sys::blosc_internal_unshuffle(bytesoftype, blocksize, src, dest)
+    run_unchecked_shuffle(dispatch::unshuffle_fn(), bytesoftype, blocksize, src, dest)
+}
+
+type ShuffleFn = unsafe extern "C" fn(usize, usize, *const libc::c_char, *const libc::c_char);
+
+fn run_unchecked_shuffle(
+    filter: ShuffleFn,
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *const libc::c_char,
+) {
+    unsafe { filter(bytesoftype, blocksize, src, dest) }
+}
+
+fn check_shuffle(typesize: usize, len: usize) -> Result<usize, CompressionError> {
+    if typesize == 0 {
+        return Err(CompressionError::InvalidArgument);
+    }
+    if len > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    // Length of the whole elements, the tail bytes are left as they are.
+    Ok(len - len % typesize)
+}
+
+fn run_shuffle(
+    filter: ShuffleFn,
+    typesize: usize,
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    let body = check_shuffle(typesize, src.len())?;
+    if dest.len() < src.len() {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    if body > 0 {
+        unsafe {
+            filter(
+                typesize,
+                body,
+                src.as_ptr() as *const libc::c_char,
+                dest.as_mut_ptr() as *const libc::c_char,
+            )
+        };
+    }
+    dest[body..src.len()].copy_from_slice(&src[body..]);
+    Ok(src.len())
+}
+
+fn run_shuffle_in_place(
+    filter: ShuffleFn,
+    typesize: usize,
+    data: &mut [u8],
+) -> Result<usize, CompressionError> {
+    let body = check_shuffle(typesize, data.len())?;
+    if body == 0 || typesize == 1 {
+        return Ok(data.len());
+    }
+    let scratch = crate::mem_allocation::spreads_mem_malloc(body) as *mut u8;
+    if scratch.is_null() {
+        return Err(CompressionError::Internal);
+    }
+    unsafe {
+        std::ptr::copy_nonoverlapping(data.as_ptr(), scratch, body);
+        filter(
+            typesize,
+            body,
+            scratch as *const libc::c_char,
+            data.as_mut_ptr() as *const libc::c_char,
+        );
+    }
+    crate::mem_allocation::spreads_mem_free(scratch as _);
+    Ok(data.len())
+}
+
+/// Byte shuffle of `src` into `dest`: the byte `j` of every element of `typesize`
+/// bytes is grouped with the bytes `j` of the other elements. The `src.len() %
+/// typesize` trailing bytes do not form an element and are copied unchanged after the
+/// shuffled ones. Returns `src.len()`.
+pub fn shuffle(typesize: usize, src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+    run_shuffle(dispatch::shuffle_fn(), typesize, src, dest)
+}
+
+/// Reverts `shuffle`.
+pub fn unshuffle(typesize: usize, src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+    run_shuffle(dispatch::unshuffle_fn(), typesize, src, dest)
+}
+
+/// `shuffle` that overwrites `data`, using an internal scratch buffer.
+pub fn shuffle_in_place(typesize: usize, data: &mut [u8]) -> Result<usize, CompressionError> {
+    run_shuffle_in_place(dispatch::shuffle_fn(), typesize, data)
+}
+
+/// Reverts `shuffle_in_place`.
+pub fn unshuffle_in_place(typesize: usize, data: &mut [u8]) -> Result<usize, CompressionError> {
+    run_shuffle_in_place(dispatch::unshuffle_fn(), typesize, data)
+}
+
+fn ffi_shuffle(
+    filter: ShuffleFn,
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    let result = if std::ptr::eq(src, dest) {
+        unsafe { ffi_slice_mut(dest, blocksize) }
+            .and_then(|buffer| run_shuffle_in_place(filter, bytesoftype, buffer))
+    } else {
+        unsafe { ffi_buffers(src, blocksize, dest, blocksize) }
+            .and_then(|(src, dest)| run_shuffle(filter, bytesoftype, src, dest))
+    };
+    to_c_result(result)
+}
+
+/// Validated byte shuffle of `blocksize` bytes from `src` to `dest`. Trailing bytes
+/// that do not form a whole element of `bytesoftype` bytes are copied unchanged.
+/// `src` and `dest` must not overlap unless they are equal, in which case the shuffle
+/// runs in place. Returns `blocksize` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_shuffle_checked(
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    ffi_shuffle(dispatch::shuffle_fn(), bytesoftype, blocksize, src, dest)
+}
+
+/// Reverts `spreads_shuffle_checked`, with the same argument requirements.
+#[no_mangle]
+pub extern "C" fn spreads_unshuffle_checked(
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    ffi_shuffle(dispatch::unshuffle_fn(), bytesoftype, blocksize, src, dest)
+}
+
+/// Shuffles `blocksize` bytes of `data` in place, see `spreads_shuffle_checked`.
+#[no_mangle]
+pub extern "C" fn spreads_shuffle_in_place(
+    bytesoftype: usize,
+    blocksize: usize,
+    data: *mut libc::c_char,
+) -> libc::c_int {
+    ffi_shuffle(dispatch::shuffle_fn(), bytesoftype, blocksize, data, data)
+}
+
+/// Reverts `spreads_shuffle_in_place`.
+#[no_mangle]
+pub extern "C" fn spreads_unshuffle_in_place(
+    bytesoftype: usize,
+    blocksize: usize,
+    data: *mut libc::c_char,
+) -> libc::c_int {
+    ffi_shuffle(dispatch::unshuffle_fn(), bytesoftype, blocksize, data, data)
+}
+
+type BitshuffleFn = unsafe extern "C" fn(
+    usize,
+    usize,
+    *const libc::c_char,
+    *const libc::c_char,
+    *const libc::c_char,
+) -> libc::c_int;
+
+/// Bitshuffle transposes bits within groups of 8 elements, so the buffer must hold a
+/// whole number of such groups.
+fn run_bitshuffle(
+    filter: BitshuffleFn,
+    typesize: usize,
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    if typesize == 0
+        || !src.len().is_multiple_of(typesize)
+        || !(src.len() / typesize).is_multiple_of(8)
+    {
+        return Err(CompressionError::InvalidArgument);
+    }
+    if src.len() > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    if dest.len() < src.len() {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    if src.is_empty() {
+        return Ok(0);
+    }
+    let tmp = crate::mem_allocation::spreads_mem_malloc(src.len());
+    if tmp.is_null() {
+        return Err(CompressionError::Internal);
+    }
+    let processed = unsafe {
+        filter(
+            typesize,
+            src.len(),
+            src.as_ptr() as *const libc::c_char,
+            dest.as_mut_ptr() as *const libc::c_char,
+            tmp as *const libc::c_char,
+        )
     };
+    crate::mem_allocation::spreads_mem_free(tmp);
+    if processed < 0 {
+        return Err(CompressionError::Internal);
+    }
+    Ok(src.len())
+}
+
+/// Applies the bitshuffle filter to `src`, which must contain a multiple of 8 elements
+/// of `typesize` bytes, and writes the result to `dest`.
+pub fn bitshuffle(typesize: usize, src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+    run_bitshuffle(dispatch::bitshuffle_fn(), typesize, src, dest)
+}
+
+/// Reverts `bitshuffle`.
+pub fn bitunshuffle(
+    typesize: usize,
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    run_bitshuffle(dispatch::bitunshuffle_fn(), typesize, src, dest)
+}
+
+fn ffi_bitshuffle(
+    filter: BitshuffleFn,
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(src, blocksize, dest, blocksize) }
+        .and_then(|(src, dest)| run_bitshuffle(filter, bytesoftype, src, dest));
+    to_c_result(result)
+}
+
+/// Bit-level shuffle of `blocksize` bytes from `src` to `dest`. `blocksize` must be
+/// a multiple of 8 elements of `bytesoftype` bytes. Returns `blocksize` or a negative
+/// error code.
+#[no_mangle]
+pub extern "C" fn spreads_bitshuffle(
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    ffi_bitshuffle(dispatch::bitshuffle_fn(), bytesoftype, blocksize, src, dest)
+}
+
+/// Reverts `spreads_bitshuffle`, with the same argument requirements.
+#[no_mangle]
+pub extern "C" fn spreads_bitunshuffle(
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    ffi_bitshuffle(
+        dispatch::bitunshuffle_fn(),
+        bytesoftype,
+        blocksize,
+        src,
+        dest,
+    )
+}
+
+fn ffi_delta(
+    decode: bool,
+    mode: libc::c_int,
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    let buffers = unsafe { ffi_buffers(src, blocksize, dest, blocksize) };
+    let result = buffers.and_then(|(src, dest)| {
+        let mode = delta::Delta::from_code(mode)?;
+        if src.len() > MAX_INPUT_LENGTH {
+            Err(CompressionError::LengthOverflow)
+        } else if decode {
+            delta::decode(mode, bytesoftype, src, dest)
+        } else {
+            delta::encode(mode, bytesoftype, src, dest)
+        }
+    });
+    to_c_result(result)
+}
+
+/// Delta filter of `blocksize` bytes from `src` to `dest`, which hold integers of
+/// `bytesoftype` bytes (4 or 8). `mode` is 1 for delta or 2 for delta-of-delta.
+/// Returns `blocksize` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_delta_encode(
+    mode: libc::c_int,
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    ffi_delta(false, mode, bytesoftype, blocksize, src, dest)
+}
+
+/// Reverts `spreads_delta_encode`, with the same arguments.
+#[no_mangle]
+pub extern "C" fn spreads_delta_decode(
+    mode: libc::c_int,
+    bytesoftype: usize,
+    blocksize: usize,
+    src: *const libc::c_char,
+    dest: *mut libc::c_char,
+) -> libc::c_int {
+    ffi_delta(true, mode, bytesoftype, blocksize, src, dest)
 }
 
 #[cfg(test)]
@@ -149,4 +1320,429 @@ mod tests {
             // println!("Hello, Blosc with N-threads: {}", threads);
         }
     }
+
+    fn compiled() -> impl Iterator<Item = &'static Codec> {
+        CODECS.iter().filter(|codec| codec.is_compiled())
+    }
+
+    fn sample_data() -> Vec<u8> {
+        (0..64 * 1024u32)
+            .flat_map(|i| (i / 7).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn could_roundtrip_all_codecs() {
+        let original = sample_data();
+        for codec in compiled() {
+            let mut compressed = vec![0u8; original.len() * 2];
+            let compressed_len = codec.compress(&original, &mut compressed, 5).unwrap();
+            assert!(
+                compressed_len < original.len(),
+                "{:?} did not compress",
+                codec
+            );
+
+            let mut decompressed = vec![0u8; original.len()];
+            let decompressed_len = codec
+                .decompress(&compressed[..compressed_len], &mut decompressed)
+                .unwrap();
+            assert_eq!(original.len(), decompressed_len);
+            assert_eq!(original, decompressed);
+        }
+    }
+
+    #[test]
+    fn could_roundtrip_vec_variants() {
+        let original = sample_data();
+        for codec in compiled() {
+            let compressed = codec.compress_to_vec(&original, 9).unwrap();
+            let decompressed = codec
+                .decompress_to_vec(&compressed, original.len())
+                .unwrap();
+            assert_eq!(original, decompressed);
+        }
+    }
+
+    #[test]
+    fn decompress_reports_too_small_buffer() {
+        let original = sample_data();
+        for codec in compiled() {
+            let compressed = codec.compress_to_vec(&original, 5).unwrap();
+            let mut decompressed = vec![0u8; original.len() / 2];
+            assert_eq!(
+                Err(CompressionError::BufferTooSmall),
+                codec.decompress(&compressed, &mut decompressed),
+                "{:?}",
+                codec
+            );
+        }
+    }
+
+    #[test]
+    fn decompress_reports_corrupt_input() {
+        let garbage = [0xFFu8; 256];
+        for codec in compiled() {
+            let mut decompressed = vec![0u8; 4096];
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                codec.decompress(&garbage, &mut decompressed),
+                "{:?}",
+                codec
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn lz4_reports_truncated_block() {
+        let original = sample_data();
+        for codec in [Codec::Lz4, Codec::Lz4Hc].iter() {
+            let compressed = codec.compress_to_vec(&original, 5).unwrap();
+            let truncated = &compressed[..compressed.len() / 2];
+            let mut decompressed = vec![0u8; original.len()];
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                codec.decompress(truncated, &mut decompressed)
+            );
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                codec
+                    .decompress_with_limit(truncated, usize::MAX)
+                    .map(|buffer| buffer.len())
+            );
+            assert_eq!(
+                Err(CompressionError::BufferTooSmall),
+                codec.decompress(&compressed, &mut decompressed[..original.len() - 1])
+            );
+        }
+    }
+
+    #[test]
+    fn could_roundtrip_empty_input() {
+        for codec in compiled() {
+            let compressed = codec.compress_to_vec(&[], 5).unwrap();
+            let decompressed = codec.decompress_to_vec(&compressed, 16).unwrap();
+            assert!(decompressed.is_empty(), "{:?}", codec);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn compress_rejects_invalid_level() {
+        let mut compressed = [0u8; 64];
+        assert_eq!(
+            Err(CompressionError::InvalidLevel),
+            Codec::Zstd.compress(&[1, 2, 3], &mut compressed, 42)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn lz4hc_is_decoded_by_fast_lz4() {
+        let original = sample_data();
+        let mut fast = vec![0u8; Codec::Lz4.compress_bound(original.len()).unwrap()];
+        let fast_len = lz4_compress_fast(&original, &mut fast, 1).unwrap();
+        for level in Codec::Lz4Hc.levels() {
+            let compressed = Codec::Lz4Hc.compress_to_vec(&original, level).unwrap();
+            if level == *Codec::Lz4Hc.levels().end() {
+                assert!(compressed.len() <= fast_len);
+            }
+            let decompressed = Codec::Lz4
+                .decompress_to_vec(&compressed, original.len())
+                .unwrap();
+            assert_eq!(original, decompressed);
+        }
+        let mut compressed = [0u8; 64];
+        for level in [-1, 10, 12].iter() {
+            assert_eq!(
+                Err(CompressionError::InvalidLevel),
+                Codec::Lz4Hc.compress(&[1, 2, 3], &mut compressed, *level)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn could_roundtrip_lz4_with_acceleration() {
+        let original = sample_data();
+        let mut compressed = vec![0u8; Codec::Lz4.compress_bound(original.len()).unwrap()];
+        let mut lengths = Vec::new();
+        for acceleration in [1, 8, 64].iter() {
+            let len = lz4_compress_fast(&original, &mut compressed, *acceleration).unwrap();
+            lengths.push(len);
+            let decompressed = Codec::Lz4
+                .decompress_to_vec(&compressed[..len], original.len())
+                .unwrap();
+            assert_eq!(original, decompressed);
+        }
+        // Higher acceleration gives up ratio, though not strictly at every step.
+        assert!(lengths[2] > lengths[0]);
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_compress_lz4_fast(
+                original.as_ptr() as *const libc::c_char,
+                original.len(),
+                compressed.as_mut_ptr() as *mut libc::c_char,
+                compressed.len(),
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn error_codes_are_stable() {
+        #[cfg(feature = "zstd")]
+        assert_eq!(
+            -1,
+            spreads_compress_zstd(std::ptr::null(), 0, std::ptr::null_mut(), 0, 5)
+        );
+        for code in -9..=-1 {
+            let err = CompressionError::from_code(code).unwrap();
+            assert_eq!(code, err.code());
+            assert!(!spreads_compression_error_message(code).is_null());
+        }
+        assert!(spreads_compression_error_message(0).is_null());
+    }
+
+    #[test]
+    fn compress_bound_fits_incompressible_input() {
+        let noise = test_data::noise(100_000);
+        for codec in compiled() {
+            for len in [0, 1, 100, 4096, noise.len()].iter() {
+                let bound = codec.compress_bound(*len).unwrap();
+                assert_eq!(bound as isize, spreads_compress_bound(codec.id(), *len));
+                let mut compressed = vec![0u8; bound];
+                codec.compress(&noise[..*len], &mut compressed, 9).unwrap();
+            }
+        }
+        assert_eq!(
+            CompressionError::UnsupportedCodec.code() as isize,
+            spreads_compress_bound(0, 10)
+        );
+    }
+
+    #[test]
+    fn could_list_compiled_codecs() {
+        let list = unsafe { std::ffi::CStr::from_ptr(spreads_list_codecs()) };
+        let names = compiled_codecs();
+        assert_eq!(names.join(","), list.to_str().unwrap());
+        for codec in CODECS.iter() {
+            assert_eq!(codec.is_compiled(), names.contains(&codec.name()));
+            assert_eq!(codec.is_compiled(), Codec::from_id(codec.id()).is_ok());
+        }
+        #[cfg(all(
+            feature = "lz4",
+            feature = "zstd",
+            feature = "zlib",
+            feature = "blosclz"
+        ))]
+        assert_eq!(
+            "lz4,zstd,zlib,deflate,gzip,lz4hc,blosclz",
+            &names[..7].join(",")
+        );
+    }
+
+    #[test]
+    fn could_bitshuffle_unshuffle() {
+        let original: Vec<u8> = (0..1024u64).flat_map(|i| (i * 3).to_le_bytes()).collect();
+        let mut shuffled = vec![0u8; original.len()];
+        let mut unshuffled = vec![0u8; original.len()];
+        assert_eq!(Ok(original.len()), bitshuffle(8, &original, &mut shuffled));
+        assert_ne!(original, shuffled);
+        assert_eq!(
+            Ok(original.len()),
+            bitunshuffle(8, &shuffled, &mut unshuffled)
+        );
+        assert_eq!(original, unshuffled);
+    }
+
+    fn reference_shuffle(typesize: usize, src: &[u8]) -> Vec<u8> {
+        let elements = src.len() / typesize;
+        let mut dest = src.to_vec();
+        for i in 0..elements {
+            for j in 0..typesize {
+                dest[j * elements + i] = src[i * typesize + j];
+            }
+        }
+        dest
+    }
+
+    #[test]
+    fn shuffle_copies_tail_bytes() {
+        let original: Vec<u8> = (0..8 * 1000 + 5).map(|i| (i * 31 % 251) as u8).collect();
+        for typesize in [1usize, 3, 4, 8, 16].iter() {
+            let expected = reference_shuffle(*typesize, &original);
+            let mut shuffled = vec![0u8; original.len()];
+            assert_eq!(
+                Ok(original.len()),
+                shuffle(*typesize, &original, &mut shuffled)
+            );
+            assert_eq!(expected, shuffled);
+
+            let mut in_place = original.clone();
+            assert_eq!(
+                Ok(original.len()),
+                shuffle_in_place(*typesize, &mut in_place)
+            );
+            assert_eq!(expected, in_place);
+            unshuffle_in_place(*typesize, &mut in_place).unwrap();
+            assert_eq!(original, in_place);
+
+            let mut unshuffled = vec![0u8; original.len()];
+            unshuffle(*typesize, &shuffled, &mut unshuffled).unwrap();
+            assert_eq!(original, unshuffled);
+        }
+        let mut short = [0u8; 3];
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            shuffle(8, &original, &mut short)
+        );
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            shuffle_in_place(0, &mut short)
+        );
+    }
+
+    #[test]
+    fn could_shuffle_in_place_over_ffi() {
+        let original: Vec<u8> = (0..1003u32).map(|i| (i % 256) as u8).collect();
+        let mut data = original.clone();
+        let ptr = data.as_mut_ptr() as *mut libc::c_char;
+        assert_eq!(1003, spreads_shuffle_checked(8, data.len(), ptr, ptr));
+        assert_eq!(reference_shuffle(8, &original), data);
+        assert_eq!(1003, spreads_unshuffle_in_place(8, data.len(), ptr));
+        assert_eq!(original, data);
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_shuffle_in_place(0, data.len(), ptr)
+        );
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_shuffle_checked(8, 16, std::ptr::null(), ptr)
+        );
+    }
+
+    #[test]
+    fn bitshuffle_rejects_partial_groups() {
+        let original = vec![0u8; 8 * 12];
+        let mut shuffled = vec![0u8; original.len()];
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            bitshuffle(8, &original, &mut shuffled)
+        );
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_bitshuffle(
+                3,
+                original.len(),
+                original.as_ptr() as *const libc::c_char,
+                shuffled.as_mut_ptr() as *mut libc::c_char
+            )
+        );
+    }
+
+    #[test]
+    fn could_roundtrip_delta_filters() {
+        let original: Vec<u8> = (0..1000i64)
+            .flat_map(|i| (1_000_000 + i * 250 + i % 3).to_ne_bytes())
+            .collect();
+        let mut encoded = vec![0u8; original.len()];
+        let mut decoded = vec![0u8; original.len()];
+        for mode in 1..=2 {
+            assert_eq!(
+                original.len() as i32,
+                spreads_delta_encode(
+                    mode,
+                    8,
+                    original.len(),
+                    original.as_ptr() as *const libc::c_char,
+                    encoded.as_mut_ptr() as *mut libc::c_char
+                )
+            );
+            assert_eq!(
+                original.len() as i32,
+                spreads_delta_decode(
+                    mode,
+                    8,
+                    encoded.len(),
+                    encoded.as_ptr() as *const libc::c_char,
+                    decoded.as_mut_ptr() as *mut libc::c_char
+                )
+            );
+            assert_eq!(original, decoded);
+        }
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_delta_encode(
+                1,
+                2,
+                original.len(),
+                original.as_ptr() as *const libc::c_char,
+                encoded.as_mut_ptr() as *mut libc::c_char
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_null_buffers_with_a_length() {
+        let invalid = CompressionError::InvalidArgument.code();
+        let mut data = vec![0u8; 64];
+        let ptr = data.as_mut_ptr() as *mut libc::c_char;
+        let null = std::ptr::null_mut::<libc::c_char>();
+        assert_eq!(invalid, spreads_bitshuffle(8, 64, null, ptr));
+        assert_eq!(invalid, spreads_bitshuffle(8, 64, ptr, null));
+        assert_eq!(invalid, spreads_delta_encode(1, 8, 64, null, ptr));
+        assert_eq!(invalid, spreads_delta_decode(1, 8, 64, ptr, null));
+        assert_eq!(invalid, spreads_shuffle_in_place(8, 64, null));
+        assert_eq!(0, spreads_delta_encode(1, 8, 0, null, null));
+    }
+
+    #[test]
+    #[cfg(any(
+        feature = "lz4",
+        feature = "zstd",
+        feature = "zlib",
+        feature = "snappy"
+    ))]
+    fn codecs_reject_null_buffers_with_a_length() {
+        let invalid = CompressionError::InvalidArgument.code();
+        let mut data = vec![0u8; 64];
+        let ptr = data.as_mut_ptr() as *mut libc::c_char;
+        let null = std::ptr::null_mut::<libc::c_char>();
+        for codec in compiled() {
+            assert_eq!(invalid, ffi_compress(*codec, null, 64, ptr, 64, 5));
+            assert_eq!(invalid, ffi_decompress(*codec, ptr, 64, null, 64));
+        }
+    }
+
+    #[test]
+    fn compress_reports_too_small_buffer() {
+        let original = sample_data();
+        for codec in compiled() {
+            let mut compressed = [0u8; 4];
+            assert_eq!(
+                Err(CompressionError::BufferTooSmall),
+                codec.compress(&original, &mut compressed, 5)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "snappy")]
+    fn snappy_compresses_into_output_below_its_bound() {
+        let original = sample_data();
+        let expected = Codec::Snappy.compress_to_vec(&original, 0).unwrap();
+        assert!(expected.len() < Codec::Snappy.compress_bound(original.len()).unwrap());
+        let mut compressed = vec![0u8; expected.len()];
+        assert_eq!(
+            Ok(expected.len()),
+            Codec::Snappy.compress(&original, &mut compressed, 0)
+        );
+        assert_eq!(expected, compressed);
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            Codec::Snappy.compress(&original, &mut compressed[..expected.len() - 1], 0)
+        );
+    }
 }
@@ -0,0 +1,427 @@
+//! Automatic selection of the Blosc compressor, level and shuffle. A sample of the
+//! input is compressed with candidate settings, cheapest first, until the time budget
+//! is spent, and the whole input is compressed with the best candidate for the
+//! objective. The output is a regular Blosc container whose header records the chosen
+//! compressor and shuffle, so it is read back with `blosc::decompress`. The header
+//! has no room for the level, the full choice is returned next to the container size.
+
+use std::time::{Duration, Instant};
+
+use super::blosc::{self, BloscCompressor, BloscParams, Shuffle};
+use super::{ffi_buffers, to_c_result, CompressionError};
+
+/// What the selection optimizes for.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Objective {
+    /// Smallest output, ties are broken by speed.
+    Ratio = 0,
+    /// Smallest product of output size and compression time.
+    Balanced = 1,
+    /// Fastest compression among the settings that shrink the sample.
+    Speed = 2,
+}
+
+impl Objective {
+    pub fn from_code(code: i32) -> Result<Objective, CompressionError> {
+        match code {
+            0 => Ok(Objective::Ratio),
+            1 => Ok(Objective::Balanced),
+            2 => Ok(Objective::Speed),
+            _ => Err(CompressionError::InvalidArgument),
+        }
+    }
+}
+
+/// Settings of an automatic compression call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AutoOptions {
+    pub objective: Objective,
+    /// Size in bytes of the element type, e.g. 8 for `f64`.
+    pub typesize: usize,
+    /// Upper bound of the time spent on trying candidates. The first candidate is
+    /// always tried, `None` tries all of them.
+    pub time_budget: Option<Duration>,
+    /// Number of input bytes compressed by each candidate.
+    pub sample_size: usize,
+    /// Number of Blosc internal threads, at least 1.
+    pub nthreads: i32,
+}
+
+impl Default for AutoOptions {
+    fn default() -> Self {
+        AutoOptions {
+            objective: Objective::Balanced,
+            typesize: 8,
+            time_budget: None,
+            sample_size: 64 * 1024,
+            nthreads: 1,
+        }
+    }
+}
+
+/// Number of evenly spaced slices the sample is taken from.
+const SAMPLE_SLICES: usize = 8;
+
+/// Compressor and level pairs in increasing order of expected cost. Pairs whose
+/// compressor is not compiled in are skipped.
+const CANDIDATES: [(BloscCompressor, i32); 8] = [
+    (BloscCompressor::Lz4, 1),
+    (BloscCompressor::Lz4, 5),
+    (BloscCompressor::BloscLz, 5),
+    (BloscCompressor::Zstd, 1),
+    (BloscCompressor::Zstd, 5),
+    (BloscCompressor::Zlib, 5),
+    (BloscCompressor::Lz4Hc, 5),
+    (BloscCompressor::Zstd, 9),
+];
+
+const SHUFFLES: [Shuffle; 3] = [Shuffle::Shuffle, Shuffle::NoShuffle, Shuffle::BitShuffle];
+
+/// Concatenates evenly spaced slices of `input` from its start to its last whole
+/// element, aligned to whole elements.
+fn sample(input: &[u8], typesize: usize, sample_size: usize) -> Vec<u8> {
+    if input.len() <= sample_size {
+        return input.to_vec();
+    }
+    let slice_elements = (sample_size / SAMPLE_SLICES / typesize).max(1);
+    let last_start = input.len() / typesize - slice_elements;
+    let mut sample = Vec::with_capacity(slice_elements * typesize * SAMPLE_SLICES);
+    for i in 0..SAMPLE_SLICES {
+        let start = i * last_start / (SAMPLE_SLICES - 1) * typesize;
+        sample.extend_from_slice(&input[start..start + slice_elements * typesize]);
+    }
+    sample
+}
+
+/// Settings chosen by `spreads_compress_auto`, as codes of `BloscCompressor` and
+/// `Shuffle` and a level from 0 to 9.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AutoChoice {
+    pub compressor: i32,
+    pub level: i32,
+    pub shuffle: i32,
+}
+
+impl From<BloscParams> for AutoChoice {
+    fn from(params: BloscParams) -> Self {
+        AutoChoice {
+            compressor: params.compressor as i32,
+            level: params.level,
+            shuffle: params.shuffle as i32,
+        }
+    }
+}
+
+struct Trial {
+    params: BloscParams,
+    size: usize,
+    elapsed: Duration,
+}
+
+impl Trial {
+    fn better_than(&self, other: &Trial, objective: Objective, sample_len: usize) -> bool {
+        match objective {
+            Objective::Ratio => (self.size, self.elapsed) < (other.size, other.elapsed),
+            Objective::Balanced => {
+                let cost = |t: &Trial| t.size as u128 * t.elapsed.as_nanos().max(1);
+                cost(self) < cost(other)
+            }
+            Objective::Speed => {
+                let shrinks = |t: &Trial| t.size < sample_len;
+                match (shrinks(self), shrinks(other)) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => self.elapsed < other.elapsed,
+                }
+            }
+        }
+    }
+}
+
+/// Tries the candidate settings on a sample of `input` and returns the best ones for
+/// the objective.
+pub fn select_params(input: &[u8], options: &AutoOptions) -> Result<BloscParams, CompressionError> {
+    // Any compiled candidate will do to check the other settings up front.
+    let compressor = CANDIDATES
+        .iter()
+        .map(|(compressor, _)| *compressor)
+        .find(|compressor| compressor.is_compiled())
+        .ok_or(CompressionError::UnsupportedCodec)?;
+    let base = BloscParams {
+        typesize: options.typesize,
+        nthreads: options.nthreads,
+        compressor,
+        ..BloscParams::default()
+    };
+    base.validate()?;
+    if input.is_empty() {
+        return Ok(base);
+    }
+    let sample = sample(
+        input,
+        options.typesize,
+        options.sample_size.max(options.typesize),
+    );
+    let mut scratch = vec![0u8; blosc::compress_bound(sample.len())?];
+    let started = Instant::now();
+    let mut best: Option<Trial> = None;
+    let mut last_error = CompressionError::Internal;
+    for (compressor, level) in CANDIDATES.iter() {
+        for shuffle in SHUFFLES.iter() {
+            if let (Some(budget), Some(best)) = (options.time_budget, &best) {
+                if started.elapsed() >= budget {
+                    return Ok(best.params);
+                }
+            }
+            let params = BloscParams {
+                level: *level,
+                shuffle: *shuffle,
+                compressor: *compressor,
+                ..base
+            };
+            let trial_started = Instant::now();
+            let size = match blosc::compress(&params, &sample, &mut scratch) {
+                Ok(size) => size,
+                // Compressors that are not compiled in are skipped.
+                Err(err) => {
+                    last_error = err;
+                    continue;
+                }
+            };
+            let trial = Trial {
+                params,
+                size,
+                elapsed: trial_started.elapsed(),
+            };
+            let replace = match &best {
+                Some(best) => trial.better_than(best, options.objective, sample.len()),
+                None => true,
+            };
+            if replace {
+                best = Some(trial);
+            }
+        }
+    }
+    best.map(|trial| trial.params).ok_or(last_error)
+}
+
+/// Compresses `input` into a Blosc container with automatically selected settings.
+/// Returns the container size and the chosen settings.
+pub fn compress(
+    input: &[u8],
+    output: &mut [u8],
+    options: &AutoOptions,
+) -> Result<(usize, BloscParams), CompressionError> {
+    let params = select_params(input, options)?;
+    let len = blosc::compress(&params, input, output)?;
+    Ok((len, params))
+}
+
+/// Compresses `input` into a new vector holding exactly the Blosc container.
+pub fn compress_to_vec(
+    input: &[u8],
+    options: &AutoOptions,
+) -> Result<(Vec<u8>, BloscParams), CompressionError> {
+    let params = select_params(input, options)?;
+    Ok((blosc::compress_to_vec(&params, input)?, params))
+}
+
+/// Compresses `src` into a Blosc container, choosing the compressor, level and shuffle
+/// by sampling the input. `objective` is 0 for ratio, 1 for balanced or 2 for speed
+/// and `time_budget_us` bounds the selection time in microseconds, 0 for no bound.
+/// The destination needs `spreads_blosc_compress_bound` bytes. Returns the container
+/// size or a negative error code. Unless `choice` is NULL it receives the chosen
+/// settings. The output is read by `spreads_blosc_decompress`.
+#[no_mangle]
+pub extern "C" fn spreads_compress_auto(
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    typesize: usize,
+    objective: libc::c_int,
+    time_budget_us: u64,
+    nthreads: libc::c_int,
+    choice: *mut AutoChoice,
+) -> libc::c_int {
+    let buffers = unsafe { ffi_buffers(src, src_length, dest, dest_length) };
+    let result = buffers.and_then(|(src, dest)| {
+        let options = AutoOptions {
+            objective: Objective::from_code(objective)?,
+            typesize,
+            time_budget: match time_budget_us {
+                0 => None,
+                us => Some(Duration::from_micros(us)),
+            },
+            nthreads,
+            ..AutoOptions::default()
+        };
+        compress(src, dest, &options)
+    });
+    to_c_result(result.map(|(len, params)| {
+        if let Some(choice) = unsafe { choice.as_mut() } {
+            *choice = params.into();
+        }
+        len
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(
+        feature = "lz4",
+        feature = "zstd",
+        feature = "zlib",
+        feature = "blosclz"
+    ))]
+    fn series(len: usize) -> Vec<u8> {
+        (0..len)
+            .flat_map(|i| (100.0 + (i / 10) as f64 * 0.25).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn ratio_objective_beats_default_settings() {
+        let original = series(4_000);
+        let options = AutoOptions {
+            objective: Objective::Ratio,
+            ..AutoOptions::default()
+        };
+        let (compressed, params) = compress_to_vec(&original, &options).unwrap();
+        let default = blosc::compress_to_vec(&BloscParams::default(), &original).unwrap();
+        assert!(compressed.len() <= default.len());
+        assert_eq!(original, blosc::decompress_to_vec(&compressed, 1).unwrap());
+
+        let info = blosc::inspect(&compressed).unwrap();
+        assert_eq!(params.shuffle as i32, info.shuffle);
+        // LZ4HC containers report the LZ4 library.
+        assert!(params
+            .compressor
+            .name()
+            .starts_with(&info.complib().to_lowercase()));
+    }
+
+    #[test]
+    #[cfg(any(
+        feature = "lz4",
+        feature = "zstd",
+        feature = "zlib",
+        feature = "blosclz"
+    ))]
+    fn could_compress_with_any_compiled_compressor() {
+        let original = series(10_000);
+        for objective in [Objective::Ratio, Objective::Balanced, Objective::Speed].iter() {
+            let options = AutoOptions {
+                objective: *objective,
+                ..AutoOptions::default()
+            };
+            let (compressed, params) = compress_to_vec(&original, &options).unwrap();
+            assert!(params.compressor.is_compiled());
+            assert_eq!(original, blosc::decompress_to_vec(&compressed, 1).unwrap());
+        }
+        let (compressed, _) = compress_to_vec(&[], &AutoOptions::default()).unwrap();
+        assert!(blosc::decompress_to_vec(&compressed, 1).unwrap().is_empty());
+        let options = AutoOptions {
+            typesize: 0,
+            ..AutoOptions::default()
+        };
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            select_params(&original, &options)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn could_compress_large_input_with_every_objective() {
+        let original = series(200_000);
+        for objective in [Objective::Ratio, Objective::Balanced, Objective::Speed].iter() {
+            let options = AutoOptions {
+                objective: *objective,
+                sample_size: 16 * 1024,
+                ..AutoOptions::default()
+            };
+            let (compressed, _) = compress_to_vec(&original, &options).unwrap();
+            assert!(compressed.len() < original.len() / 4);
+            assert_eq!(original, blosc::decompress_to_vec(&compressed, 1).unwrap());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn zero_budget_tries_only_the_first_candidate() {
+        let original = series(1_000);
+        let options = AutoOptions {
+            time_budget: Some(Duration::from_secs(0)),
+            ..AutoOptions::default()
+        };
+        let params = select_params(&original, &options).unwrap();
+        assert_eq!((BloscCompressor::Lz4, 1), (params.compressor, params.level));
+        assert_eq!(Shuffle::Shuffle, params.shuffle);
+    }
+
+    #[test]
+    fn sample_takes_whole_elements() {
+        let input: Vec<u8> = (0..10_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let sample = sample(&input, 4, 1000);
+        assert_eq!(SAMPLE_SLICES * 124, sample.len());
+        assert_eq!(&input[..4], &sample[..4]);
+        assert_eq!(&input[input.len() - 124..], &sample[sample.len() - 124..]);
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn could_use_c_api() {
+        let original = series(10_000);
+        let mut compressed = vec![0u8; blosc::compress_bound(original.len()).unwrap()];
+        let mut choice = AutoChoice::default();
+        let len = spreads_compress_auto(
+            original.as_ptr() as *const libc::c_char,
+            original.len(),
+            compressed.as_mut_ptr() as *mut libc::c_char,
+            compressed.len(),
+            8,
+            Objective::Speed as i32,
+            1_000,
+            1,
+            &mut choice,
+        );
+        assert!(len > 0);
+        assert!((1..=9).contains(&choice.level));
+        let info = blosc::inspect(&compressed[..len as usize]).unwrap();
+        assert_eq!(info.shuffle, choice.shuffle);
+        let params = BloscParams {
+            compressor: BloscCompressor::from_code(choice.compressor).unwrap(),
+            level: choice.level,
+            shuffle: Shuffle::from_code(choice.shuffle).unwrap(),
+            ..BloscParams::default()
+        };
+        assert_eq!(
+            blosc::compress_to_vec(&params, &original).unwrap(),
+            compressed[..len as usize].to_vec()
+        );
+        assert_eq!(
+            original,
+            blosc::decompress_to_vec(&compressed[..len as usize], 1).unwrap()
+        );
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_compress_auto(
+                original.as_ptr() as *const libc::c_char,
+                original.len(),
+                compressed.as_mut_ptr() as *mut libc::c_char,
+                compressed.len(),
+                8,
+                7,
+                0,
+                1,
+                std::ptr::null_mut(),
+            )
+        );
+    }
+}
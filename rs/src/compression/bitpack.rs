@@ -0,0 +1,853 @@
+//! Frame-of-reference and bit-packing of integer arrays. Values are split into blocks
+//! of 128 or 256 integers. Each block stores its minimum and the bit width of the
+//! largest difference to it, followed by the differences packed with that width.
+//!
+//! Full blocks use a vertical layout: the block is split into 16-byte vectors of 4
+//! `u32` or 2 `u64` lanes, value `i` goes to lane `i % lanes` and each lane is packed
+//! into its own sequence of words, so a whole vector is unpacked with one shift and
+//! mask. The last partial block is packed sequentially, least significant bit first.
+//!
+//! A stream starts with an 8 bytes header: the little-endian `u32` value count, the
+//! typesize, log2 of the block length, the transform and a reserved zero byte.
+
+use super::{ffi_buffers, to_c_result, CompressionError, MAX_INPUT_LENGTH};
+
+const HEADER_LENGTH: usize = 8;
+const MAX_BLOCK_LENGTH: usize = 256;
+
+/// Integer types that can be bit-packed: `u32`, `i32`, `u64` and `i64`. The list is
+/// closed because the stream only records 4 and 8 byte widths.
+pub trait PackElement: Copy + sealed::Lane {}
+
+mod sealed {
+    /// Widening of an element to the `u64` the blocks are framed in, and back.
+    pub trait Lane {
+        const TYPESIZE: usize;
+        const SIGNED: bool;
+        fn to_lane(self) -> u64;
+        fn from_lane(lane: u64) -> Self;
+    }
+}
+
+macro_rules! impl_pack_element {
+    ($t:ty, $unsigned:ty, $signed:expr) => {
+        impl sealed::Lane for $t {
+            const TYPESIZE: usize = std::mem::size_of::<$t>();
+            const SIGNED: bool = $signed;
+
+            fn to_lane(self) -> u64 {
+                u64::from(self as $unsigned)
+            }
+
+            fn from_lane(lane: u64) -> Self {
+                lane as $unsigned as $t
+            }
+        }
+
+        impl PackElement for $t {}
+    };
+}
+
+impl_pack_element!(u32, u32, false);
+impl_pack_element!(i32, u32, true);
+impl_pack_element!(u64, u64, false);
+impl_pack_element!(i64, u64, true);
+
+/// Settings of a bit-packing call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackOptions {
+    /// Number of integers per block, 128 or 256.
+    pub block_len: usize,
+    /// Zigzag-encodes signed values before taking the block minimum, which keeps the
+    /// widths small for values that oscillate around zero. Signed values are otherwise
+    /// ordered by flipping their sign bit. Only valid for signed types.
+    pub zigzag: bool,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        PackOptions {
+            block_len: 128,
+            zigzag: false,
+        }
+    }
+}
+
+/// Bijection applied to the lanes so that unsigned ordering works for the block
+/// minimum, stored in the header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Transform {
+    None = 0,
+    ZigZag = 1,
+    SignFlip = 2,
+}
+
+impl Transform {
+    fn from_code(code: u8) -> Result<Transform, CompressionError> {
+        match code {
+            0 => Ok(Transform::None),
+            1 => Ok(Transform::ZigZag),
+            2 => Ok(Transform::SignFlip),
+            _ => Err(CompressionError::CorruptInput),
+        }
+    }
+
+    fn forward(self, lane: u64, bits: u32) -> u64 {
+        let sign = 1u64 << (bits - 1);
+        match self {
+            Transform::None => lane,
+            Transform::ZigZag => {
+                let negative = if lane & sign != 0 { u64::MAX } else { 0 };
+                ((lane << 1) ^ negative) & mask(bits)
+            }
+            Transform::SignFlip => lane ^ sign,
+        }
+    }
+
+    fn inverse(self, lane: u64, bits: u32) -> u64 {
+        match self {
+            Transform::None => lane,
+            Transform::ZigZag => ((lane >> 1) ^ (lane & 1).wrapping_neg()) & mask(bits),
+            Transform::SignFlip => lane ^ (1u64 << (bits - 1)),
+        }
+    }
+}
+
+fn mask(width: u32) -> u64 {
+    if width == 64 {
+        u64::MAX
+    } else {
+        (1u64 << width) - 1
+    }
+}
+
+fn check_options(
+    typesize: usize,
+    signed: bool,
+    options: &PackOptions,
+) -> Result<Transform, CompressionError> {
+    if !(typesize == 4 || typesize == 8) || !(options.block_len == 128 || options.block_len == 256)
+    {
+        return Err(CompressionError::InvalidArgument);
+    }
+    match (signed, options.zigzag) {
+        (false, false) => Ok(Transform::None),
+        (false, true) => Err(CompressionError::InvalidArgument),
+        (true, false) => Ok(Transform::SignFlip),
+        (true, true) => Ok(Transform::ZigZag),
+    }
+}
+
+fn pack_vertical(values: &[u64], width: u32, typesize: usize, out: &mut [u8]) {
+    let lanes = 16 / typesize;
+    let word_bits = typesize as u32 * 8;
+    let per_lane = values.len() / lanes;
+    for lane in 0..lanes {
+        let mut acc = 0u128;
+        let mut filled = 0u32;
+        let mut word = 0usize;
+        for i in 0..per_lane {
+            acc |= u128::from(values[i * lanes + lane]) << filled;
+            filled += width;
+            while filled >= word_bits {
+                let offset = (word * lanes + lane) * typesize;
+                out[offset..offset + typesize]
+                    .copy_from_slice(&(acc as u64).to_le_bytes()[..typesize]);
+                acc >>= word_bits;
+                filled -= word_bits;
+                word += 1;
+            }
+        }
+    }
+}
+
+fn unpack_vertical(packed: &[u8], width: u32, typesize: usize, values: &mut [u64]) {
+    let lanes = 16 / typesize;
+    let word_bits = typesize as u32 * 8;
+    let per_lane = values.len() / lanes;
+    for lane in 0..lanes {
+        let mut acc = 0u128;
+        let mut filled = 0u32;
+        let mut word = 0usize;
+        for i in 0..per_lane {
+            while filled < width {
+                let offset = (word * lanes + lane) * typesize;
+                let mut bytes = [0u8; 8];
+                bytes[..typesize].copy_from_slice(&packed[offset..offset + typesize]);
+                acc |= u128::from(u64::from_le_bytes(bytes)) << filled;
+                filled += word_bits;
+                word += 1;
+            }
+            values[i * lanes + lane] = acc as u64 & mask(width);
+            acc >>= width;
+            filled -= width;
+        }
+    }
+}
+
+/// SSE2 unpacking of full blocks. The words of a lane sit in the same lane of
+/// consecutive vectors, so each step shifts all lanes by the same amount and only
+/// crosses into the next vector when a value straddles two words.
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    pub(super) fn unpack32(packed: &[u8], width: u32, values: &mut [u64]) {
+        let per_lane = values.len() / 4;
+        let words = per_lane * width as usize / 32;
+        assert!(width > 0 && width <= 32 && packed.len() >= words * 16);
+        // SSE2 is part of the x86_64 baseline, loads stay within the `words` vectors
+        // checked above and stores within `values`.
+        unsafe {
+            let mask = _mm_set1_epi32(super::mask(width) as i32);
+            let zero = _mm_setzero_si128();
+            let src = packed.as_ptr() as *const __m128i;
+            let mut word = 0usize;
+            let mut acc = _mm_loadu_si128(src);
+            let mut shift = 0u32;
+            for i in 0..per_lane {
+                let mut v = _mm_srl_epi32(acc, _mm_cvtsi32_si128(shift as i32));
+                shift += width;
+                if shift >= 32 {
+                    word += 1;
+                    shift -= 32;
+                    if word < words {
+                        acc = _mm_loadu_si128(src.add(word));
+                        if shift > 0 {
+                            let carry = _mm_cvtsi32_si128((width - shift) as i32);
+                            v = _mm_or_si128(v, _mm_sll_epi32(acc, carry));
+                        }
+                    }
+                }
+                v = _mm_and_si128(v, mask);
+                let dest = values.as_mut_ptr().add(i * 4) as *mut __m128i;
+                _mm_storeu_si128(dest, _mm_unpacklo_epi32(v, zero));
+                _mm_storeu_si128(dest.add(1), _mm_unpackhi_epi32(v, zero));
+            }
+        }
+    }
+
+    pub(super) fn unpack64(packed: &[u8], width: u32, values: &mut [u64]) {
+        let per_lane = values.len() / 2;
+        let words = per_lane * width as usize / 64;
+        assert!(width > 0 && width <= 64 && packed.len() >= words * 16);
+        unsafe {
+            let mask = _mm_set1_epi64x(super::mask(width) as i64);
+            let src = packed.as_ptr() as *const __m128i;
+            let mut word = 0usize;
+            let mut acc = _mm_loadu_si128(src);
+            let mut shift = 0u32;
+            for i in 0..per_lane {
+                let mut v = _mm_srl_epi64(acc, _mm_cvtsi32_si128(shift as i32));
+                shift += width;
+                if shift >= 64 {
+                    word += 1;
+                    shift -= 64;
+                    if word < words {
+                        acc = _mm_loadu_si128(src.add(word));
+                        if shift > 0 {
+                            let carry = _mm_cvtsi32_si128((width - shift) as i32);
+                            v = _mm_or_si128(v, _mm_sll_epi64(acc, carry));
+                        }
+                    }
+                }
+                v = _mm_and_si128(v, mask);
+                _mm_storeu_si128(values.as_mut_ptr().add(i * 2) as *mut __m128i, v);
+            }
+        }
+    }
+}
+
+fn unpack_full(packed: &[u8], width: u32, typesize: usize, values: &mut [u64]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if width > 0 && super::dispatch::use_sse2(super::dispatch::Kernel::BitPack) {
+            if typesize == 4 {
+                sse2::unpack32(packed, width, values)
+            } else {
+                sse2::unpack64(packed, width, values)
+            }
+            return;
+        }
+    }
+    unpack_vertical(packed, width, typesize, values)
+}
+
+fn pack_sequential(values: &[u64], width: u32, out: &mut [u8]) {
+    let mut acc = 0u128;
+    let mut filled = 0u32;
+    let mut pos = 0usize;
+    for value in values {
+        acc |= u128::from(*value) << filled;
+        filled += width;
+        while filled >= 8 {
+            out[pos] = acc as u8;
+            acc >>= 8;
+            filled -= 8;
+            pos += 1;
+        }
+    }
+    if filled > 0 {
+        out[pos] = acc as u8;
+    }
+}
+
+fn unpack_sequential(packed: &[u8], width: u32, values: &mut [u64]) {
+    let mut acc = 0u128;
+    let mut filled = 0u32;
+    let mut pos = 0usize;
+    for value in values.iter_mut() {
+        while filled < width {
+            acc |= u128::from(packed[pos]) << filled;
+            filled += 8;
+            pos += 1;
+        }
+        *value = acc as u64 & mask(width);
+        acc >>= width;
+        filled -= width;
+    }
+}
+
+fn packed_length(count: usize, width: u32, full: bool) -> usize {
+    let bits = count * width as usize;
+    if full {
+        bits / 8
+    } else {
+        bits.div_ceil(8)
+    }
+}
+
+/// Frames `block` against its minimum and writes it to `out`, returns the number of
+/// bytes written.
+fn encode_block(
+    block: &mut [u64],
+    typesize: usize,
+    full: bool,
+    out: &mut [u8],
+) -> Result<usize, CompressionError> {
+    let min = block.iter().copied().min().unwrap_or(0);
+    let max = block.iter().copied().max().unwrap_or(0);
+    let width = 64 - (max - min).leading_zeros();
+    let len = typesize + 1 + packed_length(block.len(), width, full);
+    if out.len() < len {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    for value in block.iter_mut() {
+        *value -= min;
+    }
+    out[..typesize].copy_from_slice(&min.to_le_bytes()[..typesize]);
+    out[typesize] = width as u8;
+    let packed = &mut out[typesize + 1..len];
+    if full {
+        pack_vertical(block, width, typesize, packed);
+    } else {
+        packed.iter_mut().for_each(|b| *b = 0);
+        pack_sequential(block, width, packed);
+    }
+    Ok(len)
+}
+
+fn encode<I>(
+    typesize: usize,
+    signed: bool,
+    options: &PackOptions,
+    count: usize,
+    mut values: I,
+    dest: &mut [u8],
+) -> Result<usize, CompressionError>
+where
+    I: Iterator<Item = u64>,
+{
+    let transform = check_options(typesize, signed, options)?;
+    if count > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    if dest.len() < HEADER_LENGTH {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    dest[..4].copy_from_slice(&(count as u32).to_le_bytes());
+    dest[4] = typesize as u8;
+    dest[5] = options.block_len.trailing_zeros() as u8;
+    dest[6] = transform as u8;
+    dest[7] = 0;
+    let bits = typesize as u32 * 8;
+    let mut block = [0u64; MAX_BLOCK_LENGTH];
+    let mut pos = HEADER_LENGTH;
+    let mut remaining = count;
+    while remaining > 0 {
+        let len = remaining.min(options.block_len);
+        for slot in block[..len].iter_mut() {
+            let lane = values.next().ok_or(CompressionError::InvalidArgument)?;
+            *slot = transform.forward(lane, bits);
+        }
+        let full = len == options.block_len;
+        pos += encode_block(&mut block[..len], typesize, full, &mut dest[pos..])?;
+        remaining -= len;
+    }
+    Ok(pos)
+}
+
+struct Header {
+    count: usize,
+    typesize: usize,
+    block_len: usize,
+    transform: Transform,
+}
+
+impl Header {
+    /// Parses the header of the stream `src`. Even a block of width 0 keeps its
+    /// minimum and width byte, so a count needing more blocks than `src` has room
+    /// for is corrupt.
+    fn parse(src: &[u8]) -> Result<Header, CompressionError> {
+        if src.len() < HEADER_LENGTH || src[7] != 0 {
+            return Err(CompressionError::CorruptInput);
+        }
+        let header = Header {
+            count: u32::from_le_bytes([src[0], src[1], src[2], src[3]]) as usize,
+            typesize: src[4] as usize,
+            block_len: match src[5] {
+                7 => 128,
+                8 => 256,
+                _ => return Err(CompressionError::CorruptInput),
+            },
+            transform: Transform::from_code(src[6])?,
+        };
+        let blocks = header.count.div_ceil(header.block_len);
+        if !(header.typesize == 4 || header.typesize == 8)
+            || blocks * (header.typesize + 1) > src.len() - HEADER_LENGTH
+        {
+            return Err(CompressionError::CorruptInput);
+        }
+        Ok(header)
+    }
+}
+
+fn decode<F>(
+    typesize: usize,
+    src: &[u8],
+    capacity: usize,
+    mut put: F,
+) -> Result<usize, CompressionError>
+where
+    F: FnMut(usize, u64),
+{
+    let header = Header::parse(src)?;
+    if header.typesize != typesize {
+        return Err(CompressionError::InvalidArgument);
+    }
+    if header.count > capacity {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    let bits = typesize as u32 * 8;
+    let mut block = [0u64; MAX_BLOCK_LENGTH];
+    let mut pos = HEADER_LENGTH;
+    let mut start = 0usize;
+    while start < header.count {
+        let len = (header.count - start).min(header.block_len);
+        let full = len == header.block_len;
+        if src.len() - pos < typesize + 1 {
+            return Err(CompressionError::CorruptInput);
+        }
+        let mut min = [0u8; 8];
+        min[..typesize].copy_from_slice(&src[pos..pos + typesize]);
+        let min = u64::from_le_bytes(min);
+        let width = u32::from(src[pos + typesize]);
+        pos += typesize + 1;
+        if width > bits {
+            return Err(CompressionError::CorruptInput);
+        }
+        let packed_len = packed_length(len, width, full);
+        if src.len() - pos < packed_len {
+            return Err(CompressionError::CorruptInput);
+        }
+        let packed = &src[pos..pos + packed_len];
+        if full {
+            unpack_full(packed, width, typesize, &mut block[..len]);
+        } else {
+            unpack_sequential(packed, width, &mut block[..len]);
+        }
+        pos += packed_len;
+        for (i, value) in block[..len].iter().enumerate() {
+            let lane = value.wrapping_add(min) & mask(bits);
+            put(start + i, header.transform.inverse(lane, bits));
+        }
+        start += len;
+    }
+    if pos != src.len() {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok(header.count)
+}
+
+fn bound(typesize: usize, count: usize, block_len: usize) -> Result<usize, CompressionError> {
+    if !(typesize == 4 || typesize == 8) || !(block_len == 128 || block_len == 256) {
+        return Err(CompressionError::InvalidArgument);
+    }
+    if count > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    Ok(HEADER_LENGTH + count.div_ceil(block_len) * (typesize + 1) + count * typesize)
+}
+
+/// Destination size that is always enough to pack `count` values of type `T`.
+pub fn compress_bound<T: PackElement>(
+    count: usize,
+    options: &PackOptions,
+) -> Result<usize, CompressionError> {
+    bound(T::TYPESIZE, count, options.block_len)
+}
+
+/// Packs `values` into `dest` and returns the number of bytes written.
+pub fn compress<T: PackElement>(
+    values: &[T],
+    dest: &mut [u8],
+    options: &PackOptions,
+) -> Result<usize, CompressionError> {
+    let lanes = values.iter().map(|v| v.to_lane());
+    encode(T::TYPESIZE, T::SIGNED, options, values.len(), lanes, dest)
+}
+
+/// Packs `values` into a new vector holding exactly the stream.
+pub fn compress_to_vec<T: PackElement>(
+    values: &[T],
+    options: &PackOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut dest = vec![0u8; compress_bound::<T>(values.len(), options)?];
+    let len = compress(values, &mut dest, options)?;
+    dest.truncate(len);
+    dest.shrink_to_fit();
+    Ok(dest)
+}
+
+/// Number of values stored in the stream `src`.
+pub fn value_count(src: &[u8]) -> Result<usize, CompressionError> {
+    Header::parse(src).map(|header| header.count)
+}
+
+/// Unpacks the stream `src` into `dest` and returns the number of values. Block widths
+/// above the type size and blocks that overrun or fall short of `src` fail with
+/// `CorruptInput`.
+pub fn decompress<T: PackElement>(src: &[u8], dest: &mut [T]) -> Result<usize, CompressionError> {
+    decode(T::TYPESIZE, src, dest.len(), |i, lane| {
+        dest[i] = T::from_lane(lane)
+    })
+}
+
+/// Unpacks the stream `src` into a new vector.
+pub fn decompress_to_vec<T: PackElement>(src: &[u8]) -> Result<Vec<T>, CompressionError> {
+    let mut dest = vec![T::from_lane(0); value_count(src)?];
+    decompress(src, &mut dest)?;
+    Ok(dest)
+}
+
+/// Returns the destination size that is always enough to pack `count` integers of
+/// `typesize` bytes (4 or 8) in blocks of `block_len` (128 or 256) or a negative
+/// error code.
+#[no_mangle]
+pub extern "C" fn spreads_bitpack_compress_bound(
+    count: usize,
+    typesize: usize,
+    block_len: usize,
+) -> isize {
+    match bound(typesize, count, block_len) {
+        Ok(bound) => bound as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// Packs `count` native-endian integers of `typesize` bytes (4 or 8) from `src`.
+/// `is_signed` selects signed ordering and `zigzag` enables zigzag encoding of signed
+/// values. Returns the stream size or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_bitpack_compress(
+    src: *const libc::c_char,
+    count: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    typesize: usize,
+    is_signed: libc::c_int,
+    block_len: usize,
+    zigzag: libc::c_int,
+) -> libc::c_int {
+    let options = PackOptions {
+        block_len,
+        zigzag: zigzag != 0,
+    };
+    let signed = is_signed != 0;
+    if let Err(err) = check_options(typesize, signed, &options) {
+        return err.code();
+    }
+    let src_length = match count.checked_mul(typesize) {
+        Some(len) => len,
+        None => return CompressionError::LengthOverflow.code(),
+    };
+    let result =
+        unsafe { ffi_buffers(src, src_length, dest, dest_length) }.and_then(|(src, dest)| {
+            let lanes = src.chunks_exact(typesize).map(read_lane);
+            encode(typesize, signed, &options, count, lanes, dest)
+        });
+    to_c_result(result)
+}
+
+fn read_lane(chunk: &[u8]) -> u64 {
+    if chunk.len() == 4 {
+        u64::from(u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+    } else {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(chunk);
+        u64::from_ne_bytes(bytes)
+    }
+}
+
+/// Unpacks a stream of integers of `typesize` bytes into at most `dest_count` values.
+/// Returns the number of values or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_bitpack_decompress(
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_count: usize,
+    typesize: usize,
+) -> libc::c_int {
+    let dest_length = match dest_count.checked_mul(typesize) {
+        Some(len) if typesize == 4 || typesize == 8 => len,
+        _ => return CompressionError::InvalidArgument.code(),
+    };
+    let result =
+        unsafe { ffi_buffers(src, src_length, dest, dest_length) }.and_then(|(src, dest)| {
+            decode(typesize, src, dest_count, |i, lane| {
+                let out = &mut dest[i * typesize..(i + 1) * typesize];
+                if typesize == 4 {
+                    out.copy_from_slice(&(lane as u32).to_ne_bytes());
+                } else {
+                    out.copy_from_slice(&lane.to_ne_bytes());
+                }
+            })
+        });
+    to_c_result(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volumes() -> Vec<u32> {
+        (0..10_000u32).map(|i| 1_000 + (i * 7919) % 500).collect()
+    }
+
+    fn pack<T: PackElement>(values: &[T], block_len: usize) -> Vec<u8> {
+        let options = PackOptions {
+            block_len,
+            zigzag: false,
+        };
+        compress_to_vec(values, &options).unwrap()
+    }
+
+    #[test]
+    fn could_roundtrip_all_types_and_block_lengths() {
+        let volumes = volumes();
+        let sequence: Vec<u64> = (0..1_000u64).map(|i| (1 << 40) + i * 3).collect();
+        let signed: Vec<i32> = (0..777i32).map(|i| (i % 21) - 10).collect();
+        let wide: Vec<i64> = vec![i64::MIN, i64::MAX, 0, -1, 1];
+        for block_len in [128usize, 256].iter() {
+            for zigzag in [false, true].iter() {
+                let options = PackOptions {
+                    block_len: *block_len,
+                    zigzag: *zigzag,
+                };
+                let compressed = compress_to_vec(&signed, &options).unwrap();
+                assert_eq!(signed, decompress_to_vec::<i32>(&compressed).unwrap());
+                let compressed = compress_to_vec(&wide, &options).unwrap();
+                assert_eq!(wide, decompress_to_vec::<i64>(&compressed).unwrap());
+            }
+            let compressed = pack(&volumes, *block_len);
+            // 500 distinct values need 9 bits instead of 32.
+            assert!(compressed.len() < volumes.len() * 4 * 10 / 32);
+            assert_eq!(volumes, decompress_to_vec::<u32>(&compressed).unwrap());
+            let compressed = pack(&sequence, *block_len);
+            assert_eq!(sequence, decompress_to_vec::<u64>(&compressed).unwrap());
+        }
+
+        let empty: Vec<u64> = Vec::new();
+        let compressed = compress_to_vec(&empty, &PackOptions::default()).unwrap();
+        assert_eq!(HEADER_LENGTH, compressed.len());
+        assert!(decompress_to_vec::<u64>(&compressed).unwrap().is_empty());
+    }
+
+    #[test]
+    fn could_zigzag_signed_values() {
+        let values: Vec<i64> = (0..1024i64)
+            .map(|i| if i % 2 == 0 { i % 7 } else { -(i % 5) })
+            .collect();
+        let flipped = compress_to_vec(&values, &PackOptions::default()).unwrap();
+        let zigzag = PackOptions {
+            zigzag: true,
+            ..PackOptions::default()
+        };
+        let zigzagged = compress_to_vec(&values, &zigzag).unwrap();
+        assert!(zigzagged.len() <= flipped.len());
+        assert_eq!(values, decompress_to_vec::<i64>(&zigzagged).unwrap());
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            compress_to_vec(&[1u32, 2], &zigzag)
+        );
+    }
+
+    #[test]
+    fn constant_blocks_take_width_0() {
+        let values = vec![42u32; 300];
+        let compressed = pack(&values, 128);
+        // Two full blocks and a partial one, each only its minimum and width.
+        assert_eq!(HEADER_LENGTH + 3 * 5, compressed.len());
+        for block in compressed[HEADER_LENGTH..].chunks(5) {
+            assert_eq!([42, 0, 0, 0, 0], block);
+        }
+        assert_eq!(values, decompress_to_vec::<u32>(&compressed).unwrap());
+    }
+
+    #[test]
+    fn full_range_blocks_take_width_64() {
+        let values: Vec<u64> = (0..300u64)
+            .map(|i| if i % 3 == 0 { u64::MAX } else { i })
+            .collect();
+        let compressed = pack(&values, 128);
+        // Framing saves nothing, every value keeps its 8 bytes.
+        assert_eq!(HEADER_LENGTH + 3 * 9 + 300 * 8, compressed.len());
+        assert_eq!(64, compressed[HEADER_LENGTH + 8]);
+        assert_eq!(values, decompress_to_vec::<u64>(&compressed).unwrap());
+    }
+
+    #[test]
+    fn could_roundtrip_counts_off_the_vector_lanes() {
+        // Full blocks hold whole vectors of 4 or 2 lanes, the rest of the values is
+        // packed sequentially whatever its length.
+        let volumes = volumes();
+        for count in [1usize, 2, 3, 5, 127, 129, 130, 131, 255, 257, 383].iter() {
+            let narrow = volumes[..*count].to_vec();
+            let wide: Vec<u64> = narrow.iter().map(|v| u64::from(*v) << 20).collect();
+            for block_len in [128usize, 256].iter() {
+                let compressed = pack(&narrow, *block_len);
+                assert_eq!(narrow, decompress_to_vec::<u32>(&compressed).unwrap());
+                let compressed = pack(&wide, *block_len);
+                assert_eq!(wide, decompress_to_vec::<u64>(&compressed).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn vertical_unpack_agrees_on_every_width() {
+        for typesize in [4usize, 8].iter() {
+            for block_len in [128usize, 256].iter() {
+                for width in 0..=(*typesize as u32 * 8) {
+                    let values: Vec<u64> = (0..*block_len as u64)
+                        .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) & mask(width))
+                        .collect();
+                    let mut packed = vec![0u8; packed_length(*block_len, width, true)];
+                    pack_vertical(&values, width, *typesize, &mut packed);
+                    let mut scalar = vec![0u64; *block_len];
+                    unpack_vertical(&packed, width, *typesize, &mut scalar);
+                    assert_eq!(values, scalar, "width {}", width);
+                    // Called directly, a path forced by another test does not matter.
+                    #[cfg(target_arch = "x86_64")]
+                    {
+                        if width > 0 {
+                            let mut simd = vec![0u64; *block_len];
+                            if *typesize == 4 {
+                                sse2::unpack32(&packed, width, &mut simd);
+                            } else {
+                                sse2::unpack64(&packed, width, &mut simd);
+                            }
+                            assert_eq!(scalar, simd, "typesize {} width {}", typesize, width);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_streams_that_disagree_with_their_header() {
+        let values = volumes()[..300].to_vec();
+        let compressed = pack(&values, 128);
+        let mut dest = vec![0u32; values.len()];
+        let corrupt = |offset: usize, byte: u8| {
+            let mut stream = compressed.clone();
+            stream[offset] = byte;
+            decompress_to_vec::<u32>(&stream)
+        };
+        // Unknown block length and transform codes, and a block wider than `u32`.
+        assert_eq!(Err(CompressionError::CorruptInput), corrupt(5, 6));
+        assert_eq!(Err(CompressionError::CorruptInput), corrupt(6, 3));
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            corrupt(HEADER_LENGTH + 4, 33)
+        );
+        let mut trailing = compressed.clone();
+        trailing.push(0);
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&trailing, &mut dest)
+        );
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&compressed[..compressed.len() - 1], &mut dest)
+        );
+        // 2^31 values in 2 bytes, not even room for the block minimums.
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress_to_vec::<u32>(&[0xFF, 0xFF, 0xFF, 0x7F, 4, 7, 0, 0, 0, 0])
+        );
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            decompress_to_vec::<u64>(&compressed)
+        );
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            decompress(&compressed, &mut dest[..10])
+        );
+    }
+
+    #[test]
+    fn c_api_reads_native_endian_integers() {
+        let values: Vec<i64> = (0..1000i64).map(|i| 5_000_000 - i * 13).collect();
+        let bound = spreads_bitpack_compress_bound(values.len(), 8, 256);
+        let mut compressed = vec![0u8; bound as usize];
+        let len = spreads_bitpack_compress(
+            values.as_ptr() as *const libc::c_char,
+            values.len(),
+            compressed.as_mut_ptr() as *mut libc::c_char,
+            compressed.len(),
+            8,
+            1,
+            256,
+            0,
+        );
+        assert!(len > 0);
+        assert_eq!(
+            compress_to_vec(
+                &values,
+                &PackOptions {
+                    block_len: 256,
+                    zigzag: false
+                }
+            ),
+            Ok(compressed[..len as usize].to_vec())
+        );
+        let mut decoded = vec![0i64; values.len()];
+        assert_eq!(
+            values.len() as i32,
+            spreads_bitpack_decompress(
+                compressed.as_ptr() as *const libc::c_char,
+                len as usize,
+                decoded.as_mut_ptr() as *mut libc::c_char,
+                decoded.len(),
+                8,
+            )
+        );
+        assert_eq!(values, decoded);
+        assert_eq!(
+            CompressionError::InvalidArgument.code() as isize,
+            spreads_bitpack_compress_bound(10, 8, 64)
+        );
+    }
+}
@@ -0,0 +1,1054 @@
+//! Self-describing Blosc containers: a 16 bytes header followed by blocks that are
+//! optionally shuffled and compressed with one of the Blosc compressors. Containers
+//! of delta filtered data are preceded by a 2 bytes prefix that records the filter,
+//! see `BloscParams::delta`.
+
+use super::delta::{self, Delta};
+use super::{ffi_buffers, ffi_slice, to_c_result, CompressionError};
+use spreads_blosc_sys as ffi;
+use std::cell::RefCell;
+
+/// First byte of the delta prefix. It is never a valid Blosc format version, so
+/// other Blosc readers reject the prefixed container instead of misreading it.
+const DELTA_MARKER: u8 = 0;
+/// The marker followed by the `Delta` code.
+const DELTA_PREFIX_LEN: usize = 2;
+
+/// Largest delta filter buffer kept by a thread between calls. Larger inputs are
+/// filtered into a buffer allocated for the call.
+const RETAINED_FILTER_LEN: usize = 1 << 20;
+
+thread_local! {
+    /// Delta filtered copy of the input, kept to avoid an allocation per call.
+    static FILTERED: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Compressors available inside Blosc containers. The discriminants match the
+/// `BLOSC_*` compressor codes used by the `spreads_blosc_*` exports.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BloscCompressor {
+    BloscLz = ffi::BLOSC_BLOSCLZ as i32,
+    Lz4 = ffi::BLOSC_LZ4 as i32,
+    Lz4Hc = ffi::BLOSC_LZ4HC as i32,
+    Snappy = ffi::BLOSC_SNAPPY as i32,
+    Zlib = ffi::BLOSC_ZLIB as i32,
+    Zstd = ffi::BLOSC_ZSTD as i32,
+}
+
+impl BloscCompressor {
+    pub fn from_code(code: i32) -> Result<BloscCompressor, CompressionError> {
+        match code as u32 {
+            ffi::BLOSC_BLOSCLZ => Ok(BloscCompressor::BloscLz),
+            ffi::BLOSC_LZ4 => Ok(BloscCompressor::Lz4),
+            ffi::BLOSC_LZ4HC => Ok(BloscCompressor::Lz4Hc),
+            ffi::BLOSC_SNAPPY => Ok(BloscCompressor::Snappy),
+            ffi::BLOSC_ZLIB => Ok(BloscCompressor::Zlib),
+            ffi::BLOSC_ZSTD => Ok(BloscCompressor::Zstd),
+            _ => Err(CompressionError::UnsupportedCodec),
+        }
+    }
+
+    /// Nul-terminated compressor name as expected by `blosc_compress_ctx`.
+    fn name_with_nul(self) -> &'static str {
+        match self {
+            BloscCompressor::BloscLz => "blosclz\0",
+            BloscCompressor::Lz4 => "lz4\0",
+            BloscCompressor::Lz4Hc => "lz4hc\0",
+            BloscCompressor::Snappy => "snappy\0",
+            BloscCompressor::Zlib => "zlib\0",
+            BloscCompressor::Zstd => "zstd\0",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        let name = self.name_with_nul();
+        &name[..name.len() - 1]
+    }
+
+    /// Whether the cargo feature of the compressor is enabled. Containers of any
+    /// compressor that c-blosc was built with are still decompressed.
+    pub fn is_compiled(self) -> bool {
+        match self {
+            BloscCompressor::BloscLz => cfg!(feature = "blosclz"),
+            BloscCompressor::Lz4 | BloscCompressor::Lz4Hc => cfg!(feature = "lz4"),
+            BloscCompressor::Snappy => cfg!(feature = "snappy"),
+            BloscCompressor::Zlib => cfg!(feature = "zlib"),
+            BloscCompressor::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+}
+
+/// Shuffle filter applied to each block before compression.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shuffle {
+    NoShuffle = ffi::BLOSC_NOSHUFFLE as i32,
+    Shuffle = ffi::BLOSC_SHUFFLE as i32,
+    BitShuffle = ffi::BLOSC_BITSHUFFLE as i32,
+}
+
+impl Shuffle {
+    pub fn from_code(code: i32) -> Result<Shuffle, CompressionError> {
+        match code as u32 {
+            ffi::BLOSC_NOSHUFFLE => Ok(Shuffle::NoShuffle),
+            ffi::BLOSC_SHUFFLE => Ok(Shuffle::Shuffle),
+            ffi::BLOSC_BITSHUFFLE => Ok(Shuffle::BitShuffle),
+            _ => Err(CompressionError::InvalidArgument),
+        }
+    }
+}
+
+/// Settings of a Blosc compression call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BloscParams {
+    /// Compression level from 0 (plain copy) to 9.
+    pub level: i32,
+    pub shuffle: Shuffle,
+    /// Size in bytes of the element type, e.g. 8 for `f64`. From 1 to 255.
+    pub typesize: usize,
+    pub compressor: BloscCompressor,
+    /// Size of the independently compressed blocks, 0 selects it automatically.
+    pub blocksize: usize,
+    /// Number of Blosc internal threads, at least 1.
+    pub nthreads: i32,
+    /// Delta filter applied to the whole buffer before shuffling, requires a typesize
+    /// of 4 or 8. It is recorded in a prefix before the container, which `decompress`
+    /// reverses. Other Blosc implementations cannot read prefixed containers.
+    pub delta: Delta,
+}
+
+impl Default for BloscParams {
+    fn default() -> Self {
+        BloscParams {
+            level: 5,
+            shuffle: Shuffle::Shuffle,
+            typesize: 8,
+            compressor: BloscCompressor::Lz4,
+            blocksize: 0,
+            nthreads: 1,
+            delta: Delta::None,
+        }
+    }
+}
+
+impl BloscParams {
+    pub(crate) fn validate(&self) -> Result<(), CompressionError> {
+        if !(0..=9).contains(&self.level) {
+            return Err(CompressionError::InvalidLevel);
+        }
+        if self.typesize == 0
+            || self.typesize > ffi::BLOSC_MAX_TYPESIZE as usize
+            || self.nthreads < 1
+        {
+            return Err(CompressionError::InvalidArgument);
+        }
+        if !self.compressor.is_compiled() {
+            return Err(CompressionError::UnsupportedCodec);
+        }
+        self.delta.validate(self.typesize)
+    }
+}
+
+/// Reusable compression settings backed by the Blosc context API. It owns its settings
+/// and never touches the global Blosc state, so one instance can be shared between
+/// threads. Exposed over FFI as an opaque `SpreadsCompressor` handle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compressor {
+    params: BloscParams,
+}
+
+impl Compressor {
+    pub fn new(params: BloscParams) -> Result<Compressor, CompressionError> {
+        params.validate()?;
+        Ok(Compressor { params })
+    }
+
+    pub fn params(&self) -> &BloscParams {
+        &self.params
+    }
+
+    pub fn compress(&self, src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+        compress(&self.params, src, dest)
+    }
+
+    pub fn compress_to_vec(&self, src: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        compress_to_vec(&self.params, src)
+    }
+
+    /// Decompresses a container with the thread count of the settings.
+    pub fn decompress(&self, src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+        decompress(src, dest, self.params.nthreads)
+    }
+
+    pub fn decompress_to_vec(&self, src: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        decompress_to_vec(src, self.params.nthreads)
+    }
+}
+
+/// Fields of the fixed-size Blosc header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BloscHeader {
+    pub version: u8,
+    pub compressor_version: u8,
+    pub flags: u8,
+    pub typesize: u8,
+    /// Uncompressed size.
+    pub nbytes: usize,
+    pub blocksize: usize,
+    /// Compressed size including the header.
+    pub cbytes: usize,
+}
+
+impl BloscHeader {
+    /// Parses and validates the header of a Blosc container stored in `src`.
+    pub fn parse(src: &[u8]) -> Result<BloscHeader, CompressionError> {
+        if src.len() < ffi::BLOSC_MIN_HEADER_LENGTH as usize {
+            return Err(CompressionError::CorruptInput);
+        }
+        let read_u32 = |offset: usize| {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&src[offset..offset + 4]);
+            u32::from_le_bytes(bytes) as usize
+        };
+        let header = BloscHeader {
+            version: src[0],
+            compressor_version: src[1],
+            flags: src[2],
+            typesize: src[3],
+            nbytes: read_u32(4),
+            blocksize: read_u32(8),
+            cbytes: read_u32(12),
+        };
+        let valid = header.version >= 1
+            && header.version as u32 <= ffi::BLOSC_VERSION_FORMAT
+            && header.typesize > 0
+            && header.nbytes <= ffi::BLOSC_MAX_BUFFERSIZE as usize
+            && header.cbytes >= ffi::BLOSC_MIN_HEADER_LENGTH as usize
+            && header.cbytes <= src.len()
+            && (header.nbytes == 0 || header.blocksize > 0);
+        if !valid {
+            return Err(CompressionError::CorruptInput);
+        }
+        Ok(header)
+    }
+}
+
+/// Metadata of a Blosc container, as reported by `spreads_blosc_inspect`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BloscInfo {
+    /// Uncompressed size.
+    pub nbytes: usize,
+    /// Compressed size including the header.
+    pub cbytes: usize,
+    pub blocksize: usize,
+    pub typesize: usize,
+    /// Raw header flags, see `BLOSC_DOSHUFFLE`, `BLOSC_MEMCPYED` and `BLOSC_DOBITSHUFFLE`.
+    pub flags: i32,
+    /// `Shuffle` code of the applied filter.
+    pub shuffle: i32,
+    /// 1 if the blocks are stored without compression, 0 otherwise.
+    pub memcpyed: i32,
+    /// Blosc format version.
+    pub version: i32,
+    /// Format version of the compressor.
+    pub compressor_version: i32,
+    /// Nul-terminated name of the compression library, e.g. `LZ4` or `Zstd`.
+    pub complib: [u8; 16],
+    /// `Delta` code of the filter recorded in the prefix, 0 for plain containers.
+    pub delta: i32,
+}
+
+impl BloscInfo {
+    /// Name of the compression library that produced the container.
+    pub fn complib(&self) -> &str {
+        let len = self.complib.iter().position(|b| *b == 0).unwrap_or(0);
+        std::str::from_utf8(&self.complib[..len]).unwrap_or("")
+    }
+}
+
+/// Splits the delta prefix, if any, from the Blosc container in `src`.
+fn split_prefix(src: &[u8]) -> Result<(Delta, &[u8]), CompressionError> {
+    match src {
+        [DELTA_MARKER, code, container @ ..] => match Delta::from_code(*code as i32) {
+            Ok(Delta::None) | Err(_) => Err(CompressionError::CorruptInput),
+            Ok(delta) => Ok((delta, container)),
+        },
+        _ => Ok((Delta::None, src)),
+    }
+}
+
+/// Validates the header of the Blosc container in `src` and returns its metadata
+/// without decompressing anything. `cbytes` includes the delta prefix.
+pub fn inspect(src: &[u8]) -> Result<BloscInfo, CompressionError> {
+    let (delta, container) = split_prefix(src)?;
+    let mut info = inspect_container(container)?;
+    if delta != Delta::None {
+        delta
+            .validate(info.typesize)
+            .map_err(|_| CompressionError::CorruptInput)?;
+        info.cbytes += DELTA_PREFIX_LEN;
+        info.delta = delta as i32;
+    }
+    Ok(info)
+}
+
+fn inspect_container(src: &[u8]) -> Result<BloscInfo, CompressionError> {
+    let header = BloscHeader::parse(src)?;
+    // Compressor format is stored in the 3 highest bits of the flags, only
+    // BloscLZ, LZ4, Snappy, Zlib and Zstd formats (0 to 4) exist.
+    if header.flags >> 5 > 4 {
+        return Err(CompressionError::CorruptInput);
+    }
+    let memcpyed = header.flags as u32 & ffi::BLOSC_MEMCPYED != 0;
+    if memcpyed && header.cbytes != header.nbytes + ffi::BLOSC_MAX_OVERHEAD as usize {
+        return Err(CompressionError::CorruptInput);
+    }
+
+    let cbuffer = src.as_ptr() as *const libc::c_void;
+    let mut info = BloscInfo::default();
+    let mut typesize = 0usize;
+    let complib = unsafe {
+        ffi::blosc_cbuffer_sizes(
+            cbuffer,
+            &mut info.nbytes,
+            &mut info.cbytes,
+            &mut info.blocksize,
+        );
+        ffi::blosc_cbuffer_metainfo(cbuffer, &mut typesize, &mut info.flags);
+        ffi::blosc_cbuffer_versions(cbuffer, &mut info.version, &mut info.compressor_version);
+        std::ffi::CStr::from_ptr(ffi::blosc_cbuffer_complib(cbuffer)).to_bytes()
+    };
+    info.typesize = typesize;
+    info.memcpyed = memcpyed as i32;
+    let flags = info.flags as u32;
+    info.shuffle = if flags & ffi::BLOSC_DOBITSHUFFLE != 0 {
+        Shuffle::BitShuffle as i32
+    } else if flags & ffi::BLOSC_DOSHUFFLE != 0 {
+        Shuffle::Shuffle as i32
+    } else {
+        Shuffle::NoShuffle as i32
+    };
+    let len = complib.len().min(info.complib.len() - 1);
+    info.complib[..len].copy_from_slice(&complib[..len]);
+
+    // Blosc zeroes the outputs for formats it does not support.
+    if info.nbytes != header.nbytes || info.cbytes != header.cbytes || info.typesize == 0 {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok(info)
+}
+
+/// Destination size that is always enough to compress `src_len` bytes.
+pub fn compress_bound(src_len: usize) -> Result<usize, CompressionError> {
+    if src_len > ffi::BLOSC_MAX_BUFFERSIZE as usize {
+        return Err(CompressionError::LengthOverflow);
+    }
+    Ok(src_len + ffi::BLOSC_MAX_OVERHEAD as usize + DELTA_PREFIX_LEN)
+}
+
+/// Compresses `src` into a Blosc container in `dest` and returns the container size.
+/// With a delta filter `src` must hold whole elements.
+pub fn compress(
+    params: &BloscParams,
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    params.validate()?;
+    compress_bound(src.len())?;
+    if params.delta == Delta::None {
+        return compress_container(params, src, dest);
+    }
+    if dest.len() < DELTA_PREFIX_LEN {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    let (prefix, container) = dest.split_at_mut(DELTA_PREFIX_LEN);
+    prefix.copy_from_slice(&[DELTA_MARKER, params.delta as u8]);
+    let mut filter_and_compress = |filtered: &mut [u8]| {
+        delta::encode(params.delta, params.typesize, src, filtered)?;
+        compress_container(params, filtered, container)
+    };
+    let len = if src.len() > RETAINED_FILTER_LEN {
+        filter_and_compress(&mut vec![0u8; src.len()])?
+    } else {
+        FILTERED.with(|filtered| {
+            let mut filtered = filtered.borrow_mut();
+            if filtered.len() < src.len() {
+                filtered.resize(src.len(), 0);
+            }
+            filter_and_compress(&mut filtered[..src.len()])
+        })?
+    };
+    Ok(DELTA_PREFIX_LEN + len)
+}
+
+fn compress_container(
+    params: &BloscParams,
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    let dest_len = dest.len().min(i32::MAX as usize);
+    let compressed = unsafe {
+        ffi::blosc_compress_ctx(
+            params.level,
+            params.shuffle as libc::c_int,
+            params.typesize,
+            src.len(),
+            src.as_ptr() as *const libc::c_void,
+            dest.as_mut_ptr() as *mut libc::c_void,
+            dest_len,
+            params.compressor.name_with_nul().as_ptr() as *const libc::c_char,
+            params.blocksize,
+            params.nthreads,
+        )
+    };
+    match compressed {
+        0 => Err(CompressionError::BufferTooSmall),
+        // Blosc returns -5 when the compressor is not compiled in.
+        -5 => Err(CompressionError::UnsupportedCodec),
+        n if n < 0 => Err(CompressionError::Internal),
+        n => Ok(n as usize),
+    }
+}
+
+/// Compresses `src` into a new vector holding exactly the Blosc container.
+pub fn compress_to_vec(params: &BloscParams, src: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let mut dest = vec![0u8; compress_bound(src.len())?];
+    let len = compress(params, src, &mut dest)?;
+    dest.truncate(len);
+    dest.shrink_to_fit();
+    Ok(dest)
+}
+
+/// Decompresses the Blosc container in `src` into `dest` and reverses the delta
+/// filter recorded in its prefix. The uncompressed size is read from the header and
+/// checked against `dest` before decompressing.
+pub fn decompress(src: &[u8], dest: &mut [u8], nthreads: i32) -> Result<usize, CompressionError> {
+    if nthreads < 1 {
+        return Err(CompressionError::InvalidArgument);
+    }
+    let (delta, src) = split_prefix(src)?;
+    let len = decompress_container(src, dest, nthreads)?;
+    if delta != Delta::None {
+        let typesize = BloscHeader::parse(src)?.typesize as usize;
+        if delta.validate(typesize).is_err() || !len.is_multiple_of(typesize) {
+            return Err(CompressionError::CorruptInput);
+        }
+        delta::decode_filtered(delta, typesize, &mut dest[..len]);
+    }
+    Ok(len)
+}
+
+fn decompress_container(
+    src: &[u8],
+    dest: &mut [u8],
+    nthreads: i32,
+) -> Result<usize, CompressionError> {
+    let header = BloscHeader::parse(src)?;
+    if header.nbytes > dest.len() {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    if header.nbytes == 0 {
+        return Ok(0);
+    }
+    let decompressed = unsafe {
+        ffi::blosc_decompress_ctx(
+            src.as_ptr() as *const libc::c_void,
+            dest.as_mut_ptr() as *mut libc::c_void,
+            header.nbytes,
+            nthreads,
+        )
+    };
+    if decompressed <= 0 || decompressed as usize != header.nbytes {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok(decompressed as usize)
+}
+
+/// Decompresses the Blosc container in `src` into a new vector sized from the header.
+pub fn decompress_to_vec(src: &[u8], nthreads: i32) -> Result<Vec<u8>, CompressionError> {
+    let header = BloscHeader::parse(split_prefix(src)?.1)?;
+    let mut dest = vec![0u8; header.nbytes];
+    decompress(src, &mut dest, nthreads)?;
+    Ok(dest)
+}
+
+/// Decompresses only the blocks covering `nitems` elements starting at element `start`
+/// and copies these elements to `dest`. The element size is the container typesize.
+/// Returns the number of bytes written to `dest`. Delta filtered containers fail with
+/// `InvalidArgument`, as every element depends on all the previous ones.
+pub fn getitem(
+    src: &[u8],
+    start: usize,
+    nitems: usize,
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    if split_prefix(src)?.0 != Delta::None {
+        return Err(CompressionError::InvalidArgument);
+    }
+    let header = BloscHeader::parse(src)?;
+    let typesize = header.typesize as usize;
+    let end = start
+        .checked_add(nitems)
+        .ok_or(CompressionError::InvalidArgument)?;
+    if end > header.nbytes / typesize {
+        return Err(CompressionError::InvalidArgument);
+    }
+    let len = nitems * typesize;
+    if dest.len() < len {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    if nitems == 0 {
+        return Ok(0);
+    }
+    let copied = unsafe {
+        ffi::blosc_getitem(
+            src.as_ptr() as *const libc::c_void,
+            start as libc::c_int,
+            nitems as libc::c_int,
+            dest.as_mut_ptr() as *mut libc::c_void,
+        )
+    };
+    if copied < 0 || copied as usize != len {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok(len)
+}
+
+/// Returns the destination size that is always enough for `spreads_blosc_compress`
+/// or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_blosc_compress_bound(src_length: usize) -> isize {
+    match compress_bound(src_length) {
+        Ok(bound) => bound as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// Compresses `src` into a self-describing Blosc container. `shuffle_mode` is one of
+/// `BLOSC_NOSHUFFLE`, `BLOSC_SHUFFLE` or `BLOSC_BITSHUFFLE` and `codec` is a `BLOSC_*`
+/// compressor code. Returns the container size or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_blosc_compress(
+    clevel: libc::c_int,
+    shuffle_mode: libc::c_int,
+    typesize: usize,
+    codec: libc::c_int,
+    blocksize: usize,
+    nthreads: libc::c_int,
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    let params = Shuffle::from_code(shuffle_mode).and_then(|shuffle| {
+        Ok(BloscParams {
+            level: clevel,
+            shuffle,
+            typesize,
+            compressor: BloscCompressor::from_code(codec)?,
+            blocksize,
+            nthreads,
+            delta: Delta::None,
+        })
+    });
+    ffi_compress(params, src, src_length, dest, dest_length)
+}
+
+fn ffi_compress(
+    params: Result<BloscParams, CompressionError>,
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+        .and_then(|(src, dest)| compress(&params?, src, dest));
+    to_c_result(result)
+}
+
+/// Decompresses a Blosc container, the uncompressed size is read from its header.
+/// Returns the number of bytes written to `dest` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_blosc_decompress(
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    nthreads: libc::c_int,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+        .and_then(|(src, dest)| decompress(src, dest, nthreads));
+    to_c_result(result)
+}
+
+/// Copies `nitems` elements starting at element `start` from the Blosc container
+/// in `src` to `dest`, decompressing only the blocks that cover the range. `src` must
+/// hold the whole container. Returns the number of bytes written or a negative error
+/// code.
+#[no_mangle]
+pub extern "C" fn spreads_blosc_getitem(
+    src: *const libc::c_char,
+    src_length: usize,
+    start: usize,
+    nitems: usize,
+    dest: *mut libc::c_char,
+    dest_len: usize,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_len) }
+        .and_then(|(src, dest)| getitem(src, start, nitems, dest));
+    to_c_result(result)
+}
+
+/// Validates the header of the Blosc container in `src` and fills `info` with its
+/// metadata. Returns 0 on success or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_blosc_inspect(
+    src: *const libc::c_char,
+    src_length: usize,
+    info: *mut BloscInfo,
+) -> libc::c_int {
+    if info.is_null() {
+        return CompressionError::InvalidArgument.code();
+    }
+    match unsafe { ffi_slice(src, src_length) }.and_then(inspect) {
+        Ok(result) => {
+            unsafe { *info = result };
+            0
+        }
+        Err(err) => err.code(),
+    }
+}
+
+/// Creates a compressor handle with the given settings, see `spreads_blosc_compress`
+/// for the meaning of the arguments. Returns NULL for invalid settings. The handle is
+/// thread-safe and must be released with `spreads_compressor_free`.
+#[no_mangle]
+pub extern "C" fn spreads_compressor_new(
+    codec: libc::c_int,
+    level: libc::c_int,
+    typesize: usize,
+    shuffle: libc::c_int,
+    nthreads: libc::c_int,
+) -> *mut Compressor {
+    new_compressor(codec, level, typesize, shuffle, 0, nthreads)
+}
+
+/// Same as `spreads_compressor_new` with a delta pre-filter, `delta_mode` is 0 for
+/// none, 1 for delta or 2 for delta-of-delta and requires a typesize of 4 or 8. The
+/// handle reverses the filter when decompressing.
+#[no_mangle]
+pub extern "C" fn spreads_compressor_new_with_delta(
+    codec: libc::c_int,
+    level: libc::c_int,
+    typesize: usize,
+    shuffle: libc::c_int,
+    delta_mode: libc::c_int,
+    nthreads: libc::c_int,
+) -> *mut Compressor {
+    new_compressor(codec, level, typesize, shuffle, delta_mode, nthreads)
+}
+
+fn new_compressor(
+    codec: libc::c_int,
+    level: libc::c_int,
+    typesize: usize,
+    shuffle: libc::c_int,
+    delta_mode: libc::c_int,
+    nthreads: libc::c_int,
+) -> *mut Compressor {
+    let compressor = Shuffle::from_code(shuffle).and_then(|shuffle| {
+        Compressor::new(BloscParams {
+            level,
+            shuffle,
+            typesize,
+            compressor: BloscCompressor::from_code(codec)?,
+            blocksize: 0,
+            nthreads,
+            delta: Delta::from_code(delta_mode)?,
+        })
+    });
+    match compressor {
+        Ok(compressor) => Box::into_raw(Box::new(compressor)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Releases a handle created by `spreads_compressor_new`. NULL is ignored.
+#[no_mangle]
+pub extern "C" fn spreads_compressor_free(compressor: *mut Compressor) {
+    if !compressor.is_null() {
+        unsafe { drop(Box::from_raw(compressor)) };
+    }
+}
+
+fn with_compressor<F>(compressor: *const Compressor, f: F) -> libc::c_int
+where
+    F: FnOnce(&Compressor) -> libc::c_int,
+{
+    match unsafe { compressor.as_ref() } {
+        Some(compressor) => f(compressor),
+        None => CompressionError::InvalidArgument.code(),
+    }
+}
+
+/// Compresses `src` into a Blosc container using the handle settings. Returns the
+/// container size or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_compressor_compress(
+    compressor: *const Compressor,
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    with_compressor(compressor, |compressor| {
+        ffi_compress(Ok(compressor.params), src, src_length, dest, dest_length)
+    })
+}
+
+/// Decompresses a Blosc container using the handle thread count.
+/// Returns the number of bytes written to `dest` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_compressor_decompress(
+    compressor: *const Compressor,
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    with_compressor(compressor, |compressor| {
+        let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+            .and_then(|(src, dest)| compressor.decompress(src, dest));
+        to_c_result(result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series() -> Vec<u8> {
+        (0..10_000)
+            .flat_map(|i| (100.0 + (i as f64 * 0.01).sin()).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn could_roundtrip_all_compressors_and_shuffles() {
+        let original = series();
+        let compressors = [
+            BloscCompressor::BloscLz,
+            BloscCompressor::Lz4,
+            BloscCompressor::Lz4Hc,
+            BloscCompressor::Zlib,
+            BloscCompressor::Zstd,
+        ];
+        for compressor in compressors.iter().filter(|c| c.is_compiled()) {
+            for shuffle in [Shuffle::NoShuffle, Shuffle::Shuffle, Shuffle::BitShuffle].iter() {
+                let params = BloscParams {
+                    compressor: *compressor,
+                    shuffle: *shuffle,
+                    ..BloscParams::default()
+                };
+                let compressed = compress_to_vec(&params, &original).unwrap();
+                let header = BloscHeader::parse(&compressed).unwrap();
+                assert_eq!(original.len(), header.nbytes);
+                assert_eq!(compressed.len(), header.cbytes);
+                assert_eq!(8, header.typesize);
+                assert_eq!(original, decompress_to_vec(&compressed, 2).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn decompress_checks_header() {
+        let original = series();
+        let compressed = compress_to_vec(&BloscParams::default(), &original).unwrap();
+
+        let mut short = vec![0u8; original.len() - 1];
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            decompress(&compressed, &mut short, 1)
+        );
+
+        let truncated = &compressed[..compressed.len() - 1];
+        let mut dest = vec![0u8; original.len()];
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(truncated, &mut dest, 1)
+        );
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&[0u8; 8], &mut dest, 1)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn could_get_items() {
+        let values: Vec<f64> = (0..100_000).map(|i| i as f64 * 0.5).collect();
+        let original: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let params = BloscParams {
+            blocksize: 16 * 1024,
+            ..BloscParams::default()
+        };
+        let compressed = compress_to_vec(&params, &original).unwrap();
+
+        let mut dest = vec![0u8; 10 * 8];
+        assert_eq!(Ok(80), getitem(&compressed, 54_321, 10, &mut dest));
+        for (i, chunk) in dest.chunks(8).enumerate() {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(chunk);
+            assert_eq!(values[54_321 + i], f64::from_le_bytes(bytes));
+        }
+
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            getitem(&compressed, values.len() - 5, 10, &mut dest)
+        );
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            getitem(&compressed, 0, 11, &mut dest)
+        );
+        assert_eq!(
+            80,
+            spreads_blosc_getitem(
+                compressed.as_ptr() as *const libc::c_char,
+                compressed.len(),
+                0,
+                10,
+                dest.as_mut_ptr() as *mut libc::c_char,
+                dest.len()
+            )
+        );
+        assert_eq!(
+            CompressionError::CorruptInput.code(),
+            spreads_blosc_getitem(
+                compressed.as_ptr() as *const libc::c_char,
+                compressed.len() - 1,
+                0,
+                10,
+                dest.as_mut_ptr() as *mut libc::c_char,
+                dest.len()
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn could_inspect_container() {
+        let original = series();
+        let params = BloscParams {
+            compressor: BloscCompressor::Zstd,
+            shuffle: Shuffle::BitShuffle,
+            ..BloscParams::default()
+        };
+        let compressed = compress_to_vec(&params, &original).unwrap();
+
+        let mut info = BloscInfo::default();
+        assert_eq!(
+            0,
+            spreads_blosc_inspect(
+                compressed.as_ptr() as *const libc::c_char,
+                compressed.len(),
+                &mut info
+            )
+        );
+        assert_eq!(original.len(), info.nbytes);
+        assert_eq!(compressed.len(), info.cbytes);
+        assert_eq!(8, info.typesize);
+        assert_eq!(Shuffle::BitShuffle as i32, info.shuffle);
+        assert_eq!(0, info.memcpyed);
+        assert_eq!("Zstd", info.complib());
+
+        let mut corrupt = compressed.clone();
+        corrupt[2] |= 0xE0;
+        assert_eq!(Err(CompressionError::CorruptInput), inspect(&corrupt));
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            inspect(&compressed[..10])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn could_share_compressor_between_threads() {
+        let compressor = std::sync::Arc::new(
+            Compressor::new(BloscParams {
+                compressor: BloscCompressor::Zstd,
+                nthreads: 2,
+                ..BloscParams::default()
+            })
+            .unwrap(),
+        );
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let compressor = compressor.clone();
+                std::thread::spawn(move || {
+                    let original: Vec<u8> =
+                        (0..50_000u64).flat_map(|i| (i * t).to_le_bytes()).collect();
+                    for _ in 0..10 {
+                        let compressed = compressor.compress_to_vec(&original).unwrap();
+                        assert_eq!(original, compressor.decompress_to_vec(&compressed).unwrap());
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().expect("should join");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn could_use_compressor_handle() {
+        let handle = spreads_compressor_new(BloscCompressor::Lz4 as i32, 5, 8, 1, 1);
+        assert!(!handle.is_null());
+        assert!(spreads_compressor_new(BloscCompressor::Lz4 as i32, 5, 0, 1, 1).is_null());
+
+        let original = series();
+        let mut compressed = vec![0u8; compress_bound(original.len()).unwrap()];
+        let mut decompressed = vec![0u8; original.len()];
+        let compressed_len = spreads_compressor_compress(
+            handle,
+            original.as_ptr() as *const libc::c_char,
+            original.len(),
+            compressed.as_mut_ptr() as *mut libc::c_char,
+            compressed.len(),
+        );
+        assert!(compressed_len > 0);
+        let decompressed_len = spreads_compressor_decompress(
+            handle,
+            compressed.as_ptr() as *const libc::c_char,
+            compressed_len as usize,
+            decompressed.as_mut_ptr() as *mut libc::c_char,
+            decompressed.len(),
+        );
+        assert_eq!(original.len() as i32, decompressed_len);
+        assert_eq!(original, decompressed);
+        spreads_compressor_free(handle);
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn delta_prefilter_retains_a_bounded_buffer() {
+        let timestamps: Vec<u8> = (0..RETAINED_FILTER_LEN as i64 / 4)
+            .flat_map(|i| (1_600_000_000_000_000_000 + i * 1_000_000).to_le_bytes())
+            .collect();
+        let params = BloscParams {
+            delta: Delta::Delta,
+            ..BloscParams::default()
+        };
+        for len in [1000, timestamps.len()].iter() {
+            let compressed = compress_to_vec(&params, &timestamps[..*len]).unwrap();
+            assert_eq!(
+                &timestamps[..*len],
+                &decompress_to_vec(&compressed, 1).unwrap()[..]
+            );
+            let retained = FILTERED.with(|filtered| filtered.borrow().capacity());
+            assert!(retained <= RETAINED_FILTER_LEN);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "lz4", feature = "zstd"))]
+    fn delta_prefilter_shrinks_timestamps() {
+        let timestamps: Vec<u8> = (0..100_000i64)
+            .flat_map(|i| (1_600_000_000_000_000_000 + i * 1_000_000_000 + i % 7).to_le_bytes())
+            .collect();
+        let plain = compress_to_vec(&BloscParams::default(), &timestamps).unwrap();
+        for filter in [Delta::Delta, Delta::DeltaOfDelta].iter() {
+            let compressor = Compressor::new(BloscParams {
+                delta: *filter,
+                ..BloscParams::default()
+            })
+            .unwrap();
+            let compressed = compressor.compress_to_vec(&timestamps).unwrap();
+            assert!(compressed.len() < plain.len(), "{:?}", filter);
+            assert_eq!(
+                timestamps,
+                compressor.decompress_to_vec(&compressed).unwrap()
+            );
+            // The filter is recorded, so plain decompression reverses it as well.
+            assert_eq!(timestamps, decompress_to_vec(&compressed, 1).unwrap());
+            let info = inspect(&compressed).unwrap();
+            assert_eq!(*filter as i32, info.delta);
+            assert_eq!(compressed.len(), info.cbytes);
+            assert_eq!(
+                Err(CompressionError::InvalidArgument),
+                getitem(&compressed, 0, 1, &mut [0u8; 8])
+            );
+            let mut corrupted = compressed.clone();
+            corrupted[1] = 3;
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                decompress_to_vec(&corrupted, 1)
+            );
+        }
+
+        let handle = spreads_compressor_new_with_delta(BloscCompressor::Zstd as i32, 5, 8, 1, 2, 1);
+        assert!(!handle.is_null());
+        let mut compressed = vec![0u8; compress_bound(timestamps.len()).unwrap()];
+        let mut decompressed = vec![0u8; timestamps.len()];
+        let compressed_len = spreads_compressor_compress(
+            handle,
+            timestamps.as_ptr() as *const libc::c_char,
+            timestamps.len(),
+            compressed.as_mut_ptr() as *mut libc::c_char,
+            compressed.len(),
+        );
+        assert!(compressed_len > 0);
+        assert_eq!(
+            timestamps.len() as i32,
+            spreads_compressor_decompress(
+                handle,
+                compressed.as_ptr() as *const libc::c_char,
+                compressed_len as usize,
+                decompressed.as_mut_ptr() as *mut libc::c_char,
+                decompressed.len(),
+            )
+        );
+        assert_eq!(timestamps, decompressed);
+        spreads_compressor_free(handle);
+
+        assert!(
+            spreads_compressor_new_with_delta(BloscCompressor::Lz4 as i32, 5, 2, 1, 1, 1).is_null()
+        );
+        assert!(
+            spreads_compressor_new_with_delta(BloscCompressor::Lz4 as i32, 5, 8, 1, 3, 1).is_null()
+        );
+    }
+
+    #[test]
+    fn compress_validates_params() {
+        let mut dest = [0u8; 64];
+        let params = BloscParams {
+            typesize: 0,
+            ..BloscParams::default()
+        };
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            compress(&params, &[1, 2], &mut dest)
+        );
+        #[cfg(not(feature = "snappy"))]
+        assert_eq!(
+            Err(CompressionError::UnsupportedCodec),
+            compress(
+                &BloscParams {
+                    compressor: BloscCompressor::Snappy,
+                    ..BloscParams::default()
+                },
+                &[1, 2],
+                &mut dest
+            )
+        );
+        let dest_ptr = dest.as_mut_ptr() as *mut libc::c_char;
+        assert_eq!(
+            CompressionError::UnsupportedCodec.code(),
+            spreads_blosc_compress(5, 1, 8, 42, 0, 1, std::ptr::null(), 0, dest_ptr, 64)
+        );
+    }
+}
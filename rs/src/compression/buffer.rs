@@ -0,0 +1,362 @@
+//! Decompression into buffers allocated with `spreads_mem_malloc` for input whose
+//! decompressed size is not known upfront. The buffer grows with `spreads_mem_realloc`
+//! up to a caller limit, so that a small malicious input cannot exhaust memory.
+
+#[cfg(feature = "zlib")]
+use super::zlib;
+#[cfg(feature = "zstd")]
+use super::zstd::ZstdDStream;
+use super::{Codec, CompressionError, MAX_INPUT_LENGTH};
+use crate::mem_allocation::{spreads_mem_free, spreads_mem_malloc, spreads_mem_realloc};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// Initial capacity for input without a declared content size.
+const MIN_CAPACITY: usize = 4096;
+
+/// Bytes allocated with `spreads_mem_malloc`. `into_raw` hands the allocation over to
+/// the caller, who releases it with `spreads_mem_free`.
+pub struct MemBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    capacity: usize,
+}
+
+unsafe impl Send for MemBuffer {}
+unsafe impl Sync for MemBuffer {}
+
+impl MemBuffer {
+    /// Allocates a zeroed buffer of `capacity` bytes, of which none are in use.
+    fn with_capacity(capacity: usize) -> Result<MemBuffer, CompressionError> {
+        // malloc(0) may return NULL, which is not an error.
+        let capacity = capacity.max(1);
+        let ptr = NonNull::new(spreads_mem_malloc(capacity) as *mut u8)
+            .ok_or(CompressionError::Internal)?;
+        unsafe { std::ptr::write_bytes(ptr.as_ptr(), 0, capacity) };
+        Ok(MemBuffer {
+            ptr,
+            len: 0,
+            capacity,
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the allocation and the number of bytes in use. The pointer must be
+    /// released with `spreads_mem_free`.
+    pub fn into_raw(self) -> (*mut u8, usize) {
+        let raw = (self.ptr.as_ptr(), self.len);
+        std::mem::forget(self);
+        raw
+    }
+
+    /// The whole allocation, including the bytes not in use.
+    fn storage(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.capacity) }
+    }
+
+    #[cfg(any(feature = "zstd", feature = "zlib"))]
+    fn spare(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.storage()[len..]
+    }
+
+    /// Doubles the capacity, but not beyond `limit`.
+    fn grow(&mut self, limit: usize) -> Result<(), CompressionError> {
+        if self.capacity >= limit {
+            return Err(CompressionError::OutputLimitExceeded);
+        }
+        let capacity = self.capacity.saturating_mul(2).min(limit);
+        self.resize(capacity)?;
+        let old = self.capacity;
+        unsafe { std::ptr::write_bytes(self.ptr.as_ptr().add(old), 0, capacity - old) };
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /// Releases the unused tail of the allocation.
+    fn shrink_to_fit(&mut self) -> Result<(), CompressionError> {
+        let capacity = self.len.max(1);
+        if capacity < self.capacity {
+            self.resize(capacity)?;
+            self.capacity = capacity;
+        }
+        Ok(())
+    }
+
+    fn resize(&mut self, capacity: usize) -> Result<(), CompressionError> {
+        let ptr = spreads_mem_realloc(self.ptr.as_ptr() as *mut libc::c_void, capacity);
+        // On failure realloc leaves the original allocation intact.
+        self.ptr = NonNull::new(ptr as *mut u8).ok_or(CompressionError::Internal)?;
+        Ok(())
+    }
+}
+
+impl Deref for MemBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for MemBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for MemBuffer {
+    fn drop(&mut self) {
+        spreads_mem_free(self.ptr.as_ptr() as *mut libc::c_void);
+    }
+}
+
+/// Decompresses `input` into a new buffer of at most `limit` bytes. Fails with
+/// `OutputLimitExceeded` as soon as the output would exceed the limit.
+pub(crate) fn decompress(
+    codec: Codec,
+    input: &[u8],
+    limit: usize,
+) -> Result<MemBuffer, CompressionError> {
+    let limit = limit.min(MAX_INPUT_LENGTH);
+    let mut buffer = match codec {
+        Codec::Lz4 | Codec::Lz4Hc | Codec::Snappy => decompress_blocks(codec, input, limit)?,
+        #[cfg(feature = "zstd")]
+        Codec::Zstd => decompress_zstd(input, limit)?,
+        #[cfg(feature = "zlib")]
+        Codec::Zlib => inflate(input, limit, zlib::WINDOW_BITS_ZLIB)?,
+        #[cfg(feature = "zlib")]
+        Codec::Deflate => inflate(input, limit, zlib::WINDOW_BITS_DEFLATE)?,
+        #[cfg(feature = "zlib")]
+        Codec::Gzip => inflate(input, limit, zlib::WINDOW_BITS_GZIP)?,
+        #[cfg(not(all(feature = "zstd", feature = "zlib")))]
+        _ => return Err(CompressionError::UnsupportedCodec),
+    };
+    buffer.shrink_to_fit()?;
+    Ok(buffer)
+}
+
+fn initial_capacity(input: &[u8], limit: usize) -> usize {
+    input.len().saturating_mul(4).max(MIN_CAPACITY).min(limit)
+}
+
+/// Raw blocks have no size header and cannot be decoded incrementally, so the block
+/// is decoded again into a larger buffer until it fits.
+fn decompress_blocks(
+    codec: Codec,
+    input: &[u8],
+    limit: usize,
+) -> Result<MemBuffer, CompressionError> {
+    let mut buffer = MemBuffer::with_capacity(initial_capacity(input, limit))?;
+    loop {
+        let capacity = buffer.capacity.min(limit);
+        match codec.decompress(input, &mut buffer.storage()[..capacity]) {
+            Ok(len) => {
+                buffer.len = len;
+                return Ok(buffer);
+            }
+            Err(CompressionError::BufferTooSmall) => buffer.grow(limit)?,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn decompress_zstd(input: &[u8], limit: usize) -> Result<MemBuffer, CompressionError> {
+    let content_size = unsafe {
+        spreads_blosc_sys::ZSTD_getFrameContentSize(
+            input.as_ptr() as *const libc::c_void,
+            input.len(),
+        )
+    };
+    let capacity = match content_size {
+        spreads_blosc_sys::ZSTD_CONTENTSIZE_ERROR => return Err(CompressionError::CorruptInput),
+        spreads_blosc_sys::ZSTD_CONTENTSIZE_UNKNOWN => initial_capacity(input, limit),
+        size if size > limit as u64 => return Err(CompressionError::OutputLimitExceeded),
+        // Concatenated frames need more than the size of the first one.
+        size => size as usize,
+    };
+    let mut buffer = MemBuffer::with_capacity(capacity)?;
+    let mut stream = ZstdDStream::new()?;
+    let mut pos = 0;
+    loop {
+        // The frame epilogue may complete with a full buffer, so the buffer only grows
+        // when the stream cannot progress without more space.
+        let (consumed, produced, hint) = stream.decompress_chunk(&input[pos..], buffer.spare())?;
+        pos += consumed;
+        buffer.len += produced;
+        if hint == 0 && pos == input.len() {
+            return Ok(buffer);
+        }
+        if consumed == 0 && produced == 0 {
+            if buffer.len < buffer.capacity {
+                // No progress with output space left: the input is truncated.
+                return Err(CompressionError::CorruptInput);
+            }
+            buffer.grow(limit)?;
+        }
+    }
+}
+
+#[cfg(feature = "zlib")]
+fn inflate(input: &[u8], limit: usize, window_bits: i32) -> Result<MemBuffer, CompressionError> {
+    let mut buffer = MemBuffer::with_capacity(initial_capacity(input, limit))?;
+    let mut inflater = zlib::Inflater::new(window_bits)?;
+    let mut pos = 0;
+    loop {
+        let (status, consumed, produced) = inflater.inflate(&input[pos..], buffer.spare());
+        pos += consumed;
+        buffer.len += produced;
+        if zlib::check_status(status)? {
+            if window_bits != zlib::WINDOW_BITS_GZIP || pos == input.len() {
+                return Ok(buffer);
+            }
+            // Concatenated gzip member.
+            inflater.reset()?;
+            continue;
+        }
+        if consumed == 0 && produced == 0 {
+            if buffer.len < buffer.capacity {
+                return Err(CompressionError::CorruptInput);
+            }
+            buffer.grow(limit)?;
+        }
+    }
+}
+
+/// Decompresses `input` with the codec of the given id into a buffer allocated with
+/// `spreads_mem_malloc`, growing it with `spreads_mem_realloc` up to `limit` bytes.
+/// On success stores the buffer in `*output` and returns its length; the caller
+/// releases the buffer with `spreads_mem_free`, also when the length is zero. On
+/// error `*output` is set to NULL and a negative error code is returned.
+#[no_mangle]
+pub extern "C" fn spreads_decompress_alloc(
+    codec: libc::c_int,
+    input: *const libc::c_char,
+    input_length: usize,
+    limit: usize,
+    output: *mut *mut libc::c_char,
+) -> libc::c_int {
+    let output = match unsafe { output.as_mut() } {
+        Some(output) => output,
+        None => return CompressionError::InvalidArgument.code(),
+    };
+    *output = std::ptr::null_mut();
+    let input = unsafe { super::ffi_slice(input, input_length) };
+    match input.and_then(|input| decompress(Codec::from_id(codec)?, input, limit)) {
+        Ok(buffer) => {
+            let (ptr, len) = buffer.into_raw();
+            *output = ptr as *mut libc::c_char;
+            len as libc::c_int
+        }
+        Err(err) => err.code(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_data() -> Vec<u8> {
+        (0..256 * 1024u32)
+            .flat_map(|i| (i / 13).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn could_grow_output_for_all_codecs() {
+        let original = sample_data();
+        for codec in [
+            Codec::Lz4,
+            Codec::Zstd,
+            Codec::Zlib,
+            Codec::Deflate,
+            Codec::Gzip,
+            Codec::Lz4Hc,
+            Codec::Snappy,
+        ]
+        .iter()
+        .filter(|codec| codec.is_compiled())
+        {
+            let level = *codec.levels().end();
+            let compressed = codec.compress_to_vec(&original, level).unwrap();
+            assert!(compressed.len() * 4 < original.len());
+            let decompressed = codec
+                .decompress_with_limit(&compressed, original.len())
+                .unwrap();
+            assert_eq!(&original[..], &decompressed[..], "{:?}", codec);
+            assert_eq!(original.len(), decompressed.capacity());
+            assert_eq!(
+                Err(CompressionError::OutputLimitExceeded),
+                codec
+                    .decompress_with_limit(&compressed, original.len() - 1)
+                    .map(|buffer| buffer.len()),
+                "{:?}",
+                codec
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zlib")]
+    fn stops_decompression_bombs() {
+        let zeros = vec![0u8; 64 * 1024 * 1024];
+        let bomb = Codec::Gzip.compress_to_vec(&zeros, 9).unwrap();
+        assert!(bomb.len() < 128 * 1024);
+        assert_eq!(
+            Err(CompressionError::OutputLimitExceeded),
+            Codec::Gzip
+                .decompress_with_limit(&bomb, 1024 * 1024)
+                .map(|buffer| buffer.len())
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "zstd", feature = "zlib"))]
+    fn reports_truncated_input() {
+        let original = sample_data();
+        for codec in [Codec::Zstd, Codec::Gzip].iter() {
+            let compressed = codec.compress_to_vec(&original, 5).unwrap();
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                codec
+                    .decompress_with_limit(&compressed[..compressed.len() / 2], usize::MAX)
+                    .map(|buffer| buffer.len()),
+                "{:?}",
+                codec
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn could_decompress_alloc_through_c_api() {
+        let original = sample_data();
+        let compressed = Codec::Zstd.compress_to_vec(&original, 5).unwrap();
+        let mut output: *mut libc::c_char = std::ptr::null_mut();
+        let len = spreads_decompress_alloc(
+            Codec::Zstd.id(),
+            compressed.as_ptr() as *const libc::c_char,
+            compressed.len(),
+            usize::MAX,
+            &mut output,
+        );
+        assert_eq!(original.len() as libc::c_int, len);
+        let decompressed = unsafe { std::slice::from_raw_parts(output as *const u8, len as usize) };
+        assert_eq!(&original[..], decompressed);
+        spreads_mem_free(output as *mut libc::c_void);
+
+        let len = spreads_decompress_alloc(
+            Codec::Zstd.id(),
+            compressed.as_ptr() as *const libc::c_char,
+            compressed.len(),
+            1024,
+            &mut output,
+        );
+        assert_eq!(CompressionError::OutputLimitExceeded.code(), len);
+        assert!(output.is_null());
+    }
+}
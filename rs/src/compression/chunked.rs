@@ -0,0 +1,684 @@
+//! Chunked container for large buffers. The input is split into chunks of a fixed
+//! size that are compressed independently and concurrently with one `Codec`. The
+//! container starts with a 24 bytes little-endian header:
+//!
+//! | offset | size | field                  |
+//! |--------|------|------------------------|
+//! | 0      | 4    | magic `SPRC`           |
+//! | 4      | 1    | format version, 1      |
+//! | 5      | 1    | codec id               |
+//! | 6      | 2    | reserved, zero         |
+//! | 8      | 8    | uncompressed length    |
+//! | 16     | 4    | chunk size             |
+//! | 20     | 4    | number of chunks       |
+//!
+//! followed by the index, the `u32` compressed length of every chunk, and the
+//! compressed chunks. The index locates every chunk without decoding the others, so
+//! decompression is parallel too and a byte range only decodes the chunks covering it.
+//! The work runs on one thread pool that is started on first use and shared by all
+//! calls, each call occupies at most the number of threads it asks for.
+
+use rayon::prelude::*;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use super::{ffi_buffers, ffi_slice, Codec, CompressionError, MAX_INPUT_LENGTH};
+
+const MAGIC: [u8; 4] = *b"SPRC";
+const VERSION: u8 = 1;
+const HEADER_LENGTH: usize = 24;
+const INDEX_ENTRY_LENGTH: usize = 4;
+
+/// Settings of a chunked compression call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkedOptions {
+    pub codec: Codec,
+    pub level: i32,
+    /// Uncompressed size of every chunk but the last one.
+    pub chunk_size: usize,
+    /// Number of worker threads, 0 or more than the number of cores uses all cores.
+    pub nthreads: usize,
+}
+
+impl Default for ChunkedOptions {
+    fn default() -> Self {
+        ChunkedOptions {
+            codec: Codec::Zstd,
+            level: 3,
+            chunk_size: 4 << 20,
+            nthreads: 0,
+        }
+    }
+}
+
+impl ChunkedOptions {
+    fn validate(&self) -> Result<(), CompressionError> {
+        if !self.codec.levels().contains(&self.level) {
+            return Err(CompressionError::InvalidLevel);
+        }
+        if self.chunk_size == 0 {
+            return Err(CompressionError::InvalidArgument);
+        }
+        // Every chunk must fit the codec and the `u32` index entries.
+        self.codec.compress_bound(self.chunk_size)?;
+        Ok(())
+    }
+}
+
+/// Fields of the chunked container header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkedHeader {
+    pub codec: Codec,
+    /// Uncompressed size.
+    pub content_length: usize,
+    pub chunk_size: usize,
+    pub chunk_count: usize,
+}
+
+impl ChunkedHeader {
+    /// Parses and validates the header of the container stored in `src`.
+    pub fn parse(src: &[u8]) -> Result<ChunkedHeader, CompressionError> {
+        if src.len() < HEADER_LENGTH
+            || src[..4] != MAGIC
+            || src[4] != VERSION
+            || src[6..8] != [0, 0]
+        {
+            return Err(CompressionError::CorruptInput);
+        }
+        let mut content_length = [0u8; 8];
+        content_length.copy_from_slice(&src[8..16]);
+        let content_length = u64::from_le_bytes(content_length);
+        let chunk_size = read_u32(src, 16);
+        let chunk_count = read_u32(src, 20);
+        let valid = content_length <= isize::MAX as u64
+            && chunk_size > 0
+            && chunk_size <= MAX_INPUT_LENGTH
+            && content_length.div_ceil(chunk_size as u64) == chunk_count as u64;
+        if !valid {
+            return Err(CompressionError::CorruptInput);
+        }
+        Ok(ChunkedHeader {
+            codec: Codec::from_id(i32::from(src[5]))?,
+            content_length: content_length as usize,
+            chunk_size,
+            chunk_count,
+        })
+    }
+
+    fn chunk_range(&self, chunk: usize) -> Range<usize> {
+        let start = chunk * self.chunk_size;
+        start..(start + self.chunk_size).min(self.content_length)
+    }
+}
+
+fn read_u32(src: &[u8], offset: usize) -> usize {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&src[offset..offset + 4]);
+    u32::from_le_bytes(bytes) as usize
+}
+
+/// Parses the header and the index, returns the compressed range of every chunk. A
+/// chunk that declares more content than its compressed length can decode to is
+/// rejected, so the content length is bounded by the size of `src`.
+fn read_index(src: &[u8]) -> Result<(ChunkedHeader, Vec<Range<usize>>), CompressionError> {
+    let header = ChunkedHeader::parse(src)?;
+    let index_end = header
+        .chunk_count
+        .checked_mul(INDEX_ENTRY_LENGTH)
+        .and_then(|len| len.checked_add(HEADER_LENGTH))
+        .filter(|end| *end <= src.len())
+        .ok_or(CompressionError::CorruptInput)?;
+    let mut ranges = Vec::with_capacity(header.chunk_count);
+    let mut offset = index_end;
+    for chunk in 0..header.chunk_count {
+        let len = read_u32(src, HEADER_LENGTH + chunk * INDEX_ENTRY_LENGTH);
+        if len == 0
+            || len > src.len() - offset
+            || header.chunk_range(chunk).len() > header.codec.max_decompressed_len(len)
+        {
+            return Err(CompressionError::CorruptInput);
+        }
+        ranges.push(offset..offset + len);
+        offset += len;
+    }
+    if offset != src.len() {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok((header, ranges))
+}
+
+/// Thread pool shared by all calls, with one thread per core. `None` when the system
+/// refused to start the threads.
+static POOL: OnceLock<Option<rayon::ThreadPool>> = OnceLock::new();
+
+fn cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Number of threads a call with `nthreads` runs on, capped at the number of cores.
+fn thread_count(nthreads: usize) -> usize {
+    match nthreads {
+        0 => cores(),
+        n => n.min(cores()),
+    }
+}
+
+fn pool() -> Option<&'static rayon::ThreadPool> {
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(cores())
+            .thread_name(|i| format!("spreads-chunked-{}", i))
+            .build()
+            .ok()
+    })
+    .as_ref()
+}
+
+/// Runs `f` on every task on at most `thread_count(nthreads)` threads of the shared
+/// pool and returns the results in the task order.
+fn parallel_map<T, R, F>(tasks: Vec<T>, nthreads: usize, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync + Send,
+{
+    let threads = thread_count(nthreads);
+    if threads == 1 || tasks.len() <= 1 {
+        return tasks.into_iter().map(f).collect();
+    }
+    match pool() {
+        Some(pool) => {
+            // Runs of at least this many tasks split the work into at most `threads`
+            // jobs, so the call never occupies more threads than it asked for.
+            let min_len = tasks.len().div_ceil(threads);
+            pool.install(|| tasks.into_par_iter().with_min_len(min_len).map(f).collect())
+        }
+        // The system refused to start threads, do the work on the calling one.
+        None => tasks.into_iter().map(f).collect(),
+    }
+}
+
+/// Destination size that is always enough to compress `input_len` bytes.
+pub fn compress_bound(
+    options: &ChunkedOptions,
+    input_len: usize,
+) -> Result<usize, CompressionError> {
+    options.validate()?;
+    let full_chunks = input_len / options.chunk_size;
+    let tail = input_len % options.chunk_size;
+    let mut bound = HEADER_LENGTH;
+    let mut add = |chunks: usize, len: usize| -> Result<(), CompressionError> {
+        let chunk_bound = INDEX_ENTRY_LENGTH + options.codec.compress_bound(len)?;
+        bound = chunks
+            .checked_mul(chunk_bound)
+            .and_then(|len| len.checked_add(bound))
+            .filter(|bound| *bound <= isize::MAX as usize)
+            .ok_or(CompressionError::LengthOverflow)?;
+        Ok(())
+    };
+    add(full_chunks, options.chunk_size)?;
+    if tail > 0 {
+        add(1, tail)?;
+    }
+    Ok(bound)
+}
+
+/// Compresses `input` into a chunked container in `output` and returns its size.
+/// Every chunk is compressed directly into a slot of its worst-case size in `output`
+/// and the slots are compacted afterwards, so `output` must hold `compress_bound`
+/// bytes even when the container ends up smaller.
+pub fn compress(
+    input: &[u8],
+    output: &mut [u8],
+    options: &ChunkedOptions,
+) -> Result<usize, CompressionError> {
+    let bound = compress_bound(options, input.len())?;
+    let chunk_count = input.len().div_ceil(options.chunk_size);
+    if chunk_count as u64 > u64::from(u32::MAX) {
+        return Err(CompressionError::LengthOverflow);
+    }
+    if output.len() < bound {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    let slot_lengths = input
+        .chunks(options.chunk_size)
+        .map(|chunk| options.codec.compress_bound(chunk.len()))
+        .collect::<Result<Vec<usize>, CompressionError>>()?;
+    let index_end = HEADER_LENGTH + chunk_count * INDEX_ENTRY_LENGTH;
+    let mut tasks = Vec::with_capacity(chunk_count);
+    let mut rest = &mut output[index_end..bound];
+    for (chunk, slot_len) in input.chunks(options.chunk_size).zip(&slot_lengths) {
+        let (slot, tail) = rest.split_at_mut(*slot_len);
+        rest = tail;
+        tasks.push((chunk, slot));
+    }
+    let lengths = parallel_map(tasks, options.nthreads, |(chunk, slot)| {
+        options.codec.compress(chunk, slot, options.level)
+    })
+    .into_iter()
+    .collect::<Result<Vec<usize>, CompressionError>>()?;
+
+    output[..4].copy_from_slice(&MAGIC);
+    output[4] = VERSION;
+    output[5] = options.codec.id() as u8;
+    output[6..8].copy_from_slice(&[0, 0]);
+    output[8..16].copy_from_slice(&(input.len() as u64).to_le_bytes());
+    output[16..20].copy_from_slice(&(options.chunk_size as u32).to_le_bytes());
+    output[20..24].copy_from_slice(&(chunk_count as u32).to_le_bytes());
+    let mut slot = index_end;
+    let mut offset = index_end;
+    for (i, (len, slot_len)) in lengths.iter().zip(&slot_lengths).enumerate() {
+        let entry = HEADER_LENGTH + i * INDEX_ENTRY_LENGTH;
+        output[entry..entry + 4].copy_from_slice(&(*len as u32).to_le_bytes());
+        output.copy_within(slot..slot + len, offset);
+        slot += slot_len;
+        offset += len;
+    }
+    Ok(offset)
+}
+
+/// Compresses `input` into a new vector holding exactly the chunked container.
+pub fn compress_to_vec(
+    input: &[u8],
+    options: &ChunkedOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut output = vec![0u8; compress_bound(options, input.len())?];
+    let len = compress(input, &mut output, options)?;
+    output.truncate(len);
+    output.shrink_to_fit();
+    Ok(output)
+}
+
+fn decompress_chunk(
+    codec: Codec,
+    payload: &[u8],
+    output: &mut [u8],
+) -> Result<(), CompressionError> {
+    match codec.decompress(payload, output) {
+        Ok(len) if len == output.len() => Ok(()),
+        Ok(_) | Err(CompressionError::BufferTooSmall) => Err(CompressionError::CorruptInput),
+        Err(err) => Err(err),
+    }
+}
+
+/// Decompresses the chunked container in `input` into `output` using up to
+/// `nthreads` threads, 0 for all cores. Returns the number of bytes written.
+pub fn decompress(
+    input: &[u8],
+    output: &mut [u8],
+    nthreads: usize,
+) -> Result<usize, CompressionError> {
+    let (header, ranges) = read_index(input)?;
+    if output.len() < header.content_length {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    let output = &mut output[..header.content_length];
+    let tasks: Vec<_> = ranges
+        .into_iter()
+        .zip(output.chunks_mut(header.chunk_size))
+        .collect();
+    parallel_map(tasks, nthreads, |(range, chunk)| {
+        decompress_chunk(header.codec, &input[range], chunk)
+    })
+    .into_iter()
+    .collect::<Result<(), CompressionError>>()?;
+    Ok(header.content_length)
+}
+
+/// Decompresses the chunked container in `input` into a new vector.
+pub fn decompress_to_vec(input: &[u8], nthreads: usize) -> Result<Vec<u8>, CompressionError> {
+    // The index bounds the content length by the size of the input, and an allocation
+    // failure is reported instead of aborting.
+    let (header, _) = read_index(input)?;
+    let mut output = Vec::new();
+    output
+        .try_reserve_exact(header.content_length)
+        .map_err(|_| CompressionError::Internal)?;
+    output.resize(header.content_length, 0);
+    decompress(input, &mut output, nthreads)?;
+    Ok(output)
+}
+
+/// Decompresses `output.len()` bytes starting at byte `start` of the uncompressed
+/// content, decoding only the chunks that cover the range. Returns the number of
+/// bytes written.
+pub fn decompress_range(
+    input: &[u8],
+    start: usize,
+    output: &mut [u8],
+    nthreads: usize,
+) -> Result<usize, CompressionError> {
+    let (header, ranges) = read_index(input)?;
+    let end = start
+        .checked_add(output.len())
+        .filter(|end| *end <= header.content_length)
+        .ok_or(CompressionError::InvalidArgument)?;
+    if start == end {
+        return Ok(0);
+    }
+    let first = start / header.chunk_size;
+    let last = (end - 1) / header.chunk_size;
+    let mut tasks = Vec::with_capacity(last - first + 1);
+    let mut rest = output;
+    for (chunk, range) in ranges.into_iter().enumerate().take(last + 1).skip(first) {
+        let content = header.chunk_range(chunk);
+        let from = start.max(content.start);
+        let to = end.min(content.end);
+        let (dest, tail) = rest.split_at_mut(to - from);
+        rest = tail;
+        let skip = from - content.start;
+        tasks.push((range, content, skip, dest));
+    }
+    parallel_map(tasks, nthreads, |(range, content, skip, dest)| {
+        if dest.len() == content.len() {
+            return decompress_chunk(header.codec, &input[range], dest);
+        }
+        let mut chunk = vec![0u8; content.len()];
+        decompress_chunk(header.codec, &input[range], &mut chunk)?;
+        dest.copy_from_slice(&chunk[skip..skip + dest.len()]);
+        Ok(())
+    })
+    .into_iter()
+    .collect::<Result<(), CompressionError>>()?;
+    Ok(end - start)
+}
+
+fn to_c_size(result: Result<usize, CompressionError>) -> isize {
+    match result {
+        Ok(len) => len as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// Returns the destination size that is always enough for `spreads_chunked_compress`
+/// or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_chunked_compress_bound(
+    codec: libc::c_int,
+    input_length: usize,
+    chunk_size: usize,
+) -> isize {
+    let result = Codec::from_id(codec).and_then(|codec| {
+        let options = ChunkedOptions {
+            codec,
+            chunk_size,
+            level: *codec.levels().start(),
+            ..ChunkedOptions::default()
+        };
+        compress_bound(&options, input_length)
+    });
+    to_c_size(result)
+}
+
+/// Splits `input` into chunks of `chunk_size` bytes and compresses them with the codec
+/// with the given id on up to `nthreads` threads, 0 for all cores. Returns the
+/// container size or a negative error code. Lengths are `isize` because chunked
+/// buffers may exceed the `int` range of the single-shot exports.
+#[no_mangle]
+pub extern "C" fn spreads_chunked_compress(
+    codec: libc::c_int,
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    clevel: libc::c_int,
+    chunk_size: usize,
+    nthreads: usize,
+) -> isize {
+    let result =
+        unsafe { ffi_buffers(input, input_length, output, maxout) }.and_then(|(input, output)| {
+            let options = ChunkedOptions {
+                codec: Codec::from_id(codec)?,
+                level: clevel,
+                chunk_size,
+                nthreads,
+            };
+            compress(input, output, &options)
+        });
+    to_c_size(result)
+}
+
+/// Decompresses a chunked container on up to `nthreads` threads, 0 for all cores.
+/// Returns the number of bytes written or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_chunked_decompress(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    nthreads: usize,
+) -> isize {
+    let result = unsafe { ffi_buffers(input, input_length, output, maxout) }
+        .and_then(|(input, output)| decompress(input, output, nthreads));
+    to_c_size(result)
+}
+
+/// Decompresses `length` bytes starting at byte `start` of the uncompressed content
+/// into `output`, decoding only the chunks covering the range. Returns `length` or a
+/// negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_chunked_decompress_range(
+    input: *const libc::c_char,
+    input_length: usize,
+    start: usize,
+    output: *mut libc::c_char,
+    length: usize,
+    nthreads: usize,
+) -> isize {
+    let result = unsafe { ffi_buffers(input, input_length, output, length) }
+        .and_then(|(input, output)| decompress_range(input, start, output, nthreads));
+    to_c_size(result)
+}
+
+/// Returns the uncompressed size recorded in the container header or a negative
+/// error code.
+#[no_mangle]
+pub extern "C" fn spreads_chunked_content_size(
+    input: *const libc::c_char,
+    input_length: usize,
+) -> isize {
+    let header = unsafe { ffi_slice(input, input_length) }.and_then(ChunkedHeader::parse);
+    to_c_size(header.map(|header| header.content_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_data(len: usize) -> Vec<u8> {
+        (0..len as u32 / 4)
+            .flat_map(|i| (i / 3 + (i % 5) * 1000).to_le_bytes())
+            .collect()
+    }
+
+    fn options(codec: Codec, chunk_size: usize, nthreads: usize) -> ChunkedOptions {
+        ChunkedOptions {
+            codec,
+            level: *codec.levels().start() + 1,
+            chunk_size,
+            nthreads,
+        }
+    }
+
+    #[test]
+    fn could_roundtrip_with_threads() {
+        let original = sample_data(1 << 20);
+        for codec in [Codec::Lz4, Codec::Zstd, Codec::Zlib]
+            .iter()
+            .filter(|codec| codec.is_compiled())
+        {
+            for nthreads in [1usize, 4, 0].iter() {
+                let options = options(*codec, 100_000, *nthreads);
+                let compressed = compress_to_vec(&original, &options).unwrap();
+                let header = ChunkedHeader::parse(&compressed).unwrap();
+                assert_eq!(11, header.chunk_count);
+                let bound = compress_bound(&options, original.len()).unwrap();
+                assert!(compressed.len() <= bound);
+                assert_eq!(
+                    Err(CompressionError::BufferTooSmall),
+                    compress(&original, &mut vec![0u8; bound - 1], &options)
+                );
+                assert_eq!(original, decompress_to_vec(&compressed, *nthreads).unwrap());
+            }
+        }
+
+        let empty = compress_to_vec(&[], &ChunkedOptions::default()).unwrap();
+        assert_eq!(HEADER_LENGTH, empty.len());
+        assert!(decompress_to_vec(&empty, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn could_decompress_ranges() {
+        let original = sample_data(300_000);
+        let compressed = compress_to_vec(&original, &options(Codec::Zstd, 65_536, 3)).unwrap();
+        let ranges = [
+            (0, 10),
+            (65_530, 20),
+            (65_536, 65_536),
+            (1_000, 200_000),
+            (original.len() - 7, 7),
+            (0, original.len()),
+            (12_345, 0),
+        ];
+        for (start, len) in ranges.iter() {
+            let mut output = vec![0u8; *len];
+            assert_eq!(
+                Ok(*len),
+                decompress_range(&compressed, *start, &mut output, 4)
+            );
+            assert_eq!(&original[*start..*start + *len], &output[..]);
+        }
+        let mut output = vec![0u8; 8];
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            decompress_range(&compressed, original.len() - 7, &mut output, 1)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn rejects_corrupt_containers() {
+        let original = sample_data(100_000);
+        let compressed = compress_to_vec(&original, &options(Codec::Lz4, 16_384, 2)).unwrap();
+        let mut output = vec![0u8; original.len()];
+        for len in (0..compressed.len()).step_by(101) {
+            assert!(decompress(&compressed[..len], &mut output, 2).is_err());
+        }
+        let mut corrupt = compressed.clone();
+        corrupt[20] += 1;
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&corrupt, &mut output, 2)
+        );
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            decompress(&compressed, &mut output[..1000], 2)
+        );
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            compress_to_vec(&original, &options(Codec::Lz4, 0, 1))
+        );
+    }
+
+    #[test]
+    fn rejects_index_declaring_oversized_chunks() {
+        for codec in [Codec::Lz4, Codec::Zstd, Codec::Zlib]
+            .iter()
+            .filter(|codec| codec.is_compiled())
+        {
+            // 4 chunks of 1 GB, each stored in 1 byte.
+            let chunk_size = 1usize << 30;
+            let mut hostile = Vec::new();
+            hostile.extend_from_slice(&MAGIC);
+            hostile.extend_from_slice(&[VERSION, codec.id() as u8, 0, 0]);
+            hostile.extend_from_slice(&(4 * chunk_size as u64).to_le_bytes());
+            hostile.extend_from_slice(&(chunk_size as u32).to_le_bytes());
+            hostile.extend_from_slice(&4u32.to_le_bytes());
+            for _ in 0..4 {
+                hostile.extend_from_slice(&1u32.to_le_bytes());
+            }
+            hostile.extend_from_slice(&[0u8; 4]);
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                decompress_to_vec(&hostile, 1),
+                "{:?}",
+                codec
+            );
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                decompress_range(&hostile, 0, &mut [0u8; 16], 1)
+            );
+        }
+    }
+
+    #[test]
+    fn calls_use_at_most_the_requested_threads() {
+        let tasks: Vec<usize> = (0..64).collect();
+        for nthreads in [1usize, 2, 3].iter() {
+            let workers = parallel_map(tasks.clone(), *nthreads, |task| {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                (task, std::thread::current().id())
+            });
+            assert!(workers
+                .iter()
+                .map(|(task, _)| *task)
+                .eq(tasks.iter().copied()));
+            let mut ids: Vec<_> = workers.iter().map(|(_, id)| format!("{:?}", id)).collect();
+            ids.sort();
+            ids.dedup();
+            assert!(ids.len() <= *nthreads, "{} threads", ids.len());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn could_use_c_api() {
+        let original = sample_data(500_000);
+        let codec = Codec::Zstd.id();
+        let bound = spreads_chunked_compress_bound(codec, original.len(), 50_000);
+        let mut compressed = vec![0u8; bound as usize];
+        let len = spreads_chunked_compress(
+            codec,
+            original.as_ptr() as *const libc::c_char,
+            original.len(),
+            compressed.as_mut_ptr() as *mut libc::c_char,
+            compressed.len(),
+            3,
+            50_000,
+            4,
+        );
+        assert!(len > 0);
+        let compressed = &compressed[..len as usize];
+        let input = compressed.as_ptr() as *const libc::c_char;
+        assert_eq!(
+            original.len() as isize,
+            spreads_chunked_content_size(input, compressed.len())
+        );
+        let mut output = vec![0u8; original.len()];
+        assert_eq!(
+            original.len() as isize,
+            spreads_chunked_decompress(
+                input,
+                compressed.len(),
+                output.as_mut_ptr() as *mut libc::c_char,
+                output.len(),
+                0
+            )
+        );
+        assert_eq!(original, output);
+        let mut part = vec![0u8; 1000];
+        assert_eq!(
+            1000,
+            spreads_chunked_decompress_range(
+                input,
+                compressed.len(),
+                49_500,
+                part.as_mut_ptr() as *mut libc::c_char,
+                part.len(),
+                2
+            )
+        );
+        assert_eq!(&original[49_500..50_500], &part[..]);
+    }
+}
@@ -0,0 +1,378 @@
+//! Delta and delta-of-delta filters for integer columns such as timestamps. Elements
+//! are native-endian 4 or 8 byte integers and differences wrap around, so the filters
+//! are lossless for `i32`, `u32`, `i64` and `u64` alike. Delta-of-delta is the delta
+//! filter applied twice, which turns regularly spaced timestamps into runs of zeros.
+
+use super::CompressionError;
+
+/// Delta filter applied to a buffer before compression. The discriminants are the
+/// `mode` arguments of the `spreads_delta_*` exports.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delta {
+    None = 0,
+    Delta = 1,
+    DeltaOfDelta = 2,
+}
+
+impl Delta {
+    pub fn from_code(code: i32) -> Result<Delta, CompressionError> {
+        match code {
+            0 => Ok(Delta::None),
+            1 => Ok(Delta::Delta),
+            2 => Ok(Delta::DeltaOfDelta),
+            _ => Err(CompressionError::InvalidArgument),
+        }
+    }
+
+    fn passes(self) -> usize {
+        self as usize
+    }
+
+    /// Checks that the filter can process elements of `typesize` bytes.
+    pub(crate) fn validate(self, typesize: usize) -> Result<(), CompressionError> {
+        match (self, typesize) {
+            (Delta::None, _) | (_, 4) | (_, 8) => Ok(()),
+            _ => Err(CompressionError::InvalidArgument),
+        }
+    }
+}
+
+/// Integer types the typed filter functions accept.
+pub trait DeltaElement: Copy {
+    #[doc(hidden)]
+    const TYPESIZE: usize;
+}
+
+impl DeltaElement for i32 {
+    const TYPESIZE: usize = 4;
+}
+
+impl DeltaElement for i64 {
+    const TYPESIZE: usize = 8;
+}
+
+impl DeltaElement for u64 {
+    const TYPESIZE: usize = 8;
+}
+
+trait Lane: Copy {
+    const SIZE: usize;
+    fn read(bytes: &[u8]) -> Self;
+    fn write(self, bytes: &mut [u8]);
+    fn sub(self, other: Self) -> Self;
+    fn add(self, other: Self) -> Self;
+    fn zero() -> Self;
+}
+
+macro_rules! impl_lane {
+    ($t:ty) => {
+        impl Lane for $t {
+            const SIZE: usize = std::mem::size_of::<$t>();
+
+            #[inline(always)]
+            fn read(bytes: &[u8]) -> Self {
+                let mut lane = [0u8; std::mem::size_of::<$t>()];
+                lane.copy_from_slice(bytes);
+                <$t>::from_ne_bytes(lane)
+            }
+
+            #[inline(always)]
+            fn write(self, bytes: &mut [u8]) {
+                bytes.copy_from_slice(&self.to_ne_bytes());
+            }
+
+            #[inline(always)]
+            fn sub(self, other: Self) -> Self {
+                self.wrapping_sub(other)
+            }
+
+            #[inline(always)]
+            fn add(self, other: Self) -> Self {
+                self.wrapping_add(other)
+            }
+
+            #[inline(always)]
+            fn zero() -> Self {
+                0
+            }
+        }
+    };
+}
+
+impl_lane!(u32);
+impl_lane!(u64);
+
+/// Replaces every element by its difference to the previous one. The loop carries
+/// only the previous input value, which LLVM vectorizes.
+fn encode_lanes<T: Lane>(data: &mut [u8]) {
+    let mut previous = T::zero();
+    for lane in data.chunks_exact_mut(T::SIZE) {
+        let value = T::read(lane);
+        value.sub(previous).write(lane);
+        previous = value;
+    }
+}
+
+/// Prefix sum, the inverse of `encode_lanes`.
+fn decode_lanes<T: Lane>(data: &mut [u8], mut sum: T) {
+    for lane in data.chunks_exact_mut(T::SIZE) {
+        sum = sum.add(T::read(lane));
+        sum.write(lane);
+    }
+}
+
+/// SSE2 prefix sums: each vector is summed with log2(lanes) shifted adds and then
+/// offset by the running total broadcast from the previous vector.
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    pub(super) fn decode_u32(data: &mut [u8]) {
+        let vectors = data.len() / 16;
+        let mut total = 0u32;
+        // SSE2 is part of the x86_64 baseline and the accesses are unaligned loads
+        // and stores within `data`.
+        unsafe {
+            let mut carry = _mm_setzero_si128();
+            for i in 0..vectors {
+                let ptr = data.as_mut_ptr().add(i * 16) as *mut __m128i;
+                let mut x = _mm_loadu_si128(ptr);
+                x = _mm_add_epi32(x, _mm_slli_si128(x, 4));
+                x = _mm_add_epi32(x, _mm_slli_si128(x, 8));
+                x = _mm_add_epi32(x, carry);
+                _mm_storeu_si128(ptr, x);
+                carry = _mm_shuffle_epi32(x, 0xFF);
+            }
+            if vectors > 0 {
+                total = _mm_cvtsi128_si32(carry) as u32;
+            }
+        }
+        super::decode_lanes::<u32>(&mut data[vectors * 16..], total);
+    }
+
+    pub(super) fn decode_u64(data: &mut [u8]) {
+        let vectors = data.len() / 16;
+        let mut total = 0u64;
+        unsafe {
+            let mut carry = _mm_setzero_si128();
+            for i in 0..vectors {
+                let ptr = data.as_mut_ptr().add(i * 16) as *mut __m128i;
+                let mut x = _mm_loadu_si128(ptr);
+                x = _mm_add_epi64(x, _mm_slli_si128(x, 8));
+                x = _mm_add_epi64(x, carry);
+                _mm_storeu_si128(ptr, x);
+                carry = _mm_unpackhi_epi64(x, x);
+            }
+            if vectors > 0 {
+                total = _mm_cvtsi128_si64(carry) as u64;
+            }
+        }
+        super::decode_lanes::<u64>(&mut data[vectors * 16..], total);
+    }
+}
+
+fn decode_in_place(typesize: usize, data: &mut [u8]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if super::dispatch::use_sse2(super::dispatch::Kernel::Delta) {
+            if typesize == 4 {
+                sse2::decode_u32(data)
+            } else {
+                sse2::decode_u64(data)
+            }
+            return;
+        }
+    }
+    if typesize == 4 {
+        decode_lanes::<u32>(data, 0)
+    } else {
+        decode_lanes::<u64>(data, 0)
+    }
+}
+
+fn encode_in_place(typesize: usize, data: &mut [u8]) {
+    if typesize == 4 {
+        encode_lanes::<u32>(data)
+    } else {
+        encode_lanes::<u64>(data)
+    }
+}
+
+fn check_buffers(
+    delta: Delta,
+    typesize: usize,
+    src: &[u8],
+    dest: &[u8],
+) -> Result<(), CompressionError> {
+    delta.validate(typesize)?;
+    if typesize == 0 || !src.len().is_multiple_of(typesize) {
+        return Err(CompressionError::InvalidArgument);
+    }
+    if dest.len() < src.len() {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    Ok(())
+}
+
+/// Filters `src`, an array of `typesize` byte integers, into `dest` and returns the
+/// number of bytes written.
+pub fn encode(
+    delta: Delta,
+    typesize: usize,
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    check_buffers(delta, typesize, src, dest)?;
+    let dest = &mut dest[..src.len()];
+    dest.copy_from_slice(src);
+    for _ in 0..delta.passes() {
+        encode_in_place(typesize, dest);
+    }
+    Ok(src.len())
+}
+
+/// Reverses `encode` and returns the number of bytes written.
+pub fn decode(
+    delta: Delta,
+    typesize: usize,
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    check_buffers(delta, typesize, src, dest)?;
+    let dest = &mut dest[..src.len()];
+    dest.copy_from_slice(src);
+    decode_filtered(delta, typesize, dest);
+    Ok(src.len())
+}
+
+/// Reverses `encode` in place on a buffer that was validated by the caller.
+pub(crate) fn decode_filtered(delta: Delta, typesize: usize, data: &mut [u8]) {
+    for _ in 0..delta.passes() {
+        decode_in_place(typesize, data);
+    }
+}
+
+fn as_bytes<T: DeltaElement>(values: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u8, values.len() * T::TYPESIZE) }
+}
+
+fn as_bytes_mut<T: DeltaElement>(values: &mut [T]) -> &mut [u8] {
+    unsafe {
+        std::slice::from_raw_parts_mut(values.as_mut_ptr() as *mut u8, values.len() * T::TYPESIZE)
+    }
+}
+
+/// Typed `encode`, e.g. for an `i64` timestamp column.
+pub fn encode_values<T: DeltaElement>(
+    delta: Delta,
+    src: &[T],
+    dest: &mut [T],
+) -> Result<usize, CompressionError> {
+    encode(delta, T::TYPESIZE, as_bytes(src), as_bytes_mut(dest)).map(|_| src.len())
+}
+
+/// Typed `decode`, returns the number of elements written.
+pub fn decode_values<T: DeltaElement>(
+    delta: Delta,
+    src: &[T],
+    dest: &mut [T],
+) -> Result<usize, CompressionError> {
+    decode(delta, T::TYPESIZE, as_bytes(src), as_bytes_mut(dest)).map(|_| src.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamps() -> Vec<i64> {
+        (0..1001i64)
+            .map(|i| 1_600_000_000_000_000_000 + i * 1_000_000 + (i % 3))
+            .collect()
+    }
+
+    #[test]
+    fn could_roundtrip_typed_values() {
+        let original = timestamps();
+        for delta in [Delta::None, Delta::Delta, Delta::DeltaOfDelta].iter() {
+            let mut encoded = vec![0i64; original.len()];
+            let mut decoded = vec![0i64; original.len()];
+            assert_eq!(
+                Ok(original.len()),
+                encode_values(*delta, &original, &mut encoded)
+            );
+            assert_eq!(
+                Ok(original.len()),
+                decode_values(*delta, &encoded, &mut decoded)
+            );
+            assert_eq!(original, decoded, "{:?}", delta);
+        }
+
+        let wrapping: Vec<i32> = vec![i32::MAX, i32::MIN, 0, -1, 7, i32::MIN, 3, 3, 3];
+        let unsigned: Vec<u64> = vec![u64::MAX, 0, 1, u64::MAX - 1, 5];
+        let mut encoded = vec![0i32; wrapping.len()];
+        let mut decoded = vec![0i32; wrapping.len()];
+        encode_values(Delta::DeltaOfDelta, &wrapping, &mut encoded).unwrap();
+        decode_values(Delta::DeltaOfDelta, &encoded, &mut decoded).unwrap();
+        assert_eq!(wrapping, decoded);
+        let mut encoded = vec![0u64; unsigned.len()];
+        let mut decoded = vec![0u64; unsigned.len()];
+        encode_values(Delta::Delta, &unsigned, &mut encoded).unwrap();
+        decode_values(Delta::Delta, &encoded, &mut decoded).unwrap();
+        assert_eq!(unsigned, decoded);
+    }
+
+    #[test]
+    fn delta_of_delta_flattens_regular_timestamps() {
+        let regular: Vec<i64> = (0..100).map(|i| 1_000 + i * 60).collect();
+        let mut encoded = vec![0i64; regular.len()];
+        encode_values(Delta::Delta, &regular, &mut encoded).unwrap();
+        assert_eq!(&[1_000, 60, 60], &encoded[..3]);
+        encode_values(Delta::DeltaOfDelta, &regular, &mut encoded).unwrap();
+        assert_eq!(&[1_000, -940, 0, 0], &encoded[..4]);
+        assert!(encoded[2..].iter().all(|d| *d == 0));
+    }
+
+    #[test]
+    fn simd_decode_matches_scalar() {
+        // Odd lengths exercise the scalar tail after the vectors.
+        for len in [0usize, 1, 3, 4, 5, 17, 1023].iter() {
+            let values: Vec<u32> = (0..*len as u32)
+                .map(|i| i.wrapping_mul(2_654_435_761))
+                .collect();
+            let mut bytes: Vec<u8> = values.iter().flat_map(|v| v.to_ne_bytes()).collect();
+            let mut expected = bytes.clone();
+            decode_lanes::<u32>(&mut expected, 0);
+            decode_in_place(4, &mut bytes);
+            assert_eq!(expected, bytes);
+
+            let mut wide: Vec<u8> = values
+                .iter()
+                .flat_map(|v| u64::from(*v).to_ne_bytes())
+                .collect();
+            let mut expected = wide.clone();
+            decode_lanes::<u64>(&mut expected, 0);
+            decode_in_place(8, &mut wide);
+            assert_eq!(expected, wide);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let src = [0u8; 12];
+        let mut dest = [0u8; 12];
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            encode(Delta::Delta, 2, &src, &mut dest)
+        );
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            encode(Delta::Delta, 8, &src, &mut dest)
+        );
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            decode(Delta::Delta, 4, &src, &mut dest[..8])
+        );
+        assert_eq!(Err(CompressionError::InvalidArgument), Delta::from_code(3));
+    }
+}
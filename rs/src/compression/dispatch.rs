@@ -0,0 +1,553 @@
+//! Report of the SIMD implementation each compression kernel runs on this host and a
+//! process-wide override to force a specific one for benchmarking and regression tests.
+//!
+//! c-blosc selects its shuffle and bitshuffle implementation once per process from the
+//! CPU features, preferring AVX2 over SSE2 over the generic code. Its AVX2 kernels
+//! are only built when the C compiler can target AVX2, which the build script reports
+//! as the `blosc_avx2` cfg. The report mirrors that rule. A forced path is honored by
+//! the standalone shuffle and bitshuffle filters of this crate (`spreads_shuffle*`,
+//! `spreads_bitshuffle*`) and by the delta and bit-packing decoders, which call the
+//! per-architecture kernels directly. Blosc containers are always filtered by
+//! c-blosc's own choice. LZ4 has no vectorized code and zstd picks its BMI2 entropy
+//! decoders internally, so their paths are reported but can only be "forced" to the
+//! one they already use.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use super::{BitshuffleFn, CompressionError, ShuffleFn};
+
+/// CPU features relevant to the kernels, as a bit set.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuFeatures(pub u32);
+
+impl CpuFeatures {
+    pub const SSE2: u32 = 1;
+    pub const SSSE3: u32 = 1 << 1;
+    pub const SSE41: u32 = 1 << 2;
+    pub const SSE42: u32 = 1 << 3;
+    pub const AVX: u32 = 1 << 4;
+    pub const AVX2: u32 = 1 << 5;
+    pub const BMI2: u32 = 1 << 6;
+    pub const AVX512F: u32 = 1 << 7;
+
+    /// Features of the host CPU that are also enabled by the OS.
+    pub fn detect() -> CpuFeatures {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            let mut bits = 0;
+            let detected = [
+                (Self::SSE2, is_x86_feature_detected!("sse2")),
+                (Self::SSSE3, is_x86_feature_detected!("ssse3")),
+                (Self::SSE41, is_x86_feature_detected!("sse4.1")),
+                (Self::SSE42, is_x86_feature_detected!("sse4.2")),
+                (Self::AVX, is_x86_feature_detected!("avx")),
+                (Self::AVX2, is_x86_feature_detected!("avx2")),
+                (Self::BMI2, is_x86_feature_detected!("bmi2")),
+                (Self::AVX512F, is_x86_feature_detected!("avx512f")),
+            ];
+            for (bit, present) in detected.iter() {
+                if *present {
+                    bits |= bit;
+                }
+            }
+            CpuFeatures(bits)
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            CpuFeatures(0)
+        }
+    }
+
+    pub fn has(self, feature: u32) -> bool {
+        self.0 & feature == feature
+    }
+}
+
+/// A kernel whose implementation depends on the CPU.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    Shuffle = 0,
+    BitShuffle = 1,
+    Lz4 = 2,
+    Zstd = 3,
+    Delta = 4,
+    BitPack = 5,
+}
+
+const KERNEL_COUNT: usize = 6;
+
+impl Kernel {
+    pub fn from_code(code: i32) -> Result<Kernel, CompressionError> {
+        match code {
+            0 => Ok(Kernel::Shuffle),
+            1 => Ok(Kernel::BitShuffle),
+            2 => Ok(Kernel::Lz4),
+            3 => Ok(Kernel::Zstd),
+            4 => Ok(Kernel::Delta),
+            5 => Ok(Kernel::BitPack),
+            _ => Err(CompressionError::InvalidArgument),
+        }
+    }
+}
+
+/// Implementation of a kernel. Code 0 is reserved for "automatic" in the C API.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimdPath {
+    Generic = 1,
+    Sse2 = 2,
+    Avx2 = 3,
+    Bmi2 = 4,
+}
+
+impl SimdPath {
+    pub fn from_code(code: i32) -> Result<SimdPath, CompressionError> {
+        match code {
+            1 => Ok(SimdPath::Generic),
+            2 => Ok(SimdPath::Sse2),
+            3 => Ok(SimdPath::Avx2),
+            4 => Ok(SimdPath::Bmi2),
+            _ => Err(CompressionError::InvalidArgument),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SimdPath::Generic => "generic",
+            SimdPath::Sse2 => "sse2",
+            SimdPath::Avx2 => "avx2",
+            SimdPath::Bmi2 => "bmi2",
+        }
+    }
+}
+
+/// Whether `path` of `kernel` is compiled in and runs on a CPU with `features`.
+fn available(kernel: Kernel, path: SimdPath, features: CpuFeatures) -> bool {
+    let x86 = cfg!(any(target_arch = "x86", target_arch = "x86_64"));
+    match (kernel, path) {
+        (Kernel::Shuffle, SimdPath::Generic) | (Kernel::BitShuffle, SimdPath::Generic) => true,
+        (Kernel::Shuffle, SimdPath::Sse2) | (Kernel::BitShuffle, SimdPath::Sse2) => {
+            x86 && features.has(CpuFeatures::SSE2)
+        }
+        (Kernel::Shuffle, SimdPath::Avx2) | (Kernel::BitShuffle, SimdPath::Avx2) => {
+            x86 && cfg!(blosc_avx2) && features.has(CpuFeatures::AVX2)
+        }
+        (Kernel::Delta, SimdPath::Generic) | (Kernel::BitPack, SimdPath::Generic) => true,
+        // SSE2 is part of the x86_64 baseline.
+        (Kernel::Delta, SimdPath::Sse2) | (Kernel::BitPack, SimdPath::Sse2) => {
+            cfg!(target_arch = "x86_64")
+        }
+        (Kernel::Lz4, _) | (Kernel::Zstd, _) => path == detected(kernel, features),
+        _ => false,
+    }
+}
+
+/// The path a kernel takes when nothing is forced.
+fn detected(kernel: Kernel, features: CpuFeatures) -> SimdPath {
+    match kernel {
+        Kernel::Shuffle | Kernel::BitShuffle | Kernel::Delta | Kernel::BitPack => {
+            [SimdPath::Avx2, SimdPath::Sse2]
+                .iter()
+                .copied()
+                .find(|path| available(kernel, *path, features))
+                .unwrap_or(SimdPath::Generic)
+        }
+        Kernel::Lz4 => SimdPath::Generic,
+        // zstd builds BMI2 variants of its entropy decoders on x86_64 with GCC or Clang
+        // and selects them at run time.
+        Kernel::Zstd => {
+            if cfg!(all(target_arch = "x86_64", not(target_env = "msvc")))
+                && features.has(CpuFeatures::BMI2)
+            {
+                SimdPath::Bmi2
+            } else {
+                SimdPath::Generic
+            }
+        }
+    }
+}
+
+/// Forced path codes per kernel, 0 when automatic.
+static FORCED: [AtomicI32; KERNEL_COUNT] = [
+    AtomicI32::new(0),
+    AtomicI32::new(0),
+    AtomicI32::new(0),
+    AtomicI32::new(0),
+    AtomicI32::new(0),
+    AtomicI32::new(0),
+];
+
+fn forced(kernel: Kernel) -> Option<SimdPath> {
+    SimdPath::from_code(FORCED[kernel as usize].load(Ordering::Relaxed)).ok()
+}
+
+/// The path `kernel` currently runs on.
+pub fn active(kernel: Kernel) -> SimdPath {
+    forced(kernel).unwrap_or_else(|| detected(kernel, CpuFeatures::detect()))
+}
+
+/// Forces `kernel` to run on `path` for the whole process, or restores the automatic
+/// choice with `None`. Paths that are not compiled in or not supported by the CPU are
+/// rejected with `InvalidArgument`. Returns the previously forced path.
+pub fn force(kernel: Kernel, path: Option<SimdPath>) -> Result<Option<SimdPath>, CompressionError> {
+    if let Some(path) = path {
+        if !available(kernel, path, CpuFeatures::detect()) {
+            return Err(CompressionError::InvalidArgument);
+        }
+    }
+    let code = path.map_or(0, |path| path as i32);
+    let previous = FORCED[kernel as usize].swap(code, Ordering::Relaxed);
+    Ok(SimdPath::from_code(previous).ok())
+}
+
+/// CPU features and the active path of every kernel.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DispatchReport {
+    pub features: CpuFeatures,
+    pub shuffle: SimdPath,
+    pub bitshuffle: SimdPath,
+    pub lz4: SimdPath,
+    pub zstd: SimdPath,
+    pub delta: SimdPath,
+    pub bitpack: SimdPath,
+}
+
+impl DispatchReport {
+    pub fn path(&self, kernel: Kernel) -> SimdPath {
+        match kernel {
+            Kernel::Shuffle => self.shuffle,
+            Kernel::BitShuffle => self.bitshuffle,
+            Kernel::Lz4 => self.lz4,
+            Kernel::Zstd => self.zstd,
+            Kernel::Delta => self.delta,
+            Kernel::BitPack => self.bitpack,
+        }
+    }
+}
+
+/// Detects the CPU features and reports the path each kernel runs on.
+pub fn report() -> DispatchReport {
+    let features = CpuFeatures::detect();
+    let path = |kernel| forced(kernel).unwrap_or_else(|| detected(kernel, features));
+    DispatchReport {
+        features,
+        shuffle: path(Kernel::Shuffle),
+        bitshuffle: path(Kernel::BitShuffle),
+        lz4: path(Kernel::Lz4),
+        zstd: path(Kernel::Zstd),
+        delta: path(Kernel::Delta),
+        bitpack: path(Kernel::BitPack),
+    }
+}
+
+/// Byte shuffle kernel of the forced path, or c-blosc's dispatching one.
+pub(crate) fn shuffle_fn() -> ShuffleFn {
+    match forced(Kernel::Shuffle) {
+        Some(SimdPath::Generic) => spreads_blosc_sys::blosc_internal_shuffle_generic,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Some(SimdPath::Sse2) => spreads_blosc_sys::blosc_internal_shuffle_sse2,
+        #[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+        Some(SimdPath::Avx2) => spreads_blosc_sys::blosc_internal_shuffle_avx2,
+        _ => spreads_blosc_sys::blosc_internal_shuffle,
+    }
+}
+
+/// Byte unshuffle kernel of the forced path, or c-blosc's dispatching one.
+pub(crate) fn unshuffle_fn() -> ShuffleFn {
+    match forced(Kernel::Shuffle) {
+        Some(SimdPath::Generic) => spreads_blosc_sys::blosc_internal_unshuffle_generic,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Some(SimdPath::Sse2) => spreads_blosc_sys::blosc_internal_unshuffle_sse2,
+        #[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+        Some(SimdPath::Avx2) => spreads_blosc_sys::blosc_internal_unshuffle_avx2,
+        _ => spreads_blosc_sys::blosc_internal_unshuffle,
+    }
+}
+
+type BitTransposeFn = unsafe extern "C" fn(
+    *const libc::c_void,
+    *mut libc::c_void,
+    usize,
+    usize,
+    *mut libc::c_void,
+) -> i64;
+
+/// Adapts the element-count based bit transposition of c-blosc to the signature of
+/// `blosc_internal_bitshuffle`.
+macro_rules! bitshuffle_adapter {
+    ($name:ident, $transpose:path) => {
+        unsafe extern "C" fn $name(
+            bytesoftype: usize,
+            blocksize: usize,
+            src: *const libc::c_char,
+            dest: *const libc::c_char,
+            tmp: *const libc::c_char,
+        ) -> libc::c_int {
+            let transpose: BitTransposeFn = $transpose;
+            let processed = transpose(
+                src as *const libc::c_void,
+                dest as *mut libc::c_void,
+                blocksize / bytesoftype,
+                bytesoftype,
+                tmp as *mut libc::c_void,
+            );
+            if processed < 0 {
+                processed as libc::c_int
+            } else {
+                blocksize as libc::c_int
+            }
+        }
+    };
+}
+
+bitshuffle_adapter!(
+    bitshuffle_generic,
+    spreads_blosc_sys::blosc_internal_bshuf_trans_bit_elem_scal
+);
+bitshuffle_adapter!(
+    bitunshuffle_generic,
+    spreads_blosc_sys::blosc_internal_bshuf_untrans_bit_elem_scal
+);
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+bitshuffle_adapter!(
+    bitshuffle_sse2,
+    spreads_blosc_sys::blosc_internal_bshuf_trans_bit_elem_sse2
+);
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+bitshuffle_adapter!(
+    bitunshuffle_sse2,
+    spreads_blosc_sys::blosc_internal_bshuf_untrans_bit_elem_sse2
+);
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+bitshuffle_adapter!(
+    bitshuffle_avx2,
+    spreads_blosc_sys::blosc_internal_bshuf_trans_bit_elem_avx
+);
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+bitshuffle_adapter!(
+    bitunshuffle_avx2,
+    spreads_blosc_sys::blosc_internal_bshuf_untrans_bit_elem_avx
+);
+
+/// Bitshuffle kernel of the forced path, or c-blosc's dispatching one.
+pub(crate) fn bitshuffle_fn() -> BitshuffleFn {
+    match forced(Kernel::BitShuffle) {
+        Some(SimdPath::Generic) => bitshuffle_generic,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Some(SimdPath::Sse2) => bitshuffle_sse2,
+        #[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+        Some(SimdPath::Avx2) => bitshuffle_avx2,
+        _ => spreads_blosc_sys::blosc_internal_bitshuffle,
+    }
+}
+
+/// Bitunshuffle kernel of the forced path, or c-blosc's dispatching one.
+pub(crate) fn bitunshuffle_fn() -> BitshuffleFn {
+    match forced(Kernel::BitShuffle) {
+        Some(SimdPath::Generic) => bitunshuffle_generic,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Some(SimdPath::Sse2) => bitunshuffle_sse2,
+        #[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), blosc_avx2))]
+        Some(SimdPath::Avx2) => bitunshuffle_avx2,
+        _ => spreads_blosc_sys::blosc_internal_bitunshuffle,
+    }
+}
+
+/// Whether the SSE2 code of `kernel` (delta or bit-packing) should run.
+#[cfg(target_arch = "x86_64")]
+pub(crate) fn use_sse2(kernel: Kernel) -> bool {
+    forced(kernel) != Some(SimdPath::Generic)
+}
+
+/// Writes the detected CPU features and the path every kernel runs on to `report`.
+/// Features are the `CpuFeatures` bits, paths are 1 generic, 2 SSE2, 3 AVX2 or
+/// 4 BMI2. Returns 0 or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_simd_report(report: *mut DispatchReport) -> libc::c_int {
+    if report.is_null() {
+        return CompressionError::InvalidArgument.code();
+    }
+    unsafe { report.write(self::report()) };
+    0
+}
+
+/// Forces `kernel` (0 shuffle, 1 bitshuffle, 2 LZ4, 3 zstd, 4 delta, 5 bit-packing)
+/// to run on `path` for the whole process, or restores the automatic choice with
+/// `path` 0. Returns the previously forced path, 0 if none, or a negative error code
+/// when the path is not available on this host.
+#[no_mangle]
+pub extern "C" fn spreads_simd_force(kernel: libc::c_int, path: libc::c_int) -> libc::c_int {
+    let path = match path {
+        0 => Ok(None),
+        code => SimdPath::from_code(code).map(Some),
+    };
+    match Kernel::from_code(kernel).and_then(|kernel| path.and_then(|path| force(kernel, path))) {
+        Ok(previous) => previous.map_or(0, |path| path as libc::c_int),
+        Err(err) => err.code(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{bitpack, bitshuffle, bitunshuffle, delta, shuffle, unshuffle};
+    use super::*;
+    use std::sync::Mutex;
+
+    // Forcing is process-wide, the tests that force paths or check the report run
+    // one at a time. Other tests may observe a forced path, which gives the same
+    // output.
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    const KERNELS: [Kernel; KERNEL_COUNT] = [
+        Kernel::Shuffle,
+        Kernel::BitShuffle,
+        Kernel::Lz4,
+        Kernel::Zstd,
+        Kernel::Delta,
+        Kernel::BitPack,
+    ];
+
+    const PATHS: [SimdPath; 4] = [
+        SimdPath::Generic,
+        SimdPath::Sse2,
+        SimdPath::Avx2,
+        SimdPath::Bmi2,
+    ];
+
+    fn lock() -> std::sync::MutexGuard<'static, ()> {
+        LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[test]
+    fn report_matches_detected_paths() {
+        let _guard = lock();
+        let report = report();
+        assert_eq!(CpuFeatures::detect(), report.features);
+        for kernel in KERNELS.iter() {
+            let path = report.path(*kernel);
+            assert!(available(*kernel, path, report.features), "{:?}", report);
+            assert_eq!(active(*kernel), path);
+        }
+        assert_eq!(SimdPath::Generic, report.lz4);
+        let all = CpuFeatures(!0);
+        assert_eq!(
+            cfg!(all(
+                any(target_arch = "x86", target_arch = "x86_64"),
+                blosc_avx2
+            )),
+            available(Kernel::Shuffle, SimdPath::Avx2, all)
+                && available(Kernel::BitShuffle, SimdPath::Avx2, all)
+        );
+        #[cfg(target_arch = "x86_64")]
+        {
+            assert!(report.features.has(CpuFeatures::SSE2));
+            assert_ne!(SimdPath::Generic, report.shuffle);
+            assert_eq!(SimdPath::Sse2, report.delta);
+        }
+    }
+
+    #[test]
+    fn every_available_path_gives_the_same_output() {
+        let _guard = lock();
+        let original: Vec<u8> = (0..4099u32)
+            .flat_map(|i| (i * 7919).to_le_bytes())
+            .collect();
+        let body = original.len() / 64 * 64;
+        let mut expected = vec![0u8; original.len()];
+        shuffle(4, &original, &mut expected).unwrap();
+        let mut expected_bits = vec![0u8; body];
+        bitshuffle(4, &original[..body], &mut expected_bits).unwrap();
+
+        let features = CpuFeatures::detect();
+        for path in PATHS.iter() {
+            if !available(Kernel::Shuffle, *path, features) {
+                assert_eq!(
+                    Err(CompressionError::InvalidArgument),
+                    force(Kernel::Shuffle, Some(*path))
+                );
+                continue;
+            }
+            force(Kernel::Shuffle, Some(*path)).unwrap();
+            force(Kernel::BitShuffle, Some(*path)).unwrap();
+            assert_eq!(*path, report().shuffle);
+
+            let mut shuffled = vec![0u8; original.len()];
+            shuffle(4, &original, &mut shuffled).unwrap();
+            assert_eq!(expected, shuffled, "{}", path.name());
+            let mut restored = vec![0u8; original.len()];
+            unshuffle(4, &shuffled, &mut restored).unwrap();
+            assert_eq!(original, restored);
+
+            let mut bits = vec![0u8; body];
+            bitshuffle(4, &original[..body], &mut bits).unwrap();
+            assert_eq!(expected_bits, bits, "{}", path.name());
+            let mut restored = vec![0u8; body];
+            bitunshuffle(4, &bits, &mut restored).unwrap();
+            assert_eq!(&original[..body], &restored[..]);
+        }
+        force(Kernel::Shuffle, None).unwrap();
+        force(Kernel::BitShuffle, None).unwrap();
+    }
+
+    #[test]
+    fn forced_generic_delta_and_bitpack_decode_the_same() {
+        let _guard = lock();
+        let values: Vec<i64> = (0..1000i64).map(|i| 1_600_000_000 + i * i).collect();
+        let mut encoded = vec![0i64; values.len()];
+        delta::encode_values(delta::Delta::Delta, &values, &mut encoded).unwrap();
+        let packed = bitpack::compress_to_vec(&values, &bitpack::PackOptions::default()).unwrap();
+
+        let decode = || {
+            let mut decoded = vec![0i64; values.len()];
+            delta::decode_values(delta::Delta::Delta, &encoded, &mut decoded).unwrap();
+            let unpacked: Vec<i64> = bitpack::decompress_to_vec(&packed).unwrap();
+            (decoded, unpacked)
+        };
+        let automatic = decode();
+        assert_eq!(Ok(None), force(Kernel::Delta, Some(SimdPath::Generic)));
+        assert_eq!(Ok(None), force(Kernel::BitPack, Some(SimdPath::Generic)));
+        assert_eq!(automatic, decode());
+        assert_eq!(Ok(Some(SimdPath::Generic)), force(Kernel::Delta, None));
+        force(Kernel::BitPack, None).unwrap();
+        assert_eq!(values, automatic.0);
+        assert_eq!(values, automatic.1);
+    }
+
+    #[test]
+    fn could_use_c_api() {
+        let _guard = lock();
+        let mut written = std::mem::MaybeUninit::<DispatchReport>::uninit();
+        assert_eq!(0, spreads_simd_report(written.as_mut_ptr()));
+        assert_eq!(report(), unsafe { written.assume_init() });
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_simd_report(std::ptr::null_mut())
+        );
+
+        assert_eq!(
+            0,
+            spreads_simd_force(Kernel::Shuffle as i32, SimdPath::Generic as i32)
+        );
+        assert_eq!(SimdPath::Generic, report().shuffle);
+        assert_eq!(
+            SimdPath::Generic as i32,
+            spreads_simd_force(Kernel::Shuffle as i32, 0)
+        );
+        // LZ4 has a single scalar implementation.
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_simd_force(Kernel::Lz4 as i32, SimdPath::Avx2 as i32)
+        );
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_simd_force(6, 0)
+        );
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_simd_force(Kernel::Shuffle as i32, 5)
+        );
+    }
+}
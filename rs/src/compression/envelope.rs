@@ -0,0 +1,366 @@
+//! Checksummed envelope around the output of a `Codec`. The 24 bytes header holds,
+//! in little-endian order:
+//!
+//! | offset | size | field                                               |
+//! |--------|------|-----------------------------------------------------|
+//! | 0      | 4    | magic `SPRZ`                                        |
+//! | 4      | 1    | format version, currently 1                         |
+//! | 5      | 1    | codec id, see `Codec::id`                           |
+//! | 6      | 2    | reserved, zero                                      |
+//! | 8      | 8    | uncompressed length                                 |
+//! | 16     | 4    | payload length                                      |
+//! | 20     | 4    | XXH32 of the first 20 header bytes and the payload  |
+//!
+//! The checksum is verified before the payload reaches the codec, so truncated or
+//! corrupted envelopes are rejected instead of decoding to garbage.
+
+use super::xxhash::Xxh32;
+use super::{ffi_buffers, ffi_slice, to_c_result, Codec, CompressionError, MAX_INPUT_LENGTH};
+
+const MAGIC: [u8; 4] = *b"SPRZ";
+const VERSION: u8 = 1;
+const HEADER_LENGTH: usize = 24;
+const CHECKSUM_OFFSET: usize = 20;
+
+/// Fields of the envelope header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnvelopeHeader {
+    pub version: u8,
+    pub codec: Codec,
+    /// Uncompressed size.
+    pub content_length: usize,
+    /// Size of the compressed payload that follows the header.
+    pub payload_length: usize,
+    pub checksum: u32,
+}
+
+impl EnvelopeHeader {
+    /// Parses and validates the header of the envelope stored in `src` without
+    /// verifying the checksum.
+    pub fn parse(src: &[u8]) -> Result<EnvelopeHeader, CompressionError> {
+        if src.len() < HEADER_LENGTH || src[..4] != MAGIC || src[6..8] != [0, 0] {
+            return Err(CompressionError::CorruptInput);
+        }
+        if src[4] != VERSION {
+            return Err(CompressionError::CorruptInput);
+        }
+        let mut content_length = [0u8; 8];
+        content_length.copy_from_slice(&src[8..16]);
+        let content_length = u64::from_le_bytes(content_length);
+        let read_u32 = |offset: usize| {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&src[offset..offset + 4]);
+            u32::from_le_bytes(bytes)
+        };
+        if content_length > MAX_INPUT_LENGTH as u64 {
+            return Err(CompressionError::CorruptInput);
+        }
+        Ok(EnvelopeHeader {
+            version: src[4],
+            codec: Codec::from_id(i32::from(src[5]))?,
+            content_length: content_length as usize,
+            payload_length: read_u32(16) as usize,
+            checksum: read_u32(CHECKSUM_OFFSET),
+        })
+    }
+
+    /// Size of the whole envelope.
+    pub fn envelope_length(&self) -> usize {
+        HEADER_LENGTH + self.payload_length
+    }
+}
+
+fn checksum(header: &[u8], payload: &[u8]) -> u32 {
+    let mut state = Xxh32::new(0);
+    state.update(&header[..CHECKSUM_OFFSET]);
+    state.update(payload);
+    state.digest()
+}
+
+/// Destination size that is always enough to wrap `input_len` bytes compressed with
+/// `codec`.
+pub fn compress_bound(codec: Codec, input_len: usize) -> Result<usize, CompressionError> {
+    let bound = HEADER_LENGTH + codec.compress_bound(input_len)?;
+    if bound > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    Ok(bound)
+}
+
+/// Compresses `input` with `codec` into a checksummed envelope in `output` and returns
+/// the envelope size.
+pub fn compress(
+    codec: Codec,
+    input: &[u8],
+    output: &mut [u8],
+    level: i32,
+) -> Result<usize, CompressionError> {
+    if !codec.levels().contains(&level) {
+        return Err(CompressionError::InvalidLevel);
+    }
+    if input.len() > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    if output.len() < HEADER_LENGTH {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    let (header, payload) = output.split_at_mut(HEADER_LENGTH);
+    // An empty input has an empty payload, the codecs reject empty compressed input.
+    let payload_length = if input.is_empty() {
+        0
+    } else {
+        codec.compress(input, payload, level)?
+    };
+    if HEADER_LENGTH + payload_length > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    header[..4].copy_from_slice(&MAGIC);
+    header[4] = VERSION;
+    header[5] = codec.id() as u8;
+    header[6..8].copy_from_slice(&[0, 0]);
+    header[8..16].copy_from_slice(&(input.len() as u64).to_le_bytes());
+    header[16..20].copy_from_slice(&(payload_length as u32).to_le_bytes());
+    let checksum = checksum(header, &payload[..payload_length]);
+    header[CHECKSUM_OFFSET..].copy_from_slice(&checksum.to_le_bytes());
+    Ok(HEADER_LENGTH + payload_length)
+}
+
+/// Compresses `input` into a new vector holding exactly the envelope.
+pub fn compress_to_vec(
+    codec: Codec,
+    input: &[u8],
+    level: i32,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut output = vec![0u8; compress_bound(codec, input.len())?];
+    let len = compress(codec, input, &mut output, level)?;
+    output.truncate(len);
+    output.shrink_to_fit();
+    Ok(output)
+}
+
+/// Verifies the envelope in `input` and returns its header and payload.
+fn verify(input: &[u8]) -> Result<(EnvelopeHeader, &[u8]), CompressionError> {
+    let header = EnvelopeHeader::parse(input)?;
+    if input.len() != header.envelope_length() {
+        return Err(CompressionError::CorruptInput);
+    }
+    let payload = &input[HEADER_LENGTH..];
+    if checksum(input, payload) != header.checksum {
+        return Err(CompressionError::ChecksumMismatch);
+    }
+    if payload.is_empty() != (header.content_length == 0) {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok((header, payload))
+}
+
+/// Verifies the envelope in `input` and decompresses it into `output`. Returns the
+/// number of bytes written, which always equals the length recorded in the header.
+pub fn decompress(input: &[u8], output: &mut [u8]) -> Result<usize, CompressionError> {
+    let (header, payload) = verify(input)?;
+    decode(&header, payload, output)
+}
+
+fn decode(
+    header: &EnvelopeHeader,
+    payload: &[u8],
+    output: &mut [u8],
+) -> Result<usize, CompressionError> {
+    if output.len() < header.content_length {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    if header.content_length == 0 {
+        return Ok(0);
+    }
+    let output = &mut output[..header.content_length];
+    match header.codec.decompress(payload, output) {
+        Ok(len) if len == header.content_length => Ok(len),
+        // The payload matches its checksum but not the recorded length, or does not
+        // fit into it.
+        Ok(_) | Err(CompressionError::BufferTooSmall) => Err(CompressionError::CorruptInput),
+        Err(err) => Err(err),
+    }
+}
+
+/// Verifies and decompresses the envelope in `input` into a new vector.
+pub fn decompress_to_vec(input: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let (header, payload) = verify(input)?;
+    let mut output = vec![0u8; header.content_length];
+    decode(&header, payload, &mut output)?;
+    Ok(output)
+}
+
+/// Returns the destination size that is always enough for `spreads_envelope_compress`
+/// or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_envelope_compress_bound(
+    codec: libc::c_int,
+    input_length: usize,
+) -> isize {
+    match Codec::from_id(codec).and_then(|codec| compress_bound(codec, input_length)) {
+        Ok(bound) => bound as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// Compresses `input` with the codec with the given id into a checksummed envelope.
+/// Returns the envelope size or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_envelope_compress(
+    codec: libc::c_int,
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    clevel: libc::c_int,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(input, input_length, output, maxout) }
+        .and_then(|(input, output)| compress(Codec::from_id(codec)?, input, output, clevel));
+    to_c_result(result)
+}
+
+/// Verifies the checksum of an envelope and decompresses it. Returns the number of
+/// bytes written, `ChecksumMismatch` for corrupted data, or another negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_envelope_decompress(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(input, input_length, output, maxout) }
+        .and_then(|(input, output)| decompress(input, output));
+    to_c_result(result)
+}
+
+/// Returns the uncompressed size recorded in the envelope header, which is the output
+/// size needed by `spreads_envelope_decompress`, or a negative error code. Only the
+/// header is read, the checksum is verified when decompressing.
+#[no_mangle]
+pub extern "C" fn spreads_envelope_content_size(
+    input: *const libc::c_char,
+    input_length: usize,
+) -> isize {
+    match unsafe { ffi_slice(input, input_length) }.and_then(EnvelopeHeader::parse) {
+        Ok(header) => header.content_length as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_data() -> Vec<u8> {
+        (0..50_000u32).flat_map(|i| (i / 7).to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn could_roundtrip_all_codecs() {
+        let original = sample_data();
+        let codecs = [
+            Codec::Lz4,
+            Codec::Zstd,
+            Codec::Zlib,
+            Codec::Deflate,
+            Codec::Gzip,
+            Codec::Lz4Hc,
+        ];
+        for codec in codecs.iter().filter(|codec| codec.is_compiled()) {
+            let level = *codec.levels().end();
+            let envelope = compress_to_vec(*codec, &original, level).unwrap();
+            let header = EnvelopeHeader::parse(&envelope).unwrap();
+            assert_eq!(*codec, header.codec);
+            assert_eq!(original.len(), header.content_length);
+            assert_eq!(envelope.len(), header.envelope_length());
+            assert_eq!(original, decompress_to_vec(&envelope).unwrap());
+
+            let empty = compress_to_vec(*codec, &[], level).unwrap();
+            assert_eq!(HEADER_LENGTH, empty.len());
+            assert!(decompress_to_vec(&empty).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn detects_corruption_and_truncation() {
+        let original = sample_data();
+        let envelope = compress_to_vec(Codec::Zstd, &original, 3).unwrap();
+        let mut output = vec![0u8; original.len()];
+        for len in 0..envelope.len() {
+            assert!(decompress(&envelope[..len], &mut output).is_err());
+        }
+        let corrupt_at = |position: usize| {
+            let mut corrupt = envelope.clone();
+            corrupt[position] ^= 0x10;
+            decompress(&corrupt, &mut vec![0u8; original.len()])
+        };
+        assert_eq!(Err(CompressionError::CorruptInput), corrupt_at(0));
+        assert_eq!(Err(CompressionError::UnsupportedCodec), corrupt_at(5));
+        assert_eq!(Err(CompressionError::ChecksumMismatch), corrupt_at(9));
+        // The payload length no longer matches the envelope size.
+        assert_eq!(Err(CompressionError::CorruptInput), corrupt_at(16));
+        assert_eq!(Err(CompressionError::ChecksumMismatch), corrupt_at(21));
+        for position in (HEADER_LENGTH..envelope.len()).step_by(97) {
+            assert_eq!(
+                Err(CompressionError::ChecksumMismatch),
+                corrupt_at(position),
+                "{}",
+                position
+            );
+        }
+        let mut padded = envelope.clone();
+        padded.push(0);
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&padded, &mut output)
+        );
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            decompress(&envelope, &mut output[..10])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn could_use_c_api() {
+        let original = sample_data();
+        let bound = spreads_envelope_compress_bound(Codec::Lz4.id(), original.len());
+        let mut envelope = vec![0u8; bound as usize];
+        let len = spreads_envelope_compress(
+            Codec::Lz4.id(),
+            original.as_ptr() as *const libc::c_char,
+            original.len(),
+            envelope.as_mut_ptr() as *mut libc::c_char,
+            envelope.len(),
+            5,
+        );
+        assert!(len > 0);
+        let content_size =
+            spreads_envelope_content_size(envelope.as_ptr() as *const libc::c_char, len as usize);
+        assert_eq!(original.len() as isize, content_size);
+
+        let mut output = vec![0u8; content_size as usize];
+        assert_eq!(
+            original.len() as i32,
+            spreads_envelope_decompress(
+                envelope.as_ptr() as *const libc::c_char,
+                len as usize,
+                output.as_mut_ptr() as *mut libc::c_char,
+                output.len()
+            )
+        );
+        assert_eq!(original, output);
+
+        envelope[len as usize - 1] ^= 1;
+        assert_eq!(
+            CompressionError::ChecksumMismatch.code(),
+            spreads_envelope_decompress(
+                envelope.as_ptr() as *const libc::c_char,
+                len as usize,
+                output.as_mut_ptr() as *mut libc::c_char,
+                output.len()
+            )
+        );
+    }
+}
@@ -0,0 +1,524 @@
+//! Gorilla XOR encoding of floating point series. Each value is XORed with the
+//! previous one and only the meaningful bits of the result are stored, reusing the
+//! previous leading/trailing zero window when possible. A stream is a little-endian
+//! `u32` value count followed by the MSB-first bit stream.
+
+use super::{ffi_buffers, to_c_result, CompressionError, MAX_INPUT_LENGTH};
+
+const HEADER_LENGTH: usize = 4;
+const LEADING_BITS: u32 = 5;
+const MAX_LEADING: u32 = (1 << LEADING_BITS) - 1;
+
+/// Floating point types that can be Gorilla encoded.
+pub trait GorillaFloat: Copy {
+    #[doc(hidden)]
+    const BITS: u32;
+    #[doc(hidden)]
+    fn to_raw(self) -> u64;
+    #[doc(hidden)]
+    fn from_raw(raw: u64) -> Self;
+}
+
+impl GorillaFloat for f64 {
+    const BITS: u32 = 64;
+
+    fn to_raw(self) -> u64 {
+        self.to_bits()
+    }
+
+    fn from_raw(raw: u64) -> Self {
+        f64::from_bits(raw)
+    }
+}
+
+impl GorillaFloat for f32 {
+    const BITS: u32 = 32;
+
+    fn to_raw(self) -> u64 {
+        u64::from(self.to_bits())
+    }
+
+    fn from_raw(raw: u64) -> Self {
+        f32::from_bits(raw as u32)
+    }
+}
+
+/// Bits of the meaningful length field, a length equal to the value width is stored
+/// as 0.
+fn length_bits(width: u32) -> u32 {
+    if width == 64 {
+        6
+    } else {
+        5
+    }
+}
+
+struct BitWriter<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    fn write(&mut self, value: u64, mut bits: u32) -> Result<(), CompressionError> {
+        if self.pos + bits as usize > self.out.len() * 8 {
+            return Err(CompressionError::BufferTooSmall);
+        }
+        while bits > 0 {
+            let byte = self.pos / 8;
+            let used = (self.pos % 8) as u32;
+            if used == 0 {
+                self.out[byte] = 0;
+            }
+            let free = 8 - used;
+            let take = free.min(bits);
+            let chunk = ((value >> (bits - take)) & ((1u64 << take) - 1)) as u8;
+            self.out[byte] |= chunk << (free - take);
+            bits -= take;
+            self.pos += take as usize;
+        }
+        Ok(())
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, mut bits: u32) -> Result<u64, CompressionError> {
+        if self.pos + bits as usize > self.data.len() * 8 {
+            return Err(CompressionError::CorruptInput);
+        }
+        let mut value = 0u64;
+        while bits > 0 {
+            let used = (self.pos % 8) as u32;
+            let available = 8 - used;
+            let take = available.min(bits);
+            let chunk =
+                (self.data[self.pos / 8] >> (available - take)) & ((1u16 << take) - 1) as u8;
+            value = (value << take) | u64::from(chunk);
+            bits -= take;
+            self.pos += take as usize;
+        }
+        Ok(value)
+    }
+}
+
+fn encode<I>(
+    width: u32,
+    count: usize,
+    values: I,
+    dest: &mut [u8],
+) -> Result<usize, CompressionError>
+where
+    I: Iterator<Item = u64>,
+{
+    if count > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    if dest.len() < HEADER_LENGTH {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    dest[..HEADER_LENGTH].copy_from_slice(&(count as u32).to_le_bytes());
+    let length_bits = length_bits(width);
+    let mut writer = BitWriter {
+        out: &mut dest[HEADER_LENGTH..],
+        pos: 0,
+    };
+    let mut previous = 0u64;
+    let mut window: Option<(u32, u32)> = None;
+    for (i, value) in values.enumerate() {
+        let xor = value ^ previous;
+        if i == 0 {
+            writer.write(value, width)?;
+        } else if xor == 0 {
+            writer.write(0, 1)?;
+        } else {
+            let leading = (xor.leading_zeros() - (64 - width)).min(MAX_LEADING);
+            let trailing = xor.trailing_zeros();
+            match window {
+                Some((l, t)) if leading >= l && trailing >= t => {
+                    writer.write(0b10, 2)?;
+                    writer.write(xor >> t, width - l - t)?;
+                }
+                _ => {
+                    let len = width - leading - trailing;
+                    writer.write(0b11, 2)?;
+                    writer.write(u64::from(leading), LEADING_BITS)?;
+                    writer.write(u64::from(len % (1 << length_bits)), length_bits)?;
+                    writer.write(xor >> trailing, len)?;
+                    window = Some((leading, trailing));
+                }
+            }
+        }
+        previous = value;
+    }
+    Ok(HEADER_LENGTH + writer.pos.div_ceil(8))
+}
+
+/// Reads the value count of a stream and rejects counts that the payload cannot hold,
+/// so that untrusted input never drives a large allocation.
+fn read_count(width: u32, src: &[u8]) -> Result<usize, CompressionError> {
+    if src.len() < HEADER_LENGTH {
+        return Err(CompressionError::CorruptInput);
+    }
+    let mut header = [0u8; HEADER_LENGTH];
+    header.copy_from_slice(&src[..HEADER_LENGTH]);
+    let count = u32::from_le_bytes(header) as usize;
+    let payload_bits = (src.len() - HEADER_LENGTH).saturating_mul(8);
+    if count > 0 && (payload_bits < width as usize || count - 1 > payload_bits - width as usize) {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok(count)
+}
+
+fn decode<F>(width: u32, src: &[u8], capacity: usize, mut put: F) -> Result<usize, CompressionError>
+where
+    F: FnMut(usize, u64),
+{
+    let count = read_count(width, src)?;
+    if count > capacity {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    let length_bits = length_bits(width);
+    let mut reader = BitReader {
+        data: &src[HEADER_LENGTH..],
+        pos: 0,
+    };
+    let mut previous = 0u64;
+    let mut window: Option<(u32, u32)> = None;
+    for i in 0..count {
+        let value = if i == 0 {
+            reader.read(width)?
+        } else if reader.read(1)? == 0 {
+            previous
+        } else if reader.read(1)? == 0 {
+            let (leading, trailing) = window.ok_or(CompressionError::CorruptInput)?;
+            previous ^ (reader.read(width - leading - trailing)? << trailing)
+        } else {
+            let leading = reader.read(LEADING_BITS)? as u32;
+            let len = match reader.read(length_bits)? as u32 {
+                0 => width,
+                len => len,
+            };
+            if leading + len > width {
+                return Err(CompressionError::CorruptInput);
+            }
+            let trailing = width - leading - len;
+            window = Some((leading, trailing));
+            previous ^ (reader.read(len)? << trailing)
+        };
+        put(i, value);
+        previous = value;
+    }
+    // Only the padding of the last byte may remain.
+    if reader.pos.div_ceil(8) != src.len() - HEADER_LENGTH {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok(count)
+}
+
+/// Destination size that is always enough to encode `count` values of type `T`.
+pub fn compress_bound<T: GorillaFloat>(count: usize) -> Result<usize, CompressionError> {
+    bound(T::BITS, count)
+}
+
+fn bound(width: u32, count: usize) -> Result<usize, CompressionError> {
+    if count > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    // The first value is stored verbatim, a later value takes at most two control
+    // bits, the window fields and the full value.
+    let worst = (2 + LEADING_BITS + length_bits(width) + width) as usize;
+    let bits = match count {
+        0 => 0,
+        n => width as usize + (n - 1) * worst,
+    };
+    Ok(HEADER_LENGTH + bits.div_ceil(8))
+}
+
+/// Encodes `values` into `dest` and returns the number of bytes written.
+pub fn compress<T: GorillaFloat>(values: &[T], dest: &mut [u8]) -> Result<usize, CompressionError> {
+    encode(
+        T::BITS,
+        values.len(),
+        values.iter().map(|v| v.to_raw()),
+        dest,
+    )
+}
+
+/// Encodes `values` into a new vector holding exactly the stream.
+pub fn compress_to_vec<T: GorillaFloat>(values: &[T]) -> Result<Vec<u8>, CompressionError> {
+    let mut dest = vec![0u8; compress_bound::<T>(values.len())?];
+    let len = compress(values, &mut dest)?;
+    dest.truncate(len);
+    dest.shrink_to_fit();
+    Ok(dest)
+}
+
+/// Number of values stored in the stream `src`.
+pub fn value_count<T: GorillaFloat>(src: &[u8]) -> Result<usize, CompressionError> {
+    read_count(T::BITS, src)
+}
+
+/// Decodes the stream `src` into `dest` and returns the number of values. The whole
+/// input is validated, so it is safe to pass untrusted data.
+pub fn decompress<T: GorillaFloat>(src: &[u8], dest: &mut [T]) -> Result<usize, CompressionError> {
+    decode(T::BITS, src, dest.len(), |i, raw| {
+        dest[i] = T::from_raw(raw)
+    })
+}
+
+/// Decodes the stream `src` into a new vector.
+pub fn decompress_to_vec<T: GorillaFloat>(src: &[u8]) -> Result<Vec<T>, CompressionError> {
+    let mut dest = vec![T::from_raw(0); value_count::<T>(src)?];
+    decompress(src, &mut dest)?;
+    Ok(dest)
+}
+
+fn width_of(typesize: usize) -> Result<u32, CompressionError> {
+    match typesize {
+        4 => Ok(32),
+        8 => Ok(64),
+        _ => Err(CompressionError::InvalidArgument),
+    }
+}
+
+/// Encodes native-endian floats of `typesize` bytes stored in `src`.
+fn compress_bytes(typesize: usize, src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+    let width = width_of(typesize)?;
+    if !src.len().is_multiple_of(typesize) {
+        return Err(CompressionError::InvalidArgument);
+    }
+    let values = src.chunks_exact(typesize).map(|chunk| {
+        let mut raw = [0u8; 8];
+        raw[..typesize].copy_from_slice(chunk);
+        if typesize == 4 {
+            u64::from(u32::from_ne_bytes([raw[0], raw[1], raw[2], raw[3]]))
+        } else {
+            u64::from_ne_bytes(raw)
+        }
+    });
+    encode(width, src.len() / typesize, values, dest)
+}
+
+fn decompress_bytes(
+    typesize: usize,
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, CompressionError> {
+    let width = width_of(typesize)?;
+    decode(width, src, dest.len() / typesize, |i, raw| {
+        let out = &mut dest[i * typesize..(i + 1) * typesize];
+        if typesize == 4 {
+            out.copy_from_slice(&(raw as u32).to_ne_bytes());
+        } else {
+            out.copy_from_slice(&raw.to_ne_bytes());
+        }
+    })
+}
+
+/// Returns the destination size that is always enough to encode `count` floats of
+/// `typesize` bytes (4 or 8) or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_gorilla_compress_bound(count: usize, typesize: usize) -> isize {
+    match width_of(typesize).and_then(|width| bound(width, count)) {
+        Ok(bound) => bound as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// Encodes `count` native-endian `float` (`typesize` 4) or `double` (`typesize` 8)
+/// values from `src`. Returns the stream size or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_gorilla_compress(
+    typesize: usize,
+    src: *const libc::c_char,
+    count: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    let src_length = match count.checked_mul(typesize) {
+        Some(len) if width_of(typesize).is_ok() => len,
+        _ => return CompressionError::InvalidArgument.code(),
+    };
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+        .and_then(|(src, dest)| compress_bytes(typesize, src, dest));
+    to_c_result(result)
+}
+
+/// Decodes a Gorilla stream into at most `dest_count` values of `typesize` bytes.
+/// Untrusted input is rejected with `CorruptInput`. Returns the number of values or
+/// a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_gorilla_decompress(
+    typesize: usize,
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_count: usize,
+) -> libc::c_int {
+    let dest_length = match dest_count.checked_mul(typesize) {
+        Some(len) if width_of(typesize).is_ok() => len,
+        _ => return CompressionError::InvalidArgument.code(),
+    };
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+        .and_then(|(src, dest)| decompress_bytes(typesize, src, dest));
+    to_c_result(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_data::XorShift;
+    use super::*;
+
+    fn prices() -> Vec<f64> {
+        let mut rng = XorShift(42);
+        let mut price = 100.0;
+        (0..10_000)
+            .map(|_| {
+                match rng.next() % 4 {
+                    0 => price += 0.25,
+                    1 => price -= 0.25,
+                    _ => {}
+                }
+                price
+            })
+            .collect()
+    }
+
+    #[test]
+    fn could_roundtrip_f64_and_f32() {
+        let values = prices();
+        let compressed = compress_to_vec(&values).unwrap();
+        assert!(compressed.len() < values.len() * 8 / 4);
+        assert_eq!(Ok(values.len()), value_count::<f64>(&compressed));
+        assert_eq!(values, decompress_to_vec::<f64>(&compressed).unwrap());
+
+        let narrow: Vec<f32> = values.iter().map(|v| *v as f32).collect();
+        let compressed = compress_to_vec(&narrow).unwrap();
+        assert!(compressed.len() < narrow.len() * 4 / 2);
+        assert_eq!(narrow, decompress_to_vec::<f32>(&compressed).unwrap());
+
+        let empty: Vec<f64> = Vec::new();
+        let compressed = compress_to_vec(&empty).unwrap();
+        assert_eq!(HEADER_LENGTH, compressed.len());
+        assert!(decompress_to_vec::<f64>(&compressed).unwrap().is_empty());
+    }
+
+    #[test]
+    fn preserves_special_values() {
+        let values = [
+            0.0,
+            -0.0,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::MIN_POSITIVE / 3.0,
+            f64::MAX,
+            1.0,
+            1.0,
+            f64::from_bits(0x7FF8_0000_0000_0001),
+        ];
+        let decoded = decompress_to_vec::<f64>(&compress_to_vec(&values).unwrap()).unwrap();
+        let bits = |v: &[f64]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&values), bits(&decoded));
+    }
+
+    #[test]
+    fn bound_holds_for_random_bits() {
+        let mut rng = XorShift(7);
+        let wide: Vec<f64> = (0..1000).map(|_| f64::from_bits(rng.next())).collect();
+        let mut dest = vec![0u8; compress_bound::<f64>(wide.len()).unwrap()];
+        assert!(compress(&wide, &mut dest).is_ok());
+        let narrow: Vec<f32> = (0..1000)
+            .map(|_| f32::from_bits(rng.next() as u32))
+            .collect();
+        let mut dest = vec![0u8; compress_bound::<f32>(narrow.len()).unwrap()];
+        assert!(compress(&narrow, &mut dest).is_ok());
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            compress(&wide, &mut dest[..100])
+        );
+    }
+
+    #[test]
+    fn rejects_corrupt_input_without_panicking() {
+        let values: Vec<f64> = prices().into_iter().take(200).collect();
+        let compressed = compress_to_vec(&values).unwrap();
+        let mut dest = vec![0f64; values.len()];
+        for len in 0..compressed.len() {
+            assert!(decompress(&compressed[..len], &mut dest).is_err());
+        }
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            decompress(&compressed, &mut dest[..10])
+        );
+
+        let mut rng = XorShift(99);
+        for _ in 0..2000 {
+            let mut mutated = compressed.clone();
+            for _ in 0..1 + rng.next() % 4 {
+                let bit = (rng.next() as usize) % (mutated.len() * 8);
+                mutated[bit / 8] ^= 1 << (bit % 8);
+            }
+            if let Ok(count) = decompress(&mutated, &mut dest) {
+                assert!(count <= dest.len());
+            }
+            let garbage: Vec<u8> = (0..rng.next() % 64).map(|_| rng.next() as u8).collect();
+            let _ = decompress_to_vec::<f64>(&garbage);
+            let _ = decompress_to_vec::<f32>(&garbage);
+        }
+        // A huge count in a short stream is rejected before allocating.
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress_to_vec::<f64>(&[0xFF, 0xFF, 0xFF, 0x7F, 0, 0, 0, 0, 0, 0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn could_use_c_api() {
+        let values = prices();
+        let bound = spreads_gorilla_compress_bound(values.len(), 8);
+        let mut compressed = vec![0u8; bound as usize];
+        let len = spreads_gorilla_compress(
+            8,
+            values.as_ptr() as *const libc::c_char,
+            values.len(),
+            compressed.as_mut_ptr() as *mut libc::c_char,
+            compressed.len(),
+        );
+        assert!(len > 0);
+        assert_eq!(
+            compress_to_vec(&values).unwrap(),
+            compressed[..len as usize].to_vec()
+        );
+        let mut decoded = vec![0f64; values.len()];
+        assert_eq!(
+            values.len() as i32,
+            spreads_gorilla_decompress(
+                8,
+                compressed.as_ptr() as *const libc::c_char,
+                len as usize,
+                decoded.as_mut_ptr() as *mut libc::c_char,
+                decoded.len(),
+            )
+        );
+        assert_eq!(values, decoded);
+        assert_eq!(
+            CompressionError::InvalidArgument.code(),
+            spreads_gorilla_compress(
+                8,
+                std::ptr::null(),
+                values.len(),
+                compressed.as_mut_ptr() as *mut libc::c_char,
+                compressed.len(),
+            )
+        );
+        assert_eq!(
+            CompressionError::InvalidArgument.code() as isize,
+            spreads_gorilla_compress_bound(10, 2)
+        );
+    }
+}
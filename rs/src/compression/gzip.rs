@@ -0,0 +1,365 @@
+//! Gzip members with header metadata. `Codec::Gzip` writes members without a file
+//! name, comment or modification time; the functions here set and read them. All
+//! decompression functions decode concatenated members, e.g. from `cat a.gz b.gz`.
+
+use super::zlib::{self, Deflater, Inflater};
+use super::{ffi_buffers, ffi_slice_mut, to_c_result, Codec, CompressionError, MAX_INPUT_LENGTH};
+use spreads_blosc_sys as ffi;
+use std::ffi::CStr;
+
+/// Space for a header string read back from a member, including the terminating nul.
+/// Longer strings are truncated.
+const FIELD_CAPACITY: usize = 4096;
+
+/// Header fields of a gzip member. Strings are ISO 8859-1 without the terminating nul.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GzipHeader {
+    /// Original file name.
+    pub filename: Option<Vec<u8>>,
+    pub comment: Option<Vec<u8>>,
+    /// Modification time in seconds since the Unix epoch, 0 if unknown.
+    pub mtime: u32,
+}
+
+/// Nul-terminated copy of a header string, rejecting interior nul bytes.
+fn terminated(field: &Option<Vec<u8>>) -> Result<Option<Vec<u8>>, CompressionError> {
+    match field {
+        Some(field) if field.contains(&0) => Err(CompressionError::InvalidArgument),
+        Some(field) => {
+            let mut terminated = Vec::with_capacity(field.len() + 1);
+            terminated.extend_from_slice(field);
+            terminated.push(0);
+            Ok(Some(terminated))
+        }
+        None => Ok(None),
+    }
+}
+
+fn field_len(field: &Option<Vec<u8>>) -> usize {
+    field.as_ref().map_or(0, |field| field.len() + 1)
+}
+
+/// Worst-case size of a member holding `input_len` bytes with `header`.
+pub fn compress_bound(input_len: usize, header: &GzipHeader) -> Result<usize, CompressionError> {
+    let bound = Codec::Gzip.compress_bound(input_len)?
+        + field_len(&header.filename)
+        + field_len(&header.comment);
+    if bound > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    Ok(bound)
+}
+
+/// Compresses `input` into a single gzip member with the given header fields and
+/// returns the number of bytes written. Levels are zlib levels from 0 to 9.
+pub fn compress(
+    input: &[u8],
+    output: &mut [u8],
+    level: i32,
+    header: &GzipHeader,
+) -> Result<usize, CompressionError> {
+    if !Codec::Gzip.levels().contains(&level) {
+        return Err(CompressionError::InvalidLevel);
+    }
+    if input.len() > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    let output_len = output.len().min(MAX_INPUT_LENGTH);
+    let mut filename = terminated(&header.filename)?;
+    let mut comment = terminated(&header.comment)?;
+    let mut head: ffi::gz_header = unsafe { std::mem::zeroed() };
+    head.time = libc::c_ulong::from(header.mtime);
+    head.os = ffi::OS_UNKNOWN as libc::c_int;
+    if let Some(filename) = filename.as_mut() {
+        head.name = filename.as_mut_ptr();
+    }
+    if let Some(comment) = comment.as_mut() {
+        head.comment = comment.as_mut_ptr();
+    }
+    let mut deflater = Deflater::new(level, zlib::WINDOW_BITS_GZIP)?;
+    // `head` and the strings live until the end of this function.
+    unsafe { deflater.set_header(&mut head)? };
+    deflater.finish(input, &mut output[..output_len])
+}
+
+/// Compresses `input` into a new vector that is exactly as long as the member.
+pub fn compress_to_vec(
+    input: &[u8],
+    level: i32,
+    header: &GzipHeader,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut output = vec![0u8; compress_bound(input.len(), header)?];
+    let len = compress(input, &mut output, level, header)?;
+    output.truncate(len);
+    output.shrink_to_fit();
+    Ok(output)
+}
+
+/// Decompresses all members of `input` into `output`. Returns the number of bytes
+/// written and the header of the first member.
+pub fn decompress(
+    input: &[u8],
+    output: &mut [u8],
+) -> Result<(usize, GzipHeader), CompressionError> {
+    if input.len() > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    let output_len = output.len().min(MAX_INPUT_LENGTH);
+    let mut filename = vec![0u8; FIELD_CAPACITY];
+    let mut comment = vec![0u8; FIELD_CAPACITY];
+    let mut head: ffi::gz_header = unsafe { std::mem::zeroed() };
+    head.name = filename.as_mut_ptr();
+    head.name_max = FIELD_CAPACITY as libc::c_uint;
+    head.comment = comment.as_mut_ptr();
+    head.comm_max = FIELD_CAPACITY as libc::c_uint;
+    let len = {
+        let mut inflater = Inflater::new(zlib::WINDOW_BITS_GZIP)?;
+        // The inflater is dropped before `head` and the buffers.
+        unsafe { inflater.capture_header(&mut head)? };
+        zlib::inflate_members(&mut inflater, input, &mut output[..output_len], true)?
+    };
+    // zlib clears the pointers of fields that are not present.
+    let field = |ptr: *mut u8, buffer: &[u8]| {
+        if ptr.is_null() {
+            None
+        } else {
+            let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+            Some(buffer[..len].to_vec())
+        }
+    };
+    let header = GzipHeader {
+        filename: field(head.name, &filename),
+        comment: field(head.comment, &comment),
+        mtime: head.time as u32,
+    };
+    Ok((len, header))
+}
+
+/// Returns the worst-case member size of `input_length` bytes with the given
+/// nul-terminated header strings (NULL when omitted), or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_gzip_compress_bound(
+    input_length: usize,
+    filename: *const libc::c_char,
+    comment: *const libc::c_char,
+) -> isize {
+    let header = unsafe { ffi_header(filename, comment, 0) };
+    match compress_bound(input_length, &header) {
+        Ok(bound) => bound as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// Compresses into a gzip member whose header stores `filename` and `comment`
+/// (nul-terminated ISO 8859-1 strings, NULL to omit) and `mtime` (seconds since the
+/// Unix epoch, 0 if unknown). Returns the member length or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_gzip_compress(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    clevel: libc::c_int,
+    filename: *const libc::c_char,
+    comment: *const libc::c_char,
+    mtime: u32,
+) -> libc::c_int {
+    let header = unsafe { ffi_header(filename, comment, mtime) };
+    let result = unsafe { ffi_buffers(input, input_length, output, maxout) }
+        .and_then(|(input, output)| compress(input, output, clevel, &header));
+    to_c_result(result)
+}
+
+/// Decompresses all members of `input`. The header fields of the first member are
+/// written to `filename` and `comment` as nul-terminated strings, truncated to the
+/// given capacities and empty when absent, and to `mtime`. Each of them may be NULL.
+/// Returns the number of bytes written to `output` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_gzip_decompress(
+    input: *const libc::c_char,
+    input_length: usize,
+    output: *mut libc::c_char,
+    maxout: usize,
+    filename: *mut libc::c_char,
+    filename_capacity: usize,
+    comment: *mut libc::c_char,
+    comment_capacity: usize,
+    mtime: *mut u32,
+) -> libc::c_int {
+    let buffers = unsafe { ffi_buffers(input, input_length, output, maxout) };
+    match buffers.and_then(|(input, output)| decompress(input, output)) {
+        Ok((len, header)) => {
+            copy_field(&header.filename, filename, filename_capacity);
+            copy_field(&header.comment, comment, comment_capacity);
+            if let Some(mtime) = unsafe { mtime.as_mut() } {
+                *mtime = header.mtime;
+            }
+            len as libc::c_int
+        }
+        Err(err) => err.code(),
+    }
+}
+
+unsafe fn ffi_string(ptr: *const libc::c_char) -> Option<Vec<u8>> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_bytes().to_vec())
+    }
+}
+
+unsafe fn ffi_header(
+    filename: *const libc::c_char,
+    comment: *const libc::c_char,
+    mtime: u32,
+) -> GzipHeader {
+    GzipHeader {
+        filename: ffi_string(filename),
+        comment: ffi_string(comment),
+        mtime,
+    }
+}
+
+/// Copies a header string into a caller buffer as a truncated nul-terminated string.
+fn copy_field(field: &Option<Vec<u8>>, ptr: *mut libc::c_char, capacity: usize) {
+    // The fields are optional outputs, a null buffer skips the copy.
+    let dest = match unsafe { ffi_slice_mut(ptr, capacity) } {
+        Ok(dest) if !dest.is_empty() => dest,
+        _ => return,
+    };
+    let field = field.as_ref().map_or(&[][..], |field| &field[..]);
+    let len = field.len().min(dest.len() - 1);
+    dest[..len].copy_from_slice(&field[..len]);
+    dest[len] = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_data() -> Vec<u8> {
+        (0..10_000u32)
+            .flat_map(|i| format!("line {}\n", i % 97).into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn could_roundtrip_header_fields() {
+        let original = sample_data();
+        let header = GzipHeader {
+            filename: Some(b"quotes.csv".to_vec()),
+            comment: Some(b"rotated at 00:00".to_vec()),
+            mtime: 1_600_000_000,
+        };
+        let compressed = compress_to_vec(&original, 6, &header).unwrap();
+        assert_eq!(&[0x1F, 0x8B], &compressed[..2]);
+        let mut decompressed = vec![0u8; original.len()];
+        assert_eq!(
+            Ok((original.len(), header)),
+            decompress(&compressed, &mut decompressed)
+        );
+        assert_eq!(original, decompressed);
+
+        let plain = Codec::Gzip.compress_to_vec(&original, 6).unwrap();
+        assert_eq!(
+            Ok((original.len(), GzipHeader::default())),
+            decompress(&plain, &mut decompressed)
+        );
+    }
+
+    #[test]
+    fn could_decompress_concatenated_members() {
+        let first = b"first member\n".to_vec();
+        let second = sample_data();
+        let header = GzipHeader {
+            filename: Some(b"a.log".to_vec()),
+            ..GzipHeader::default()
+        };
+        let mut members = compress_to_vec(&first, 6, &header).unwrap();
+        members.extend_from_slice(&Codec::Gzip.compress_to_vec(&second, 6).unwrap());
+        let mut expected = first.clone();
+        expected.extend_from_slice(&second);
+
+        assert_eq!(
+            expected,
+            Codec::Gzip
+                .decompress_to_vec(&members, expected.len())
+                .unwrap()
+        );
+        assert_eq!(
+            &expected[..],
+            &Codec::Gzip
+                .decompress_with_limit(&members, expected.len())
+                .unwrap()[..]
+        );
+        let mut decompressed = vec![0u8; expected.len()];
+        assert_eq!(
+            Ok((expected.len(), header)),
+            decompress(&members, &mut decompressed)
+        );
+
+        members.extend_from_slice(b"garbage");
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            Codec::Gzip.decompress(&members, &mut vec![0u8; expected.len() * 2])
+        );
+    }
+
+    #[test]
+    fn rejects_nul_in_header_fields() {
+        let header = GzipHeader {
+            comment: Some(b"a\0b".to_vec()),
+            ..GzipHeader::default()
+        };
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            compress_to_vec(b"data", 6, &header)
+        );
+    }
+
+    #[test]
+    fn could_roundtrip_through_c_api() {
+        let original = sample_data();
+        let filename = b"quotes.csv\0";
+        let bound = spreads_gzip_compress_bound(
+            original.len(),
+            filename.as_ptr() as *const libc::c_char,
+            std::ptr::null(),
+        );
+        let mut compressed = vec![0u8; bound as usize];
+        let len = spreads_gzip_compress(
+            original.as_ptr() as *const libc::c_char,
+            original.len(),
+            compressed.as_mut_ptr() as *mut libc::c_char,
+            compressed.len(),
+            9,
+            filename.as_ptr() as *const libc::c_char,
+            std::ptr::null(),
+            42,
+        );
+        assert!(len > 0);
+
+        let mut decompressed = vec![0u8; original.len()];
+        let mut name = [0xFFu8; 6];
+        let mut comment = [0xFFu8; 8];
+        let mut mtime = 0u32;
+        assert_eq!(
+            original.len() as libc::c_int,
+            spreads_gzip_decompress(
+                compressed.as_ptr() as *const libc::c_char,
+                len as usize,
+                decompressed.as_mut_ptr() as *mut libc::c_char,
+                decompressed.len(),
+                name.as_mut_ptr() as *mut libc::c_char,
+                name.len(),
+                comment.as_mut_ptr() as *mut libc::c_char,
+                comment.len(),
+                &mut mtime,
+            )
+        );
+        assert_eq!(original, decompressed);
+        assert_eq!(b"quote\0", &name);
+        assert_eq!(0, comment[0]);
+        assert_eq!(42, mtime);
+    }
+}
@@ -0,0 +1,1035 @@
+//! LZ4 frame format on top of the LZ4 block codec vendored inside c-blosc, so that
+//! the output can be read by the `lz4` command line tool and other LZ4 bindings.
+//! Frames are written with independent blocks. Both independent and linked blocks
+//! are read, as well as concatenated and skippable frames. Dictionary ids are not
+//! supported. Levels are the Blosc 0 to 9 scale of `Codec::Lz4`.
+
+use super::xxhash::{xxh32, Xxh32};
+use super::{ffi_buffers, ffi_slice, to_c_result, Codec, CompressionError, MAX_INPUT_LENGTH};
+use spreads_blosc_sys as ffi;
+use std::io::{self, Read, Write};
+
+const MAGIC: u32 = 0x184D_2204;
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+const SKIPPABLE_MASK: u32 = 0xFFFF_FFF0;
+
+const FLG_VERSION: u8 = 0x40;
+const FLG_VERSION_MASK: u8 = 0xC0;
+const FLG_BLOCK_INDEPENDENT: u8 = 0x20;
+const FLG_BLOCK_CHECKSUM: u8 = 0x10;
+const FLG_CONTENT_SIZE: u8 = 0x08;
+const FLG_CONTENT_CHECKSUM: u8 = 0x04;
+const FLG_RESERVED: u8 = 0x02;
+const FLG_DICT_ID: u8 = 0x01;
+const BD_RESERVED: u8 = 0x8F;
+
+/// Magic, FLG, BD, content size, dictionary id and header checksum.
+const MAX_HEADER_SIZE: usize = 19;
+const UNCOMPRESSED_BLOCK: u32 = 0x8000_0000;
+/// Linked blocks may reference up to 64 KB of previously decoded data.
+const LINKED_WINDOW: usize = 64 * 1024;
+
+/// Maximum size of the uncompressed data in a block. The discriminants are the
+/// block size ids of the frame format.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockSize {
+    Max64KB = 4,
+    Max256KB = 5,
+    Max1MB = 6,
+    Max4MB = 7,
+}
+
+impl BlockSize {
+    pub fn from_code(code: i32) -> Result<BlockSize, CompressionError> {
+        match code {
+            4 => Ok(BlockSize::Max64KB),
+            5 => Ok(BlockSize::Max256KB),
+            6 => Ok(BlockSize::Max1MB),
+            7 => Ok(BlockSize::Max4MB),
+            _ => Err(CompressionError::InvalidArgument),
+        }
+    }
+
+    pub fn bytes(self) -> usize {
+        1 << (8 + 2 * self as usize)
+    }
+}
+
+/// Options of written frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameOptions {
+    /// Blosc level from 0 to 9.
+    pub level: i32,
+    pub block_size: BlockSize,
+    /// Append XXH32 of every stored block.
+    pub block_checksum: bool,
+    /// Append XXH32 of the uncompressed content after the last block.
+    pub content_checksum: bool,
+    /// Store the uncompressed size in the header. `Lz4FrameWriter` requires the size
+    /// upfront, see `Lz4FrameWriter::with_content_size`.
+    pub content_size: bool,
+}
+
+impl Default for FrameOptions {
+    /// The defaults of the `lz4` command line tool: content checksum only.
+    fn default() -> FrameOptions {
+        FrameOptions {
+            level: 5,
+            block_size: BlockSize::Max64KB,
+            block_checksum: false,
+            content_checksum: true,
+            content_size: false,
+        }
+    }
+}
+
+impl FrameOptions {
+    fn validate(&self) -> Result<(), CompressionError> {
+        if !Codec::Lz4.levels().contains(&self.level) {
+            return Err(CompressionError::InvalidLevel);
+        }
+        Ok(())
+    }
+
+    fn header(&self, content_size: Option<u64>) -> FrameHeader {
+        FrameHeader {
+            block_size: self.block_size.bytes(),
+            linked: false,
+            block_checksum: self.block_checksum,
+            content_checksum: self.content_checksum,
+            content_size,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct FrameHeader {
+    block_size: usize,
+    linked: bool,
+    block_checksum: bool,
+    content_checksum: bool,
+    content_size: Option<u64>,
+}
+
+impl FrameHeader {
+    /// Length of the frame descriptor that follows the magic number, derived from FLG.
+    fn descriptor_len(flg: u8) -> usize {
+        let mut len = 3;
+        if flg & FLG_CONTENT_SIZE != 0 {
+            len += 8;
+        }
+        if flg & FLG_DICT_ID != 0 {
+            len += 4;
+        }
+        len
+    }
+
+    /// Parses the frame descriptor, i.e. FLG to the header checksum inclusive.
+    fn parse(descriptor: &[u8]) -> Result<FrameHeader, CompressionError> {
+        let flg = descriptor[0];
+        let bd = descriptor[1];
+        if flg & FLG_VERSION_MASK != FLG_VERSION || flg & FLG_RESERVED != 0 || bd & BD_RESERVED != 0
+        {
+            return Err(CompressionError::CorruptInput);
+        }
+        let block_size = BlockSize::from_code(i32::from(bd >> 4))
+            .map_err(|_| CompressionError::CorruptInput)?
+            .bytes();
+        let checked = &descriptor[..descriptor.len() - 1];
+        if (xxh32(checked, 0) >> 8) as u8 != descriptor[descriptor.len() - 1] {
+            return Err(CompressionError::CorruptInput);
+        }
+        if flg & FLG_DICT_ID != 0 {
+            // Decoding would need the dictionary the frame was written with.
+            return Err(CompressionError::UnsupportedCodec);
+        }
+        let content_size = if flg & FLG_CONTENT_SIZE != 0 {
+            let mut size = [0u8; 8];
+            size.copy_from_slice(&descriptor[2..10]);
+            Some(u64::from_le_bytes(size))
+        } else {
+            None
+        };
+        Ok(FrameHeader {
+            block_size,
+            linked: flg & FLG_BLOCK_INDEPENDENT == 0,
+            block_checksum: flg & FLG_BLOCK_CHECKSUM != 0,
+            content_checksum: flg & FLG_CONTENT_CHECKSUM != 0,
+            content_size,
+        })
+    }
+
+    /// Writes the magic number and the frame descriptor, returns the header length.
+    fn encode(&self, dest: &mut [u8; MAX_HEADER_SIZE]) -> usize {
+        let mut flg = FLG_VERSION;
+        if !self.linked {
+            flg |= FLG_BLOCK_INDEPENDENT;
+        }
+        if self.block_checksum {
+            flg |= FLG_BLOCK_CHECKSUM;
+        }
+        if self.content_checksum {
+            flg |= FLG_CONTENT_CHECKSUM;
+        }
+        if self.content_size.is_some() {
+            flg |= FLG_CONTENT_SIZE;
+        }
+        let block_size_id = self.block_size.trailing_zeros() as u8 / 2 - 4;
+        dest[..4].copy_from_slice(&MAGIC.to_le_bytes());
+        dest[4] = flg;
+        dest[5] = block_size_id << 4;
+        let mut len = 6;
+        if let Some(size) = self.content_size {
+            dest[6..14].copy_from_slice(&size.to_le_bytes());
+            len = 14;
+        }
+        dest[len] = (xxh32(&dest[4..len], 0) >> 8) as u8;
+        len + 1
+    }
+
+    fn block_checksum_len(&self) -> usize {
+        if self.block_checksum {
+            4
+        } else {
+            0
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Worst-case size of a frame holding `len` bytes: incompressible blocks are stored
+/// as they are, so a block never grows by more than its size word and checksum.
+pub fn compress_bound(len: usize, options: &FrameOptions) -> Result<usize, CompressionError> {
+    if len > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    let block_size = options.block_size.bytes();
+    let blocks = len.div_ceil(block_size);
+    let per_block = if options.block_checksum { 8 } else { 4 };
+    let checksum = if options.content_checksum { 4 } else { 0 };
+    let bound = MAX_HEADER_SIZE + len + blocks * per_block + 4 + checksum;
+    if bound > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    Ok(bound)
+}
+
+/// Compresses a block with its size word and optional checksum into `dest`, storing
+/// it uncompressed when LZ4 does not make it smaller. Returns the written length.
+fn compress_block(
+    block: &[u8],
+    dest: &mut [u8],
+    header: &FrameHeader,
+    level: i32,
+) -> Result<usize, CompressionError> {
+    let checksum_len = header.block_checksum_len();
+    if dest.len() < 4 + checksum_len {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    let available = dest.len() - 4 - checksum_len;
+    let limit = available.min(block.len().saturating_sub(1));
+    let (word, len) = match Codec::Lz4.compress(block, &mut dest[4..4 + limit], level) {
+        Ok(len) => (len as u32, len),
+        Err(CompressionError::BufferTooSmall) => {
+            if block.len() > available {
+                return Err(CompressionError::BufferTooSmall);
+            }
+            dest[4..4 + block.len()].copy_from_slice(block);
+            (block.len() as u32 | UNCOMPRESSED_BLOCK, block.len())
+        }
+        Err(err) => return Err(err),
+    };
+    dest[..4].copy_from_slice(&word.to_le_bytes());
+    if header.block_checksum {
+        let checksum = xxh32(&dest[4..4 + len], 0);
+        dest[4 + len..8 + len].copy_from_slice(&checksum.to_le_bytes());
+    }
+    Ok(4 + len + checksum_len)
+}
+
+/// Decodes a stored block into `out[prefix..]`, where `out[..prefix]` is the content
+/// decoded so far that linked blocks may reference.
+fn decompress_block(
+    header: &FrameHeader,
+    word: u32,
+    data: &[u8],
+    out: &mut [u8],
+    prefix: usize,
+) -> Result<usize, CompressionError> {
+    let capacity = (out.len() - prefix).min(header.block_size);
+    if word & UNCOMPRESSED_BLOCK != 0 {
+        if data.len() > capacity {
+            return Err(CompressionError::BufferTooSmall);
+        }
+        out[prefix..prefix + data.len()].copy_from_slice(data);
+        return Ok(data.len());
+    }
+    let dict_len = if header.linked {
+        prefix.min(LINKED_WINDOW)
+    } else {
+        0
+    };
+    let (history, block) = out.split_at_mut(prefix);
+    let decoded = unsafe {
+        ffi::LZ4_decompress_safe_usingDict(
+            data.as_ptr() as *const libc::c_char,
+            block.as_mut_ptr() as *mut libc::c_char,
+            data.len() as libc::c_int,
+            capacity as libc::c_int,
+            history[prefix - dict_len..].as_ptr() as *const libc::c_char,
+            dict_len as libc::c_int,
+        )
+    };
+    if decoded >= 0 {
+        return Ok(decoded as usize);
+    }
+    if capacity == header.block_size {
+        return Err(CompressionError::CorruptInput);
+    }
+    // LZ4 reports a short output like malformed input. Decode into a full block to
+    // tell them apart.
+    let mut scratch = history[prefix - dict_len..].to_vec();
+    scratch.resize(dict_len + header.block_size, 0);
+    decompress_block(header, word, data, &mut scratch, dict_len)?;
+    Err(CompressionError::BufferTooSmall)
+}
+
+/// Compresses `src` into a single frame and returns the number of bytes written.
+pub fn compress(
+    src: &[u8],
+    dest: &mut [u8],
+    options: &FrameOptions,
+) -> Result<usize, CompressionError> {
+    options.validate()?;
+    if src.len() > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    let dest_len = dest.len().min(MAX_INPUT_LENGTH);
+    let dest = &mut dest[..dest_len];
+    let content_size = if options.content_size {
+        Some(src.len() as u64)
+    } else {
+        None
+    };
+    let header = options.header(content_size);
+    let mut encoded = [0u8; MAX_HEADER_SIZE];
+    let mut pos = header.encode(&mut encoded);
+    if dest.len() < pos {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    dest[..pos].copy_from_slice(&encoded[..pos]);
+    for block in src.chunks(header.block_size) {
+        pos += compress_block(block, &mut dest[pos..], &header, options.level)?;
+    }
+    let trailer_len = if header.content_checksum { 8 } else { 4 };
+    if dest.len() - pos < trailer_len {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    dest[pos..pos + 4].copy_from_slice(&0u32.to_le_bytes());
+    if header.content_checksum {
+        dest[pos + 4..pos + 8].copy_from_slice(&xxh32(src, 0).to_le_bytes());
+    }
+    Ok(pos + trailer_len)
+}
+
+/// Compresses `src` into a new vector that is exactly as long as the frame.
+pub fn compress_to_vec(src: &[u8], options: &FrameOptions) -> Result<Vec<u8>, CompressionError> {
+    let mut dest = vec![0u8; compress_bound(src.len(), options)?];
+    let len = compress(src, &mut dest, options)?;
+    dest.truncate(len);
+    dest.shrink_to_fit();
+    Ok(dest)
+}
+
+/// Returns the length of the frame descriptor at `src[pos..]` after validating that
+/// the whole descriptor is present.
+fn descriptor_at(src: &[u8], pos: usize) -> Result<FrameHeader, CompressionError> {
+    let len = match src.get(pos) {
+        Some(flg) => FrameHeader::descriptor_len(*flg),
+        None => return Err(CompressionError::CorruptInput),
+    };
+    match src.get(pos..pos + len) {
+        Some(descriptor) => FrameHeader::parse(descriptor),
+        None => Err(CompressionError::CorruptInput),
+    }
+}
+
+/// Uncompressed size stored in the header of the first frame of `src`, if any.
+pub fn content_size(src: &[u8]) -> Result<Option<u64>, CompressionError> {
+    if src.len() < 4 || read_u32(src) != MAGIC {
+        return Err(CompressionError::CorruptInput);
+    }
+    Ok(descriptor_at(src, 4)?.content_size)
+}
+
+/// Decompresses all frames in `src` into `dest` and returns the number of bytes written.
+pub fn decompress(src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+    if src.is_empty() {
+        return Err(CompressionError::CorruptInput);
+    }
+    let dest_len = dest.len().min(MAX_INPUT_LENGTH);
+    let dest = &mut dest[..dest_len];
+    let mut pos = 0;
+    let mut written = 0;
+    while pos < src.len() {
+        let magic = match src.get(pos..pos + 4) {
+            Some(magic) => read_u32(magic),
+            None => return Err(CompressionError::CorruptInput),
+        };
+        pos += 4;
+        if magic & SKIPPABLE_MASK == SKIPPABLE_MAGIC {
+            let size = match src.get(pos..pos + 4) {
+                Some(size) => read_u32(size) as usize,
+                None => return Err(CompressionError::CorruptInput),
+            };
+            pos = pos
+                .checked_add(4 + size)
+                .filter(|end| *end <= src.len())
+                .ok_or(CompressionError::CorruptInput)?;
+            continue;
+        }
+        if magic != MAGIC {
+            return Err(CompressionError::CorruptInput);
+        }
+        let header = descriptor_at(src, pos)?;
+        pos += FrameHeader::descriptor_len(src[pos]);
+        if let Some(size) = header.content_size {
+            if size > (dest.len() - written) as u64 {
+                return Err(CompressionError::BufferTooSmall);
+            }
+        }
+        let frame_start = written;
+        loop {
+            let word = match src.get(pos..pos + 4) {
+                Some(word) => read_u32(word),
+                None => return Err(CompressionError::CorruptInput),
+            };
+            pos += 4;
+            if word == 0 {
+                break;
+            }
+            let len = (word & !UNCOMPRESSED_BLOCK) as usize;
+            let checksum_len = header.block_checksum_len();
+            if len > header.block_size || src.len() - pos < len + checksum_len {
+                return Err(CompressionError::CorruptInput);
+            }
+            let data = &src[pos..pos + len];
+            if header.block_checksum && xxh32(data, 0) != read_u32(&src[pos + len..]) {
+                return Err(CompressionError::CorruptInput);
+            }
+            pos += len + checksum_len;
+            let out = &mut dest[frame_start..];
+            written += decompress_block(&header, word, data, out, written - frame_start)?;
+        }
+        let content = &dest[frame_start..written];
+        if header.content_checksum {
+            match src.get(pos..pos + 4) {
+                Some(checksum) if read_u32(checksum) == xxh32(content, 0) => pos += 4,
+                _ => return Err(CompressionError::CorruptInput),
+            }
+        }
+        if let Some(size) = header.content_size {
+            if size != content.len() as u64 {
+                return Err(CompressionError::CorruptInput);
+            }
+        }
+    }
+    Ok(written)
+}
+
+/// Decompresses `src` into a new vector. `max_len` is the upper bound of the
+/// decompressed size, the returned vector is truncated to the actual size.
+pub fn decompress_to_vec(src: &[u8], max_len: usize) -> Result<Vec<u8>, CompressionError> {
+    let mut dest = vec![0u8; max_len];
+    let len = decompress(src, &mut dest)?;
+    dest.truncate(len);
+    dest.shrink_to_fit();
+    Ok(dest)
+}
+
+/// Writes a single LZ4 frame to the inner writer. `flush` emits the buffered data as
+/// a shorter block, which is valid but lowers the compression ratio.
+pub struct Lz4FrameWriter<W: Write> {
+    writer: Option<W>,
+    options: FrameOptions,
+    header: FrameHeader,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    checksum: Xxh32,
+    written: u64,
+}
+
+impl<W: Write> Lz4FrameWriter<W> {
+    /// Starts a frame without a content size, `options.content_size` must be false.
+    pub fn new(writer: W, options: FrameOptions) -> io::Result<Lz4FrameWriter<W>> {
+        if options.content_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the content size must be given to Lz4FrameWriter::with_content_size",
+            ));
+        }
+        Self::start(writer, options, None)
+    }
+
+    /// Starts a frame that declares `size` bytes of content. Finishing the frame
+    /// fails if a different number of bytes was written.
+    pub fn with_content_size(
+        writer: W,
+        options: FrameOptions,
+        size: u64,
+    ) -> io::Result<Lz4FrameWriter<W>> {
+        Self::start(writer, options, Some(size))
+    }
+
+    fn start(
+        mut writer: W,
+        options: FrameOptions,
+        content_size: Option<u64>,
+    ) -> io::Result<Lz4FrameWriter<W>> {
+        options.validate()?;
+        let header = options.header(content_size);
+        let mut encoded = [0u8; MAX_HEADER_SIZE];
+        let len = header.encode(&mut encoded);
+        writer.write_all(&encoded[..len])?;
+        Ok(Lz4FrameWriter {
+            writer: Some(writer),
+            input: Vec::with_capacity(header.block_size),
+            output: vec![0u8; 8 + header.block_size],
+            options,
+            header,
+            checksum: Xxh32::new(0),
+            written: 0,
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer
+            .as_ref()
+            .expect("writer is only taken by finish")
+    }
+
+    /// Completes the frame and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.end_frame()?;
+        Ok(self.writer.take().expect("writer is only taken by finish"))
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.input.is_empty() {
+            return Ok(());
+        }
+        let len = compress_block(
+            &self.input,
+            &mut self.output,
+            &self.header,
+            self.options.level,
+        )?;
+        self.input.clear();
+        self.writer
+            .as_mut()
+            .expect("writer is only taken by finish")
+            .write_all(&self.output[..len])
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        if let Some(size) = self.header.content_size {
+            if size != self.written {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "written length differs from the declared content size",
+                ));
+            }
+        }
+        let writer = self
+            .writer
+            .as_mut()
+            .expect("writer is only taken by finish");
+        writer.write_all(&0u32.to_le_bytes())?;
+        if self.header.content_checksum {
+            writer.write_all(&self.checksum.digest().to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Lz4FrameWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(size) = self.header.content_size {
+            if self.written + buf.len() as u64 > size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "write exceeds the declared content size",
+                ));
+            }
+        }
+        let mut consumed = 0;
+        while consumed < buf.len() {
+            let take = (self.header.block_size - self.input.len()).min(buf.len() - consumed);
+            self.input
+                .extend_from_slice(&buf[consumed..consumed + take]);
+            consumed += take;
+            if self.input.len() == self.header.block_size {
+                self.write_block()?;
+            }
+        }
+        self.checksum.update(buf);
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.writer
+            .as_mut()
+            .expect("writer is only taken by finish")
+            .flush()
+    }
+}
+
+impl<W: Write> Drop for Lz4FrameWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.end_frame();
+        }
+    }
+}
+
+/// Decompresses LZ4 frames read from the inner reader, skipping skippable frames.
+pub struct Lz4FrameReader<R: Read> {
+    reader: R,
+    header: Option<FrameHeader>,
+    checksum: Xxh32,
+    frame_len: u64,
+    block: Vec<u8>,
+    decoded: Vec<u8>,
+    pos: usize,
+    len: usize,
+}
+
+impl<R: Read> Lz4FrameReader<R> {
+    pub fn new(reader: R) -> Lz4FrameReader<R> {
+        Lz4FrameReader {
+            reader,
+            header: None,
+            checksum: Xxh32::new(0),
+            frame_len: 0,
+            block: Vec::new(),
+            decoded: Vec::new(),
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads a little-endian word, `None` at a clean end of the stream.
+    fn read_word(&mut self) -> io::Result<Option<u32>> {
+        let mut word = [0u8; 4];
+        let mut filled = 0;
+        while filled < word.len() {
+            match self.reader.read(&mut word[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(truncated()),
+                Ok(n) => filled += n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Some(u32::from_le_bytes(word)))
+    }
+
+    fn expect_word(&mut self) -> io::Result<u32> {
+        self.read_word()?.ok_or_else(truncated)
+    }
+
+    /// Reads the next frame header, returns false at the end of the stream.
+    fn next_frame(&mut self) -> io::Result<bool> {
+        loop {
+            let magic = match self.read_word()? {
+                Some(magic) => magic,
+                None => return Ok(false),
+            };
+            if magic & SKIPPABLE_MASK == SKIPPABLE_MAGIC {
+                let size = u64::from(self.expect_word()?);
+                let skipped = io::copy(&mut self.reader.by_ref().take(size), &mut io::sink())?;
+                if skipped != size {
+                    return Err(truncated());
+                }
+                continue;
+            }
+            if magic != MAGIC {
+                return Err(CompressionError::CorruptInput.into());
+            }
+            let mut descriptor = [0u8; MAX_HEADER_SIZE - 4];
+            self.reader.read_exact(&mut descriptor[..1])?;
+            let len = FrameHeader::descriptor_len(descriptor[0]);
+            self.reader.read_exact(&mut descriptor[1..len])?;
+            let header = FrameHeader::parse(&descriptor[..len])?;
+            let window = if header.linked { LINKED_WINDOW } else { 0 };
+            self.decoded.resize(window + header.block_size, 0);
+            self.checksum = Xxh32::new(0);
+            self.frame_len = 0;
+            self.pos = 0;
+            self.len = 0;
+            self.header = Some(header);
+            return Ok(true);
+        }
+    }
+
+    /// Decodes the next block into `decoded[pos..len]`, verifying the frame trailer
+    /// at the end of the frame.
+    fn next_block(&mut self) -> io::Result<()> {
+        let word = self.expect_word()?;
+        let header = self.header.expect("called inside a frame");
+        if word == 0 {
+            if header.content_checksum && self.expect_word()? != self.checksum.digest() {
+                return Err(CompressionError::CorruptInput.into());
+            }
+            if let Some(size) = header.content_size {
+                if size != self.frame_len {
+                    return Err(CompressionError::CorruptInput.into());
+                }
+            }
+            self.header = None;
+            return Ok(());
+        }
+        let len = (word & !UNCOMPRESSED_BLOCK) as usize;
+        if len > header.block_size {
+            return Err(CompressionError::CorruptInput.into());
+        }
+        let checksum_len = header.block_checksum_len();
+        self.block.resize(len + checksum_len, 0);
+        self.reader.read_exact(&mut self.block)?;
+        let data = &self.block[..len];
+        if header.block_checksum && xxh32(data, 0) != read_u32(&self.block[len..]) {
+            return Err(CompressionError::CorruptInput.into());
+        }
+        // Keep the tail of the previous block for linked blocks to reference.
+        let prefix = if header.linked {
+            let kept = self.len.min(LINKED_WINDOW);
+            self.decoded.copy_within(self.len - kept..self.len, 0);
+            kept
+        } else {
+            0
+        };
+        let decoded = decompress_block(&header, word, data, &mut self.decoded, prefix)?;
+        self.checksum
+            .update(&self.decoded[prefix..prefix + decoded]);
+        self.frame_len += decoded as u64;
+        self.pos = prefix;
+        self.len = prefix + decoded;
+        Ok(())
+    }
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "lz4 frame is truncated")
+}
+
+impl<R: Read> Read for Lz4FrameReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.pos < self.len {
+                let len = buf.len().min(self.len - self.pos);
+                buf[..len].copy_from_slice(&self.decoded[self.pos..self.pos + len]);
+                self.pos += len;
+                return Ok(len);
+            }
+            if self.header.is_none() && !self.next_frame()? {
+                return Ok(0);
+            }
+            self.next_block()?;
+        }
+    }
+}
+
+/// Returns the worst-case frame size of `src_length` bytes with the given block size
+/// id (4 to 7) and both checksums enabled, or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_lz4frame_compress_bound(
+    src_length: usize,
+    block_size: libc::c_int,
+) -> isize {
+    let bound = BlockSize::from_code(block_size).and_then(|block_size| {
+        let options = FrameOptions {
+            block_size,
+            block_checksum: true,
+            content_checksum: true,
+            ..FrameOptions::default()
+        };
+        compress_bound(src_length, &options)
+    });
+    match bound {
+        Ok(bound) => bound as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// Compresses `src` into a single LZ4 frame. `block_size` is the block size id of the
+/// frame format (4 to 7), the flags are booleans. Returns the frame length or a
+/// negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_lz4frame_compress(
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    level: libc::c_int,
+    block_size: libc::c_int,
+    block_checksum: libc::c_int,
+    content_checksum: libc::c_int,
+    content_size: libc::c_int,
+) -> libc::c_int {
+    let options = match BlockSize::from_code(block_size) {
+        Ok(block_size) => FrameOptions {
+            level,
+            block_size,
+            block_checksum: block_checksum != 0,
+            content_checksum: content_checksum != 0,
+            content_size: content_size != 0,
+        },
+        Err(err) => return err.code(),
+    };
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+        .and_then(|(src, dest)| compress(src, dest, &options));
+    to_c_result(result)
+}
+
+/// Decompresses all LZ4 frames in `src`. Returns the number of bytes written to
+/// `dest` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_lz4frame_decompress(
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+        .and_then(|(src, dest)| decompress(src, dest));
+    to_c_result(result)
+}
+
+/// Reads the content size of the first frame in `src`. Returns 1 and writes the size
+/// when the frame declares it, 0 when it does not, or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_lz4frame_content_size(
+    src: *const libc::c_char,
+    src_length: usize,
+    size: *mut u64,
+) -> libc::c_int {
+    match unsafe { ffi_slice(src, src_length) }.and_then(content_size) {
+        Ok(Some(content_size)) => {
+            if let Some(size) = unsafe { size.as_mut() } {
+                *size = content_size;
+            }
+            1
+        }
+        Ok(None) => 0,
+        Err(err) => err.code(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_data::delimited_text;
+    use super::*;
+
+    #[test]
+    fn could_roundtrip_frame_options() {
+        let data = delimited_text();
+        for block_size in [BlockSize::Max64KB, BlockSize::Max4MB].iter() {
+            for flags in 0..8 {
+                let options = FrameOptions {
+                    block_size: *block_size,
+                    block_checksum: flags & 1 != 0,
+                    content_checksum: flags & 2 != 0,
+                    content_size: flags & 4 != 0,
+                    ..FrameOptions::default()
+                };
+                let frame = compress_to_vec(&data, &options).unwrap();
+                assert!(frame.len() < data.len());
+                assert_eq!(
+                    if options.content_size {
+                        Some(data.len() as u64)
+                    } else {
+                        None
+                    },
+                    content_size(&frame).unwrap()
+                );
+                assert_eq!(data, decompress_to_vec(&frame, data.len()).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_reference_empty_frame() {
+        // `lz4 -c -B4 < /dev/null | xxd`
+        let frame = compress_to_vec(b"", &FrameOptions::default()).unwrap();
+        assert_eq!(
+            vec![0x04, 0x22, 0x4D, 0x18, 0x64, 0x40, 0xA7, 0, 0, 0, 0, 0x05, 0x5D, 0xCC, 0x02],
+            frame
+        );
+        assert_eq!(Ok(0), decompress(&frame, &mut []));
+    }
+
+    #[test]
+    fn stores_incompressible_blocks() {
+        let data: Vec<u8> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        let options = FrameOptions::default();
+        let frame = compress_to_vec(&data, &options).unwrap();
+        assert!(frame.len() <= compress_bound(data.len(), &options).unwrap());
+        assert_eq!(data, decompress_to_vec(&frame, data.len()).unwrap());
+    }
+
+    #[test]
+    fn could_read_concatenated_and_skippable_frames() {
+        let options = FrameOptions::default();
+        let mut stream = compress_to_vec(b"hello ", &options).unwrap();
+        stream.extend_from_slice(&(SKIPPABLE_MAGIC + 3).to_le_bytes());
+        stream.extend_from_slice(&3u32.to_le_bytes());
+        stream.extend_from_slice(b"abc");
+        stream.extend_from_slice(&compress_to_vec(b"world", &options).unwrap());
+
+        assert_eq!(
+            b"hello world".to_vec(),
+            decompress_to_vec(&stream, 64).unwrap()
+        );
+        let mut read = Vec::new();
+        Lz4FrameReader::new(&stream[..])
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(b"hello world".to_vec(), read);
+    }
+
+    #[test]
+    fn decompress_reports_errors() {
+        let data = delimited_text();
+        let options = FrameOptions {
+            block_checksum: true,
+            ..FrameOptions::default()
+        };
+        let frame = compress_to_vec(&data, &options).unwrap();
+        let mut small = vec![0u8; data.len() - 1];
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            decompress(&frame, &mut small)
+        );
+        let mut corrupted = frame.clone();
+        corrupted[40] ^= 0xFF;
+        let mut dest = vec![0u8; data.len()];
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&corrupted, &mut dest)
+        );
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&frame[..frame.len() - 1], &mut dest)
+        );
+    }
+
+    #[test]
+    fn could_stream_frames() {
+        let data = delimited_text();
+        let options = FrameOptions {
+            block_checksum: true,
+            ..FrameOptions::default()
+        };
+        let mut writer = Lz4FrameWriter::new(Vec::new(), options).unwrap();
+        for chunk in data.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let frame = writer.finish().unwrap();
+        assert_eq!(data, decompress_to_vec(&frame, data.len()).unwrap());
+
+        let mut reader = Lz4FrameReader::new(&frame[..]);
+        let mut read = Vec::new();
+        let mut chunk = [0u8; 777];
+        loop {
+            let n = reader.read(&mut chunk).unwrap();
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&chunk[..n]);
+        }
+        assert_eq!(data, read);
+
+        let mut truncated = Lz4FrameReader::new(&frame[..frame.len() - 3]);
+        let err = truncated.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn writer_checks_content_size() {
+        let options = FrameOptions {
+            content_size: true,
+            ..FrameOptions::default()
+        };
+        assert!(Lz4FrameWriter::new(Vec::new(), options).is_err());
+        let mut writer = Lz4FrameWriter::with_content_size(Vec::new(), options, 5).unwrap();
+        writer.write_all(b"hello").unwrap();
+        let frame = writer.finish().unwrap();
+        assert_eq!(Ok(Some(5)), content_size(&frame));
+        assert_eq!(b"hello".to_vec(), decompress_to_vec(&frame, 5).unwrap());
+
+        let mut writer = Lz4FrameWriter::with_content_size(Vec::new(), options, 5).unwrap();
+        writer.write_all(b"hell").unwrap();
+        assert!(writer.finish().is_err());
+    }
+
+    #[test]
+    fn could_roundtrip_through_c_api() {
+        let data = delimited_text();
+        let bound = spreads_lz4frame_compress_bound(data.len(), 5);
+        assert!(bound > 0);
+        let mut frame = vec![0u8; bound as usize];
+        let len = spreads_lz4frame_compress(
+            data.as_ptr() as *const libc::c_char,
+            data.len(),
+            frame.as_mut_ptr() as *mut libc::c_char,
+            frame.len(),
+            5,
+            5,
+            1,
+            1,
+            1,
+        );
+        assert!(len > 0);
+        let mut size = 0u64;
+        assert_eq!(
+            1,
+            spreads_lz4frame_content_size(
+                frame.as_ptr() as *const libc::c_char,
+                len as usize,
+                &mut size
+            )
+        );
+        assert_eq!(data.len() as u64, size);
+        let mut decompressed = vec![0u8; data.len()];
+        assert_eq!(
+            data.len() as libc::c_int,
+            spreads_lz4frame_decompress(
+                frame.as_ptr() as *const libc::c_char,
+                len as usize,
+                decompressed.as_mut_ptr() as *mut libc::c_char,
+                decompressed.len(),
+            )
+        );
+        assert_eq!(data, decompressed);
+        assert_eq!(
+            CompressionError::InvalidArgument.code() as isize,
+            spreads_lz4frame_compress_bound(data.len(), 3)
+        );
+    }
+}
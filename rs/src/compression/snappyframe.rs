@@ -0,0 +1,464 @@
+//! Snappy framing format on top of the raw Snappy codec vendored inside c-blosc, so
+//! that the output can be read by `snzip`, the Snappy stream classes of Java and
+//! Python and other framed Snappy readers. Streams are a stream identifier followed
+//! by chunks of at most 64 KB of uncompressed data, each with the masked CRC-32C of
+//! that data. Padding and reserved skippable chunks are skipped when reading, as
+//! are repeated stream identifiers of concatenated streams.
+
+use super::{ffi_buffers, ffi_slice, to_c_result, Codec, CompressionError, MAX_INPUT_LENGTH};
+use spreads_blosc_sys as ffi;
+
+const STREAM_IDENTIFIER: [u8; 10] = [0xFF, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y'];
+const CHUNK_COMPRESSED: u8 = 0x00;
+const CHUNK_UNCOMPRESSED: u8 = 0x01;
+const CHUNK_STREAM_IDENTIFIER: u8 = 0xFF;
+/// Chunk types from 0x02 to 0x7F are reserved and must not be skipped, the ones from
+/// 0x80 up to the 0xFE padding chunk are skippable.
+const FIRST_SKIPPABLE: u8 = 0x80;
+
+/// Maximum size of the uncompressed data in a chunk.
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+/// Chunk type, 3 bytes length and the checksum.
+const CHUNK_HEADER_SIZE: usize = 8;
+
+/// CRC-32C (Castagnoli) lookup table, reflected polynomial 0x82F63B78.
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        CRC32C_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// The framing format stores the CRC rotated and offset, so that checksums of data
+/// that embeds checksums do not degenerate.
+fn masked_crc32c(data: &[u8]) -> u32 {
+    crc32c(data).rotate_right(15).wrapping_add(0xA282_EAD8)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Writes the chunk type and the 3 bytes length of a chunk.
+fn write_chunk_header(dest: &mut [u8], chunk_type: u8, len: usize) {
+    dest[0] = chunk_type;
+    dest[1..4].copy_from_slice(&(len as u32).to_le_bytes()[..3]);
+}
+
+/// Worst-case size of a stream holding `len` bytes: incompressible chunks are stored
+/// as they are, so a chunk never grows by more than its header.
+pub fn compress_bound(len: usize) -> Result<usize, CompressionError> {
+    if len > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    let chunks = len.div_ceil(MAX_CHUNK_SIZE);
+    let bound = STREAM_IDENTIFIER.len() + len + chunks * CHUNK_HEADER_SIZE;
+    if bound > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    Ok(bound)
+}
+
+/// Compresses `src` into a stream and returns the number of bytes written. A chunk is
+/// stored uncompressed when Snappy saves less than 12.5% of it, like the reference
+/// framer does.
+pub fn compress(src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+    if src.len() > MAX_INPUT_LENGTH {
+        return Err(CompressionError::LengthOverflow);
+    }
+    let dest_len = dest.len().min(MAX_INPUT_LENGTH);
+    let dest = &mut dest[..dest_len];
+    if dest.len() < STREAM_IDENTIFIER.len() {
+        return Err(CompressionError::BufferTooSmall);
+    }
+    dest[..STREAM_IDENTIFIER.len()].copy_from_slice(&STREAM_IDENTIFIER);
+    let mut pos = STREAM_IDENTIFIER.len();
+    // Whether a chunk is stored compressed is only known after compressing it, so
+    // compress into a scratch chunk.
+    let mut scratch = vec![0u8; Codec::Snappy.compress_bound(MAX_CHUNK_SIZE)?];
+    for chunk in src.chunks(MAX_CHUNK_SIZE) {
+        let compressed = Codec::Snappy.compress(chunk, &mut scratch, 0)?;
+        let (chunk_type, data) = if compressed < chunk.len() - chunk.len() / 8 {
+            (CHUNK_COMPRESSED, &scratch[..compressed])
+        } else {
+            (CHUNK_UNCOMPRESSED, chunk)
+        };
+        let end = pos + CHUNK_HEADER_SIZE + data.len();
+        if end > dest.len() {
+            return Err(CompressionError::BufferTooSmall);
+        }
+        write_chunk_header(&mut dest[pos..], chunk_type, 4 + data.len());
+        dest[pos + 4..pos + 8].copy_from_slice(&masked_crc32c(chunk).to_le_bytes());
+        dest[pos + CHUNK_HEADER_SIZE..end].copy_from_slice(data);
+        pos = end;
+    }
+    Ok(pos)
+}
+
+/// Compresses `src` into a new vector that is exactly as long as the stream.
+pub fn compress_to_vec(src: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let mut dest = vec![0u8; compress_bound(src.len())?];
+    let len = compress(src, &mut dest)?;
+    dest.truncate(len);
+    dest.shrink_to_fit();
+    Ok(dest)
+}
+
+/// A chunk of a stream: its type and the data after the chunk header.
+struct Chunk<'a> {
+    chunk_type: u8,
+    data: &'a [u8],
+}
+
+/// Iterates over the chunks of `src`, validating that the stream starts with a stream
+/// identifier and that no chunk is truncated.
+fn chunks(src: &[u8]) -> impl Iterator<Item = Result<Chunk<'_>, CompressionError>> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        if pos == src.len() {
+            return None;
+        }
+        let header = match src.get(pos..pos + 4) {
+            Some(header) => header,
+            None => return Some(Err(CompressionError::CorruptInput)),
+        };
+        let len = read_u32(header) as usize >> 8;
+        let chunk_type = header[0];
+        let data = match src.get(pos + 4..pos + 4 + len) {
+            Some(data) => data,
+            None => return Some(Err(CompressionError::CorruptInput)),
+        };
+        if pos == 0 && chunk_type != CHUNK_STREAM_IDENTIFIER {
+            return Some(Err(CompressionError::CorruptInput));
+        }
+        pos += 4 + len;
+        Some(Ok(Chunk { chunk_type, data }))
+    })
+}
+
+/// Decodes the data chunk at `chunk` into `out` and returns the decoded length.
+/// `Ok(None)` for chunks that carry no data.
+fn decompress_chunk(chunk: &Chunk<'_>, out: &mut [u8]) -> Result<Option<usize>, CompressionError> {
+    match chunk.chunk_type {
+        CHUNK_STREAM_IDENTIFIER => {
+            if chunk.data != &STREAM_IDENTIFIER[4..] {
+                return Err(CompressionError::CorruptInput);
+            }
+            return Ok(None);
+        }
+        CHUNK_COMPRESSED | CHUNK_UNCOMPRESSED => {}
+        chunk_type if chunk_type >= FIRST_SKIPPABLE => return Ok(None),
+        _ => return Err(CompressionError::CorruptInput),
+    }
+    if chunk.data.len() < 4 {
+        return Err(CompressionError::CorruptInput);
+    }
+    let (checksum, data) = chunk.data.split_at(4);
+    let capacity = out.len().min(MAX_CHUNK_SIZE);
+    let len = if chunk.chunk_type == CHUNK_UNCOMPRESSED {
+        if data.len() > MAX_CHUNK_SIZE {
+            return Err(CompressionError::CorruptInput);
+        }
+        if data.len() > capacity {
+            return Err(CompressionError::BufferTooSmall);
+        }
+        out[..data.len()].copy_from_slice(data);
+        data.len()
+    } else {
+        match Codec::Snappy.decompress(data, &mut out[..capacity]) {
+            // The chunk holds more than the framing format allows.
+            Err(CompressionError::BufferTooSmall) if capacity == MAX_CHUNK_SIZE => {
+                return Err(CompressionError::CorruptInput)
+            }
+            result => result?,
+        }
+    };
+    if masked_crc32c(&out[..len]) != read_u32(checksum) {
+        return Err(CompressionError::ChecksumMismatch);
+    }
+    Ok(Some(len))
+}
+
+/// Decompresses all chunks of `src` into `dest` and returns the number of bytes
+/// written. Fails with `ChecksumMismatch` when a chunk does not match its CRC.
+pub fn decompress(src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+    if src.is_empty() {
+        return Err(CompressionError::CorruptInput);
+    }
+    let dest_len = dest.len().min(MAX_INPUT_LENGTH);
+    let dest = &mut dest[..dest_len];
+    let mut written = 0;
+    for chunk in chunks(src) {
+        if let Some(len) = decompress_chunk(&chunk?, &mut dest[written..])? {
+            written += len;
+        }
+    }
+    Ok(written)
+}
+
+/// Decompressed size of the stream in `src`, read from the chunk headers without
+/// decoding the data. Each chunk is bounded by the chunk size limit and by what its
+/// compressed data can expand to, so the result stays proportional to `src`.
+pub fn decompressed_len(src: &[u8]) -> Result<usize, CompressionError> {
+    if src.is_empty() {
+        return Err(CompressionError::CorruptInput);
+    }
+    let mut total = 0usize;
+    for chunk in chunks(src) {
+        let chunk = chunk?;
+        let data = match chunk.chunk_type {
+            CHUNK_COMPRESSED | CHUNK_UNCOMPRESSED if chunk.data.len() >= 4 => &chunk.data[4..],
+            CHUNK_STREAM_IDENTIFIER => continue,
+            chunk_type if chunk_type >= FIRST_SKIPPABLE => continue,
+            _ => return Err(CompressionError::CorruptInput),
+        };
+        let len = if chunk.chunk_type == CHUNK_UNCOMPRESSED {
+            data.len()
+        } else {
+            let mut len = 0;
+            let status = unsafe {
+                ffi::snappy_uncompressed_length(
+                    data.as_ptr() as *const libc::c_char,
+                    data.len(),
+                    &mut len,
+                )
+            };
+            if status != ffi::SNAPPY_OK || len > Codec::Snappy.max_decompressed_len(data.len()) {
+                return Err(CompressionError::CorruptInput);
+            }
+            len
+        };
+        if len > MAX_CHUNK_SIZE {
+            return Err(CompressionError::CorruptInput);
+        }
+        total += len;
+        if total > MAX_INPUT_LENGTH {
+            return Err(CompressionError::LengthOverflow);
+        }
+    }
+    Ok(total)
+}
+
+/// Decompresses `src` into a new vector sized from the chunk headers.
+pub fn decompress_to_vec(src: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let mut dest = vec![0u8; decompressed_len(src)?];
+    let len = decompress(src, &mut dest)?;
+    dest.truncate(len);
+    Ok(dest)
+}
+
+/// Returns the worst-case stream size of `src_length` bytes or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_snappyframe_compress_bound(src_length: usize) -> isize {
+    match compress_bound(src_length) {
+        Ok(bound) => bound as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+/// Compresses `src` into a framed Snappy stream. Returns the stream length or a
+/// negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_snappyframe_compress(
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+        .and_then(|(src, dest)| compress(src, dest));
+    to_c_result(result)
+}
+
+/// Decompresses a framed Snappy stream. Returns the number of bytes written to
+/// `dest` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_snappyframe_decompress(
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    let result = unsafe { ffi_buffers(src, src_length, dest, dest_length) }
+        .and_then(|(src, dest)| decompress(src, dest));
+    to_c_result(result)
+}
+
+/// Returns the decompressed size of a framed Snappy stream or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_snappyframe_decompressed_size(
+    src: *const libc::c_char,
+    src_length: usize,
+) -> isize {
+    match unsafe { ffi_slice(src, src_length) }.and_then(decompressed_len) {
+        Ok(len) => len as isize,
+        Err(err) => err.code() as isize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_data::{delimited_text, noise};
+    use super::*;
+
+    #[test]
+    fn crc32c_matches_reference_values() {
+        assert_eq!(0, crc32c(b""));
+        assert_eq!(0xE306_9283, crc32c(b"123456789"));
+        assert_eq!(0x8A91_36AA, crc32c(&[0u8; 32]));
+        assert_eq!(0x62A8_AB43, crc32c(&[0xFFu8; 32]));
+    }
+
+    #[test]
+    fn could_roundtrip_streams() {
+        for data in [Vec::new(), b"a".to_vec(), delimited_text(), noise(200_000)].iter() {
+            let stream = compress_to_vec(data).unwrap();
+            assert_eq!(&STREAM_IDENTIFIER[..], &stream[..STREAM_IDENTIFIER.len()]);
+            assert!(stream.len() <= compress_bound(data.len()).unwrap());
+            assert_eq!(data.len(), decompressed_len(&stream).unwrap());
+            assert_eq!(data, &decompress_to_vec(&stream).unwrap());
+        }
+        let data = delimited_text();
+        assert!(compress_to_vec(&data).unwrap().len() * 4 < data.len());
+    }
+
+    #[test]
+    fn stores_incompressible_chunks() {
+        let data = noise(1000);
+        let stream = compress_to_vec(&data).unwrap();
+        assert_eq!(
+            STREAM_IDENTIFIER.len() + CHUNK_HEADER_SIZE + 1000,
+            stream.len()
+        );
+        assert_eq!(CHUNK_UNCOMPRESSED, stream[STREAM_IDENTIFIER.len()]);
+        assert_eq!(
+            &data[..],
+            &stream[STREAM_IDENTIFIER.len() + CHUNK_HEADER_SIZE..]
+        );
+    }
+
+    #[test]
+    fn skips_padding_and_concatenated_streams() {
+        let first = compress_to_vec(b"hello ").unwrap();
+        let second = compress_to_vec(b"world").unwrap();
+        let mut stream = first;
+        // A padding chunk and a skippable chunk.
+        stream.extend_from_slice(&[0xFE, 3, 0, 0, 0, 0, 0]);
+        stream.extend_from_slice(&[FIRST_SKIPPABLE, 1, 0, 0, 42]);
+        stream.extend_from_slice(&second);
+        assert_eq!(b"hello world".to_vec(), decompress_to_vec(&stream).unwrap());
+        let mut reserved = compress_to_vec(b"hello").unwrap();
+        reserved.extend_from_slice(&[0x02, 0, 0, 0]);
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&reserved, &mut [0u8; 16])
+        );
+    }
+
+    #[test]
+    fn decompress_reports_errors() {
+        let data = delimited_text();
+        let stream = compress_to_vec(&data).unwrap();
+        let mut dest = vec![0u8; data.len()];
+        let mut small = vec![0u8; data.len() - 1];
+        assert_eq!(
+            Err(CompressionError::BufferTooSmall),
+            decompress(&stream, &mut small)
+        );
+        let mut corrupted = stream.clone();
+        // The checksum of the first chunk.
+        corrupted[STREAM_IDENTIFIER.len() + 4] ^= 0xFF;
+        assert_eq!(
+            Err(CompressionError::ChecksumMismatch),
+            decompress(&corrupted, &mut dest)
+        );
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&stream[..stream.len() - 1], &mut dest)
+        );
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&stream[STREAM_IDENTIFIER.len()..], &mut dest)
+        );
+        assert_eq!(
+            Err(CompressionError::CorruptInput),
+            decompress(&[], &mut dest)
+        );
+    }
+
+    #[test]
+    fn rejects_chunks_declaring_oversized_data() {
+        // Compressed chunks that only hold the varint length of their data.
+        for declared in [[0xE0, 0xD4, 0x03], [0x81, 0x80, 0x04]].iter() {
+            let mut stream = STREAM_IDENTIFIER.to_vec();
+            stream.extend_from_slice(&[CHUNK_COMPRESSED, 7, 0, 0, 0, 0, 0, 0]);
+            stream.extend_from_slice(declared);
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                decompressed_len(&stream)
+            );
+            assert_eq!(
+                Err(CompressionError::CorruptInput),
+                decompress_to_vec(&stream)
+            );
+        }
+    }
+
+    #[test]
+    fn could_roundtrip_through_c_api() {
+        let data = delimited_text();
+        let bound = spreads_snappyframe_compress_bound(data.len());
+        assert!(bound > 0);
+        let mut stream = vec![0u8; bound as usize];
+        let len = spreads_snappyframe_compress(
+            data.as_ptr() as *const libc::c_char,
+            data.len(),
+            stream.as_mut_ptr() as *mut libc::c_char,
+            stream.len(),
+        );
+        assert!(len > 0);
+        assert_eq!(
+            data.len() as isize,
+            spreads_snappyframe_decompressed_size(
+                stream.as_ptr() as *const libc::c_char,
+                len as usize
+            )
+        );
+        let mut decompressed = vec![0u8; data.len()];
+        assert_eq!(
+            data.len() as libc::c_int,
+            spreads_snappyframe_decompress(
+                stream.as_ptr() as *const libc::c_char,
+                len as usize,
+                decompressed.as_mut_ptr() as *mut libc::c_char,
+                decompressed.len(),
+            )
+        );
+        assert_eq!(data, decompressed);
+        assert_eq!(
+            CompressionError::LengthOverflow.code() as isize,
+            spreads_snappyframe_compress_bound(usize::MAX)
+        );
+    }
+}
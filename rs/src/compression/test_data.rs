@@ -0,0 +1,29 @@
+//! Inputs shared by the codec tests.
+
+/// xorshift64, enough randomness for tests without extra dependencies. The seed must
+/// not be zero.
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// About 2 MB of short delimited numbers, which compress well and span many blocks
+/// of the framed formats.
+#[cfg(any(feature = "lz4", feature = "snappy"))]
+pub fn delimited_text() -> Vec<u8> {
+    (0..300_000u32)
+        .flat_map(|i| format!("{},{};", i % 1000, i / 7).into_bytes())
+        .collect()
+}
+
+/// Random bytes that do not compress, to exercise the worst case of a codec.
+pub fn noise(len: usize) -> Vec<u8> {
+    let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+    (0..len).map(|_| rng.next() as u8).collect()
+}
@@ -0,0 +1,138 @@
+//! XXH32, the checksum of the LZ4 frame format.
+
+const PRIME1: u32 = 2_654_435_761;
+const PRIME2: u32 = 2_246_822_519;
+const PRIME3: u32 = 3_266_489_917;
+const PRIME4: u32 = 668_265_263;
+const PRIME5: u32 = 374_761_393;
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn round(acc: u32, lane: u32) -> u32 {
+    acc.wrapping_add(lane.wrapping_mul(PRIME2))
+        .rotate_left(13)
+        .wrapping_mul(PRIME1)
+}
+
+/// Streaming XXH32 state.
+#[derive(Clone)]
+pub(crate) struct Xxh32 {
+    seed: u32,
+    acc: [u32; 4],
+    total: u64,
+    buffer: [u8; 16],
+    buffered: usize,
+}
+
+impl Xxh32 {
+    pub fn new(seed: u32) -> Xxh32 {
+        Xxh32 {
+            seed,
+            acc: [
+                seed.wrapping_add(PRIME1).wrapping_add(PRIME2),
+                seed.wrapping_add(PRIME2),
+                seed,
+                seed.wrapping_sub(PRIME1),
+            ],
+            total: 0,
+            buffer: [0u8; 16],
+            buffered: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total += data.len() as u64;
+        if self.buffered > 0 {
+            let take = data.len().min(16 - self.buffered);
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+            if self.buffered < 16 {
+                return;
+            }
+            let stripe = self.buffer;
+            self.stripe(&stripe);
+            self.buffered = 0;
+        }
+        let mut stripes = data.chunks_exact(16);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    fn stripe(&mut self, stripe: &[u8]) {
+        for (lane, acc) in self.acc.iter_mut().enumerate() {
+            *acc = round(*acc, read_u32(&stripe[lane * 4..]));
+        }
+    }
+
+    pub fn digest(&self) -> u32 {
+        let mut hash = if self.total >= 16 {
+            self.acc[0]
+                .rotate_left(1)
+                .wrapping_add(self.acc[1].rotate_left(7))
+                .wrapping_add(self.acc[2].rotate_left(12))
+                .wrapping_add(self.acc[3].rotate_left(18))
+        } else {
+            self.seed.wrapping_add(PRIME5)
+        };
+        hash = hash.wrapping_add(self.total as u32);
+        let mut words = self.buffer[..self.buffered].chunks_exact(4);
+        for word in &mut words {
+            hash = hash
+                .wrapping_add(read_u32(word).wrapping_mul(PRIME3))
+                .rotate_left(17)
+                .wrapping_mul(PRIME4);
+        }
+        for byte in words.remainder() {
+            hash = hash
+                .wrapping_add(u32::from(*byte).wrapping_mul(PRIME5))
+                .rotate_left(11)
+                .wrapping_mul(PRIME1);
+        }
+        hash ^= hash >> 15;
+        hash = hash.wrapping_mul(PRIME2);
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(PRIME3);
+        hash ^ (hash >> 16)
+    }
+}
+
+/// One-shot XXH32 of `data`.
+#[cfg(any(feature = "lz4", test))]
+pub(crate) fn xxh32(data: &[u8], seed: u32) -> u32 {
+    let mut state = Xxh32::new(seed);
+    state.update(data);
+    state.digest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_reference_values() {
+        assert_eq!(0x02CC_5D05, xxh32(b"", 0));
+        assert_eq!(0x550D_7456, xxh32(b"a", 0));
+        assert_eq!(0x32D1_53FF, xxh32(b"abc", 0));
+        assert_eq!(
+            0xE229_3B2F,
+            xxh32(b"Nobody inspects the spammish repetition", 0)
+        );
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let mut state = Xxh32::new(7);
+        for chunk in data.chunks(13) {
+            state.update(chunk);
+        }
+        assert_eq!(xxh32(&data, 7), state.digest());
+    }
+}
@@ -0,0 +1,218 @@
+//! Thin streaming wrapper over the zlib sources compiled into c-blosc.
+
+use super::CompressionError;
+use spreads_blosc_sys as ffi;
+
+/// `windowBits` for the zlib format (RFC 1950).
+pub(crate) const WINDOW_BITS_ZLIB: i32 = 15;
+/// `windowBits` for raw deflate streams (RFC 1951).
+pub(crate) const WINDOW_BITS_DEFLATE: i32 = -15;
+/// `windowBits` for the gzip format (RFC 1952).
+pub(crate) const WINDOW_BITS_GZIP: i32 = 31;
+
+pub(crate) struct Inflater {
+    // zlib keeps a back-pointer to the stream, so it must not move after init.
+    stream: Box<ffi::z_stream>,
+}
+
+impl Inflater {
+    pub(crate) fn new(window_bits: i32) -> Result<Inflater, CompressionError> {
+        let mut stream: Box<ffi::z_stream> = Box::new(unsafe { std::mem::zeroed() });
+        let status = unsafe {
+            ffi::inflateInit2_(
+                &mut *stream,
+                window_bits,
+                ffi::zlibVersion(),
+                std::mem::size_of::<ffi::z_stream>() as libc::c_int,
+            )
+        };
+        if status != ffi::Z_OK as libc::c_int {
+            return Err(CompressionError::Internal);
+        }
+        Ok(Inflater { stream })
+    }
+
+    /// Prepares the stream for the next gzip member or zlib stream.
+    pub(crate) fn reset(&mut self) -> Result<(), CompressionError> {
+        check_status(unsafe { ffi::inflateReset(&mut *self.stream) }).map(|_| ())
+    }
+
+    /// Stores the gzip header of the next member into `head`, which zlib writes to
+    /// while inflating.
+    ///
+    /// # Safety
+    ///
+    /// `head` and the buffers it points to must outlive the next member or the
+    /// next `reset`, whichever comes first.
+    pub(crate) unsafe fn capture_header(
+        &mut self,
+        head: *mut ffi::gz_header,
+    ) -> Result<(), CompressionError> {
+        check_status(ffi::inflateGetHeader(&mut *self.stream, head)).map(|_| ())
+    }
+
+    /// Inflates as much of `input` into `output` as possible. Returns the zlib status
+    /// together with the number of consumed and produced bytes.
+    pub(crate) fn inflate(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> (libc::c_int, usize, usize) {
+        let avail_in = input.len().min(libc::c_uint::MAX as usize);
+        let avail_out = output.len().min(libc::c_uint::MAX as usize);
+        self.stream.next_in = input.as_ptr();
+        self.stream.avail_in = avail_in as libc::c_uint;
+        self.stream.next_out = output.as_mut_ptr();
+        self.stream.avail_out = avail_out as libc::c_uint;
+        let status = unsafe { ffi::inflate(&mut *self.stream, ffi::Z_NO_FLUSH as libc::c_int) };
+        let consumed = avail_in - self.stream.avail_in as usize;
+        let produced = avail_out - self.stream.avail_out as usize;
+        (status, consumed, produced)
+    }
+}
+
+impl Drop for Inflater {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::inflateEnd(&mut *self.stream);
+        }
+    }
+}
+
+/// Maps a zlib status to an error; `Ok(true)` means the end of the stream was reached.
+pub(crate) fn check_status(status: libc::c_int) -> Result<bool, CompressionError> {
+    match status {
+        s if s == ffi::Z_STREAM_END as libc::c_int => Ok(true),
+        s if s == ffi::Z_OK as libc::c_int || s == ffi::Z_BUF_ERROR => Ok(false),
+        s if s == ffi::Z_MEM_ERROR => Err(CompressionError::Internal),
+        _ => Err(CompressionError::CorruptInput),
+    }
+}
+
+/// Decodes a whole stream into `output`, telling a short output buffer apart from
+/// malformed or truncated input. Concatenated gzip members are decoded one after
+/// another, as `gzip -d` does.
+pub(crate) fn inflate_exact(
+    input: &[u8],
+    output: &mut [u8],
+    window_bits: i32,
+) -> Result<usize, CompressionError> {
+    let mut inflater = Inflater::new(window_bits)?;
+    inflate_members(
+        &mut inflater,
+        input,
+        output,
+        window_bits == WINDOW_BITS_GZIP,
+    )
+}
+
+/// `inflate_exact` with a prepared inflater.
+pub(crate) fn inflate_members(
+    inflater: &mut Inflater,
+    input: &[u8],
+    output: &mut [u8],
+    multi_member: bool,
+) -> Result<usize, CompressionError> {
+    let mut consumed_total = 0;
+    let mut produced_total = 0;
+    loop {
+        let (status, consumed, produced) =
+            inflater.inflate(&input[consumed_total..], &mut output[produced_total..]);
+        consumed_total += consumed;
+        produced_total += produced;
+        if check_status(status)? {
+            if !multi_member || consumed_total == input.len() {
+                return Ok(produced_total);
+            }
+            inflater.reset()?;
+            continue;
+        }
+        if produced_total == output.len() {
+            return Err(CompressionError::BufferTooSmall);
+        }
+        if consumed == 0 && produced == 0 {
+            // No progress with output space left: the input is truncated.
+            return Err(CompressionError::CorruptInput);
+        }
+    }
+}
+
+pub(crate) struct Deflater {
+    // zlib keeps a back-pointer to the stream, so it must not move after init.
+    stream: Box<ffi::z_stream>,
+}
+
+impl Deflater {
+    /// `level` is the zlib level from 0 to 9.
+    pub(crate) fn new(level: i32, window_bits: i32) -> Result<Deflater, CompressionError> {
+        let mut stream: Box<ffi::z_stream> = Box::new(unsafe { std::mem::zeroed() });
+        let status = unsafe {
+            ffi::deflateInit2_(
+                &mut *stream,
+                level,
+                ffi::Z_DEFLATED as libc::c_int,
+                window_bits,
+                // The default memLevel of deflateInit.
+                8,
+                ffi::Z_DEFAULT_STRATEGY as libc::c_int,
+                ffi::zlibVersion(),
+                std::mem::size_of::<ffi::z_stream>() as libc::c_int,
+            )
+        };
+        match status {
+            s if s == ffi::Z_OK as libc::c_int => Ok(Deflater { stream }),
+            s if s == ffi::Z_STREAM_ERROR => Err(CompressionError::InvalidLevel),
+            _ => Err(CompressionError::Internal),
+        }
+    }
+
+    /// Sets the gzip header written by the next `finish`.
+    ///
+    /// # Safety
+    ///
+    /// `head` and the buffers it points to must outlive the next `finish` call.
+    pub(crate) unsafe fn set_header(
+        &mut self,
+        head: *mut ffi::gz_header,
+    ) -> Result<(), CompressionError> {
+        match ffi::deflateSetHeader(&mut *self.stream, head) {
+            s if s == ffi::Z_OK as libc::c_int => Ok(()),
+            _ => Err(CompressionError::Internal),
+        }
+    }
+
+    /// Compresses all of `input` into `output` as a complete stream.
+    pub(crate) fn finish(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, CompressionError> {
+        let avail_out = output.len().min(libc::c_uint::MAX as usize);
+        if input.len() > libc::c_uint::MAX as usize {
+            return Err(CompressionError::LengthOverflow);
+        }
+        self.stream.next_in = input.as_ptr();
+        self.stream.avail_in = input.len() as libc::c_uint;
+        self.stream.next_out = output.as_mut_ptr();
+        self.stream.avail_out = avail_out as libc::c_uint;
+        let status = unsafe { ffi::deflate(&mut *self.stream, ffi::Z_FINISH as libc::c_int) };
+        match status {
+            s if s == ffi::Z_STREAM_END as libc::c_int => {
+                Ok(avail_out - self.stream.avail_out as usize)
+            }
+            // The output is full before the stream could be completed.
+            s if s == ffi::Z_OK as libc::c_int || s == ffi::Z_BUF_ERROR => {
+                Err(CompressionError::BufferTooSmall)
+            }
+            _ => Err(CompressionError::Internal),
+        }
+    }
+}
+
+impl Drop for Deflater {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::deflateEnd(&mut *self.stream);
+        }
+    }
+}
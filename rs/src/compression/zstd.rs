@@ -0,0 +1,954 @@
+//! Streaming Zstandard on top of the zstd sources vendored inside c-blosc. Levels here
+//! are native zstd levels from 1 to `ZSTD_maxCLevel()`, not the Blosc 0 to 9 scale.
+
+use super::{ffi_buffers, ffi_slice, ffi_slice_mut, CompressionError};
+use spreads_blosc_sys as ffi;
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::ptr::NonNull;
+
+/// Maps a zstd `size_t` result to a stable error.
+pub(crate) fn check(code: usize) -> Result<usize, CompressionError> {
+    if unsafe { ffi::ZSTD_isError(code) } == 0 {
+        return Ok(code);
+    }
+    let err = match unsafe { ffi::ZSTD_getErrorCode(code) } {
+        ffi::ZSTD_error_dstSize_tooSmall => CompressionError::BufferTooSmall,
+        ffi::ZSTD_error_prefix_unknown
+        | ffi::ZSTD_error_version_unsupported
+        | ffi::ZSTD_error_frameParameter_unsupported
+        | ffi::ZSTD_error_frameParameter_windowTooLarge
+        | ffi::ZSTD_error_corruption_detected
+        | ffi::ZSTD_error_checksum_wrong
+        | ffi::ZSTD_error_srcSize_wrong
+        | ffi::ZSTD_error_dictionary_corrupted
+        | ffi::ZSTD_error_dictionary_wrong => CompressionError::CorruptInput,
+        ffi::ZSTD_error_parameter_unsupported
+        | ffi::ZSTD_error_parameter_outOfBound
+        | ffi::ZSTD_error_dictionaryCreation_failed => CompressionError::InvalidArgument,
+        _ => CompressionError::Internal,
+    };
+    Err(err)
+}
+
+pub(crate) fn check_level(level: i32) -> Result<(), CompressionError> {
+    if level < 1 || level > unsafe { ffi::ZSTD_maxCLevel() } {
+        return Err(CompressionError::InvalidLevel);
+    }
+    Ok(())
+}
+
+fn in_buffer(input: &[u8]) -> ffi::ZSTD_inBuffer {
+    ffi::ZSTD_inBuffer {
+        src: input.as_ptr() as *const libc::c_void,
+        size: input.len(),
+        pos: 0,
+    }
+}
+
+fn out_buffer(output: &mut [u8]) -> ffi::ZSTD_outBuffer {
+    ffi::ZSTD_outBuffer {
+        dst: output.as_mut_ptr() as *mut libc::c_void,
+        size: output.len(),
+        pos: 0,
+    }
+}
+
+/// Zstd compression stream producing a single frame per `end` call.
+pub struct ZstdCStream {
+    ptr: NonNull<ffi::ZSTD_CStream>,
+}
+
+// A zstd context may be used from any thread, just not concurrently.
+unsafe impl Send for ZstdCStream {}
+
+impl ZstdCStream {
+    pub fn new(level: i32) -> Result<ZstdCStream, CompressionError> {
+        check_level(level)?;
+        let ptr =
+            NonNull::new(unsafe { ffi::ZSTD_createCStream() }).ok_or(CompressionError::Internal)?;
+        let stream = ZstdCStream { ptr };
+        check(unsafe { ffi::ZSTD_initCStream(stream.ptr.as_ptr(), level) })?;
+        Ok(stream)
+    }
+
+    /// Output buffer size that can always hold at least one compressed block.
+    pub fn recommended_output_size() -> usize {
+        unsafe { ffi::ZSTD_CStreamOutSize() }
+    }
+
+    /// Compresses as much of `input` as possible into `output`. Returns the number of
+    /// consumed and produced bytes.
+    pub fn compress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), CompressionError> {
+        let mut input = in_buffer(input);
+        let mut output = out_buffer(output);
+        check(unsafe { ffi::ZSTD_compressStream(self.ptr.as_ptr(), &mut output, &mut input) })?;
+        Ok((input.pos, output.pos))
+    }
+
+    /// Flushes buffered data into `output`. Returns the number of produced bytes and
+    /// the number of bytes still to be flushed, call again while it is not zero.
+    pub fn flush(&mut self, output: &mut [u8]) -> Result<(usize, usize), CompressionError> {
+        let mut output = out_buffer(output);
+        let remaining = check(unsafe { ffi::ZSTD_flushStream(self.ptr.as_ptr(), &mut output) })?;
+        Ok((output.pos, remaining))
+    }
+
+    /// Flushes buffered data and writes the frame epilogue, with the same return value
+    /// as `flush`. Once it reports nothing remaining, the stream starts a new frame.
+    pub fn end(&mut self, output: &mut [u8]) -> Result<(usize, usize), CompressionError> {
+        let mut output = out_buffer(output);
+        let remaining = check(unsafe { ffi::ZSTD_endStream(self.ptr.as_ptr(), &mut output) })?;
+        Ok((output.pos, remaining))
+    }
+}
+
+impl Drop for ZstdCStream {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::ZSTD_freeCStream(self.ptr.as_ptr());
+        }
+    }
+}
+
+/// Zstd decompression stream, concatenated frames are decoded one after another.
+pub struct ZstdDStream {
+    ptr: NonNull<ffi::ZSTD_DStream>,
+}
+
+unsafe impl Send for ZstdDStream {}
+
+impl ZstdDStream {
+    pub fn new() -> Result<ZstdDStream, CompressionError> {
+        let ptr =
+            NonNull::new(unsafe { ffi::ZSTD_createDStream() }).ok_or(CompressionError::Internal)?;
+        let stream = ZstdDStream { ptr };
+        check(unsafe { ffi::ZSTD_initDStream(stream.ptr.as_ptr()) })?;
+        Ok(stream)
+    }
+
+    /// Input buffer size that matches the internal block size.
+    pub fn recommended_input_size() -> usize {
+        unsafe { ffi::ZSTD_DStreamInSize() }
+    }
+
+    /// Decompresses as much of `input` as possible into `output`. Returns the number of
+    /// consumed and produced bytes and a hint for the next input size, which is zero
+    /// once the current frame is complete.
+    pub fn decompress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize, usize), CompressionError> {
+        let mut input = in_buffer(input);
+        let mut output = out_buffer(output);
+        let hint = check(unsafe {
+            ffi::ZSTD_decompressStream(self.ptr.as_ptr(), &mut output, &mut input)
+        })?;
+        Ok((input.pos, output.pos, hint))
+    }
+}
+
+impl Drop for ZstdDStream {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::ZSTD_freeDStream(self.ptr.as_ptr());
+        }
+    }
+}
+
+/// Compresses everything written to it as a zstd frame into the inner writer. Call
+/// `finish` to complete the frame, dropping the encoder completes it ignoring errors.
+pub struct ZstdEncoder<W: Write> {
+    stream: ZstdCStream,
+    writer: Option<W>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> ZstdEncoder<W> {
+    pub fn new(writer: W, level: i32) -> io::Result<ZstdEncoder<W>> {
+        Ok(ZstdEncoder {
+            stream: ZstdCStream::new(level)?,
+            writer: Some(writer),
+            buffer: vec![0u8; ZstdCStream::recommended_output_size()],
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer
+            .as_ref()
+            .expect("writer is only taken by finish")
+    }
+
+    /// Completes the frame and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.end_frame()?;
+        Ok(self.writer.take().expect("writer is only taken by finish"))
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        let writer = self
+            .writer
+            .as_mut()
+            .expect("writer is only taken by finish");
+        loop {
+            let (produced, remaining) = self.stream.end(&mut self.buffer)?;
+            writer.write_all(&self.buffer[..produced])?;
+            if remaining == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for ZstdEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let writer = self
+            .writer
+            .as_mut()
+            .expect("writer is only taken by finish");
+        let mut consumed = 0;
+        while consumed < buf.len() {
+            let (chunk_consumed, produced) = self
+                .stream
+                .compress_chunk(&buf[consumed..], &mut self.buffer)?;
+            writer.write_all(&self.buffer[..produced])?;
+            consumed += chunk_consumed;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let writer = self
+            .writer
+            .as_mut()
+            .expect("writer is only taken by finish");
+        loop {
+            let (produced, remaining) = self.stream.flush(&mut self.buffer)?;
+            writer.write_all(&self.buffer[..produced])?;
+            if remaining == 0 {
+                break;
+            }
+        }
+        writer.flush()
+    }
+}
+
+impl<W: Write> Drop for ZstdEncoder<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.end_frame();
+        }
+    }
+}
+
+/// Decompresses zstd frames read from the inner reader.
+pub struct ZstdDecoder<R: Read> {
+    stream: ZstdDStream,
+    reader: R,
+    buffer: Vec<u8>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+    in_frame: bool,
+}
+
+impl<R: Read> ZstdDecoder<R> {
+    pub fn new(reader: R) -> io::Result<ZstdDecoder<R>> {
+        Ok(ZstdDecoder {
+            stream: ZstdDStream::new()?,
+            reader,
+            buffer: vec![0u8; ZstdDStream::recommended_input_size()],
+            pos: 0,
+            len: 0,
+            eof: false,
+            in_frame: false,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for ZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.pos == self.len && !self.eof {
+                self.len = self.reader.read(&mut self.buffer)?;
+                self.pos = 0;
+                self.eof = self.len == 0;
+            }
+            // Between frames the stream expects a new frame header, so decoding the
+            // empty input at the end would report a truncated frame.
+            if self.eof && self.pos == self.len && !self.in_frame {
+                return Ok(0);
+            }
+            let (consumed, produced, hint) = self
+                .stream
+                .decompress_chunk(&self.buffer[self.pos..self.len], buf)?;
+            self.pos += consumed;
+            self.in_frame = hint != 0;
+            if produced > 0 {
+                return Ok(produced);
+            }
+            if self.eof && self.pos == self.len && self.in_frame {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "zstd stream is truncated",
+                ));
+            }
+        }
+    }
+}
+
+/// Trains a dictionary of at most `capacity` bytes from `samples`. Training needs a
+/// representative set of samples, usually a few thousands, and fails with
+/// `InvalidArgument` when there is not enough data. 100 KB is a reasonable capacity.
+pub fn train_dictionary(samples: &[&[u8]], capacity: usize) -> Result<Vec<u8>, CompressionError> {
+    if samples.len() > libc::c_uint::MAX as usize {
+        return Err(CompressionError::LengthOverflow);
+    }
+    let sizes: Vec<usize> = samples.iter().map(|sample| sample.len()).collect();
+    let flat = samples.concat();
+    let mut dictionary = vec![0u8; capacity];
+    let size = train_dictionary_raw(&flat, &sizes, &mut dictionary)?;
+    dictionary.truncate(size);
+    Ok(dictionary)
+}
+
+fn train_dictionary_raw(
+    samples: &[u8],
+    sizes: &[usize],
+    dictionary: &mut [u8],
+) -> Result<usize, CompressionError> {
+    let size = unsafe {
+        ffi::ZDICT_trainFromBuffer(
+            dictionary.as_mut_ptr() as *mut libc::c_void,
+            dictionary.len(),
+            samples.as_ptr() as *const libc::c_void,
+            sizes.as_ptr(),
+            sizes.len() as libc::c_uint,
+        )
+    };
+    if unsafe { ffi::ZDICT_isError(size) } != 0 {
+        return match unsafe { ffi::ZSTD_getErrorCode(size) } {
+            ffi::ZSTD_error_memory_allocation => Err(CompressionError::Internal),
+            ffi::ZSTD_error_dstSize_tooSmall => Err(CompressionError::BufferTooSmall),
+            _ => Err(CompressionError::InvalidArgument),
+        };
+    }
+    Ok(size)
+}
+
+/// Per-thread compression and decompression contexts, reused by dictionary calls so
+/// that small messages do not pay for context allocation.
+struct Contexts {
+    cctx: NonNull<ffi::ZSTD_CCtx>,
+    dctx: NonNull<ffi::ZSTD_DCtx>,
+}
+
+impl Contexts {
+    fn new() -> Result<Contexts, CompressionError> {
+        let cctx =
+            NonNull::new(unsafe { ffi::ZSTD_createCCtx() }).ok_or(CompressionError::Internal)?;
+        match NonNull::new(unsafe { ffi::ZSTD_createDCtx() }) {
+            Some(dctx) => Ok(Contexts { cctx, dctx }),
+            None => {
+                unsafe { ffi::ZSTD_freeCCtx(cctx.as_ptr()) };
+                Err(CompressionError::Internal)
+            }
+        }
+    }
+}
+
+impl Drop for Contexts {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::ZSTD_freeCCtx(self.cctx.as_ptr());
+            ffi::ZSTD_freeDCtx(self.dctx.as_ptr());
+        }
+    }
+}
+
+thread_local! {
+    static CONTEXTS: RefCell<Option<Contexts>> = const { RefCell::new(None) };
+}
+
+fn with_contexts<T, F>(f: F) -> Result<T, CompressionError>
+where
+    F: FnOnce(&Contexts) -> Result<T, CompressionError>,
+{
+    CONTEXTS.with(|contexts| {
+        let mut contexts = contexts.borrow_mut();
+        if contexts.is_none() {
+            *contexts = Some(Contexts::new()?);
+        }
+        f(contexts.as_ref().expect("contexts are initialized above"))
+    })
+}
+
+/// A zstd dictionary digested for compression at a fixed level and for decompression.
+/// It is immutable and can be shared between threads, each thread uses its own
+/// contexts.
+pub struct ZstdDictionary {
+    bytes: Vec<u8>,
+    cdict: NonNull<ffi::ZSTD_CDict>,
+    ddict: NonNull<ffi::ZSTD_DDict>,
+}
+
+// Digested dictionaries are read-only after creation.
+unsafe impl Send for ZstdDictionary {}
+unsafe impl Sync for ZstdDictionary {}
+
+impl ZstdDictionary {
+    /// Loads a dictionary produced by `train_dictionary` (or any bytes, which are then
+    /// used as raw content) for compression with the native zstd `level`.
+    pub fn new(bytes: &[u8], level: i32) -> Result<ZstdDictionary, CompressionError> {
+        check_level(level)?;
+        let bytes = bytes.to_vec();
+        let src = bytes.as_ptr() as *const libc::c_void;
+        let cdict = NonNull::new(unsafe { ffi::ZSTD_createCDict(src, bytes.len(), level) })
+            .ok_or(CompressionError::Internal)?;
+        match NonNull::new(unsafe { ffi::ZSTD_createDDict(src, bytes.len()) }) {
+            Some(ddict) => Ok(ZstdDictionary {
+                bytes,
+                cdict,
+                ddict,
+            }),
+            None => {
+                unsafe { ffi::ZSTD_freeCDict(cdict.as_ptr()) };
+                Err(CompressionError::Internal)
+            }
+        }
+    }
+
+    /// Serialized dictionary, which can be stored and loaded again with `new`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Dictionary id stored in the frames, 0 for raw content dictionaries.
+    pub fn id(&self) -> u32 {
+        unsafe {
+            ffi::ZDICT_getDictID(self.bytes.as_ptr() as *const libc::c_void, self.bytes.len())
+        }
+    }
+
+    pub fn compress(&self, src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+        with_contexts(|contexts| {
+            check(unsafe {
+                ffi::ZSTD_compress_usingCDict(
+                    contexts.cctx.as_ptr(),
+                    dest.as_mut_ptr() as *mut libc::c_void,
+                    dest.len(),
+                    src.as_ptr() as *const libc::c_void,
+                    src.len(),
+                    self.cdict.as_ptr(),
+                )
+            })
+        })
+    }
+
+    pub fn compress_to_vec(&self, src: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let mut dest = vec![0u8; unsafe { ffi::ZSTD_compressBound(src.len()) }];
+        let len = self.compress(src, &mut dest)?;
+        dest.truncate(len);
+        Ok(dest)
+    }
+
+    pub fn decompress(&self, src: &[u8], dest: &mut [u8]) -> Result<usize, CompressionError> {
+        with_contexts(|contexts| {
+            check(unsafe {
+                ffi::ZSTD_decompress_usingDDict(
+                    contexts.dctx.as_ptr(),
+                    dest.as_mut_ptr() as *mut libc::c_void,
+                    dest.len(),
+                    src.as_ptr() as *const libc::c_void,
+                    src.len(),
+                    self.ddict.as_ptr(),
+                )
+            })
+        })
+    }
+}
+
+impl Drop for ZstdDictionary {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::ZSTD_freeCDict(self.cdict.as_ptr());
+            ffi::ZSTD_freeDDict(self.ddict.as_ptr());
+        }
+    }
+}
+
+/// Trains a dictionary from `nb_samples` samples stored back to back in `samples`, with
+/// their sizes in `sample_sizes`, and writes it to `dict`. Returns the dictionary size
+/// or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_train_dictionary(
+    samples: *const libc::c_char,
+    sample_sizes: *const usize,
+    nb_samples: libc::c_uint,
+    dict: *mut libc::c_char,
+    dict_capacity: usize,
+) -> libc::c_int {
+    if sample_sizes.is_null() && nb_samples > 0 {
+        return CompressionError::InvalidArgument.code();
+    }
+    let sizes = if nb_samples == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(sample_sizes, nb_samples as usize) }
+    };
+    let total = sizes
+        .iter()
+        .try_fold(0usize, |total, size| total.checked_add(*size));
+    let total = match total {
+        Some(total) => total,
+        None => return CompressionError::LengthOverflow.code(),
+    };
+    let buffers = unsafe { ffi_buffers(samples, total, dict, clamp_output(dict_capacity)) };
+    let result = buffers.and_then(|(samples, dict)| train_dictionary_raw(samples, sizes, dict));
+    super::to_c_result(result)
+}
+
+/// Loads a dictionary for compression with the native zstd `level` and decompression.
+/// The bytes are copied. Returns NULL for an invalid level. The handle is thread-safe
+/// and must be released with `spreads_zstd_dict_free`.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_dict_new(
+    dict: *const libc::c_char,
+    dict_length: usize,
+    level: libc::c_int,
+) -> *mut ZstdDictionary {
+    match unsafe { ffi_slice(dict, dict_length) }.and_then(|dict| ZstdDictionary::new(dict, level))
+    {
+        Ok(dict) => Box::into_raw(Box::new(dict)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn spreads_zstd_dict_free(dict: *mut ZstdDictionary) {
+    free_stream(dict)
+}
+
+/// Dictionary id, 0 for raw content dictionaries or a NULL handle.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_dict_id(dict: *const ZstdDictionary) -> libc::c_uint {
+    match unsafe { dict.as_ref() } {
+        Some(dict) => dict.id(),
+        None => 0,
+    }
+}
+
+/// Compresses `src` into a zstd frame using the dictionary. Returns the frame size or
+/// a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_dict_compress(
+    dict: *const ZstdDictionary,
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    ffi_dict_call(
+        dict,
+        src,
+        src_length,
+        dest,
+        dest_length,
+        ZstdDictionary::compress,
+    )
+}
+
+/// Decompresses a zstd frame produced with the same dictionary. Returns the number of
+/// bytes written to `dest` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_dict_decompress(
+    dict: *const ZstdDictionary,
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    ffi_dict_call(
+        dict,
+        src,
+        src_length,
+        dest,
+        dest_length,
+        ZstdDictionary::decompress,
+    )
+}
+
+/// Creates a zstd compression stream with a native zstd level, NULL for an invalid
+/// level. Release it with `spreads_zstd_cstream_free`.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_cstream_new(level: libc::c_int) -> *mut ZstdCStream {
+    match ZstdCStream::new(level) {
+        Ok(stream) => Box::into_raw(Box::new(stream)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Compresses as much of `src` as possible into `dest`. The number of consumed input
+/// bytes is stored to `src_consumed`. Returns the number of bytes written to `dest`
+/// or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_cstream_compress_chunk(
+    stream: *mut ZstdCStream,
+    src: *const libc::c_char,
+    src_length: usize,
+    src_consumed: *mut usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+) -> libc::c_int {
+    let stream = match unsafe { stream.as_mut() } {
+        Some(stream) if !src_consumed.is_null() => stream,
+        _ => return CompressionError::InvalidArgument.code(),
+    };
+    let buffers = unsafe { ffi_buffers(src, src_length, dest, clamp_output(dest_length)) };
+    match buffers.and_then(|(src, dest)| stream.compress_chunk(src, dest)) {
+        Ok((consumed, produced)) => {
+            unsafe { *src_consumed = consumed };
+            produced as libc::c_int
+        }
+        Err(err) => err.code(),
+    }
+}
+
+/// Flushes buffered data into `dest`. The number of bytes still to be flushed is
+/// stored to `remaining`, call again while it is not zero. Returns the number of bytes
+/// written to `dest` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_cstream_flush(
+    stream: *mut ZstdCStream,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    remaining: *mut usize,
+) -> libc::c_int {
+    ffi_finish_chunk(stream, dest, dest_length, remaining, ZstdCStream::flush)
+}
+
+/// Completes the current frame, with the same contract as `spreads_zstd_cstream_flush`.
+/// Once nothing remains, the stream can be used for a new frame.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_cstream_end(
+    stream: *mut ZstdCStream,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    remaining: *mut usize,
+) -> libc::c_int {
+    ffi_finish_chunk(stream, dest, dest_length, remaining, ZstdCStream::end)
+}
+
+#[no_mangle]
+pub extern "C" fn spreads_zstd_cstream_free(stream: *mut ZstdCStream) {
+    free_stream(stream)
+}
+
+/// Creates a zstd decompression stream. Release it with `spreads_zstd_dstream_free`.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_dstream_new() -> *mut ZstdDStream {
+    match ZstdDStream::new() {
+        Ok(stream) => Box::into_raw(Box::new(stream)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Decompresses as much of `src` as possible into `dest`. The number of consumed input
+/// bytes is stored to `src_consumed` and, if not NULL, the hint for the next input size
+/// to `next_input_hint`, which is zero once a frame is complete. Returns the number
+/// of bytes written to `dest` or a negative error code.
+#[no_mangle]
+pub extern "C" fn spreads_zstd_dstream_decompress_chunk(
+    stream: *mut ZstdDStream,
+    src: *const libc::c_char,
+    src_length: usize,
+    src_consumed: *mut usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    next_input_hint: *mut usize,
+) -> libc::c_int {
+    let stream = match unsafe { stream.as_mut() } {
+        Some(stream) if !src_consumed.is_null() => stream,
+        _ => return CompressionError::InvalidArgument.code(),
+    };
+    let buffers = unsafe { ffi_buffers(src, src_length, dest, clamp_output(dest_length)) };
+    match buffers.and_then(|(src, dest)| stream.decompress_chunk(src, dest)) {
+        Ok((consumed, produced, hint)) => {
+            unsafe {
+                *src_consumed = consumed;
+                if !next_input_hint.is_null() {
+                    *next_input_hint = hint;
+                }
+            }
+            produced as libc::c_int
+        }
+        Err(err) => err.code(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn spreads_zstd_dstream_free(stream: *mut ZstdDStream) {
+    free_stream(stream)
+}
+
+fn free_stream<T>(stream: *mut T) {
+    if !stream.is_null() {
+        unsafe { drop(Box::from_raw(stream)) };
+    }
+}
+
+/// Output lengths are reported as `int`, so larger buffers are capped.
+fn clamp_output(dest_length: usize) -> usize {
+    dest_length.min(i32::MAX as usize)
+}
+
+/// `ZstdCStream::flush` or `ZstdCStream::end`.
+type FinishFn = fn(&mut ZstdCStream, &mut [u8]) -> Result<(usize, usize), CompressionError>;
+
+fn ffi_finish_chunk(
+    stream: *mut ZstdCStream,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    remaining: *mut usize,
+    finish: FinishFn,
+) -> libc::c_int {
+    let stream = match unsafe { stream.as_mut() } {
+        Some(stream) if !remaining.is_null() => stream,
+        _ => return CompressionError::InvalidArgument.code(),
+    };
+    let dest = unsafe { ffi_slice_mut(dest, clamp_output(dest_length)) };
+    match dest.and_then(|dest| finish(stream, dest)) {
+        Ok((produced, left)) => {
+            unsafe { *remaining = left };
+            produced as libc::c_int
+        }
+        Err(err) => err.code(),
+    }
+}
+
+/// `ZstdDictionary::compress` or `ZstdDictionary::decompress`.
+type DictFn = fn(&ZstdDictionary, &[u8], &mut [u8]) -> Result<usize, CompressionError>;
+
+fn ffi_dict_call(
+    dict: *const ZstdDictionary,
+    src: *const libc::c_char,
+    src_length: usize,
+    dest: *mut libc::c_char,
+    dest_length: usize,
+    call: DictFn,
+) -> libc::c_int {
+    let dict = match unsafe { dict.as_ref() } {
+        Some(dict) => dict,
+        None => return CompressionError::InvalidArgument.code(),
+    };
+    let buffers = unsafe { ffi_buffers(src, src_length, dest, clamp_output(dest_length)) };
+    super::to_c_result(buffers.and_then(|(src, dest)| call(dict, src, dest)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_lines(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|i| {
+                format!("{} INFO replayed order {} at {}\n", i, i % 97, i * 13).into_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn could_stream_through_encoder_and_decoder() {
+        let original = log_lines(200_000);
+        let mut encoder = ZstdEncoder::new(Vec::new(), 3).unwrap();
+        for chunk in original.chunks(10_000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        encoder.flush().unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.len() < original.len() / 4);
+
+        let mut decoder = ZstdDecoder::new(&compressed[..]).unwrap();
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(original, decompressed);
+
+        let mut one_shot = vec![0u8; original.len()];
+        let len = super::super::Codec::Zstd
+            .decompress(&compressed, &mut one_shot)
+            .unwrap();
+        assert_eq!(original.len(), len);
+    }
+
+    #[test]
+    fn decoder_reads_concatenated_frames_to_the_end() {
+        let original = log_lines(50_000);
+        let mut compressed = Vec::new();
+        for (i, chunk) in original.chunks(original.len() / 3 + 1).enumerate() {
+            let mut encoder = ZstdEncoder::new(Vec::new(), 1 + i as i32).unwrap();
+            encoder.write_all(chunk).unwrap();
+            compressed.extend_from_slice(&encoder.finish().unwrap());
+        }
+        // A small output buffer leaves decoded bytes pending across reads.
+        let mut decoder = ZstdDecoder::new(&compressed[..]).unwrap();
+        let mut decompressed = Vec::new();
+        let mut buf = [0u8; 1000];
+        loop {
+            match decoder.read(&mut buf).unwrap() {
+                0 => break,
+                n => decompressed.extend_from_slice(&buf[..n]),
+            }
+        }
+        assert_eq!(original, decompressed);
+
+        let mut decoder = ZstdDecoder::new(&compressed[..]).unwrap();
+        let mut decompressed = Vec::new();
+        assert_eq!(
+            original.len(),
+            decoder.read_to_end(&mut decompressed).unwrap()
+        );
+        assert_eq!(original, decompressed);
+
+        let mut decoder = ZstdDecoder::new(&[][..]).unwrap();
+        assert_eq!(0, decoder.read_to_end(&mut Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn decoder_reports_truncated_stream() {
+        let original = log_lines(10_000);
+        let mut encoder = ZstdEncoder::new(Vec::new(), 1).unwrap();
+        encoder.write_all(&original).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = ZstdDecoder::new(&compressed[..compressed.len() - 10]).unwrap();
+        let mut decompressed = Vec::new();
+        let err = decoder.read_to_end(&mut decompressed).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    fn messages() -> Vec<Vec<u8>> {
+        (0..5_000)
+            .map(|i| {
+                format!(
+                    "{{\"type\":\"quote\",\"symbol\":\"SYM{}\",\"bid\":{}.{},\"ask\":{}.{},\"seq\":{}}}",
+                    i % 50,
+                    100 + i % 7,
+                    i % 100,
+                    101 + i % 7,
+                    (i * 7) % 100,
+                    i
+                )
+                .into_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn could_train_and_use_dictionary() {
+        let messages = messages();
+        let samples: Vec<&[u8]> = messages.iter().map(|m| &m[..]).collect();
+        let trained = train_dictionary(&samples, 16 * 1024).unwrap();
+        assert!(!trained.is_empty() && trained.len() <= 16 * 1024);
+
+        let dictionary = ZstdDictionary::new(&trained, 3).unwrap();
+        assert_ne!(0, dictionary.id());
+        let loaded = ZstdDictionary::new(dictionary.as_bytes(), 3).unwrap();
+        assert_eq!(dictionary.id(), loaded.id());
+
+        let mut with_dict = 0;
+        let mut without_dict = 0;
+        for message in messages.iter().take(100) {
+            let compressed = dictionary.compress_to_vec(message).unwrap();
+            with_dict += compressed.len();
+            without_dict += super::super::Codec::Zstd
+                .compress_to_vec(message, 5)
+                .unwrap()
+                .len();
+            let mut decompressed = vec![0u8; message.len()];
+            assert_eq!(
+                Ok(message.len()),
+                loaded.decompress(&compressed, &mut decompressed)
+            );
+            assert_eq!(message, &decompressed);
+        }
+        assert!(with_dict * 2 < without_dict);
+    }
+
+    #[test]
+    fn training_needs_samples() {
+        let samples: Vec<&[u8]> = vec![b"a", b"b"];
+        assert_eq!(
+            Err(CompressionError::InvalidArgument),
+            train_dictionary(&samples, 1024)
+        );
+    }
+
+    #[test]
+    fn could_stream_through_c_api() {
+        let original = log_lines(5_000);
+        assert!(spreads_zstd_cstream_new(0).is_null());
+        let cstream = spreads_zstd_cstream_new(5);
+        let mut compressed = vec![0u8; original.len()];
+        let mut written = 0;
+        for chunk in original.chunks(1000) {
+            let mut offset = 0;
+            while offset < chunk.len() {
+                let mut consumed = 0;
+                let produced = spreads_zstd_cstream_compress_chunk(
+                    cstream,
+                    chunk[offset..].as_ptr() as *const libc::c_char,
+                    chunk.len() - offset,
+                    &mut consumed,
+                    compressed[written..].as_mut_ptr() as *mut libc::c_char,
+                    compressed.len() - written,
+                );
+                assert!(produced >= 0);
+                written += produced as usize;
+                offset += consumed;
+            }
+        }
+        let mut remaining = 1;
+        while remaining != 0 {
+            let produced = spreads_zstd_cstream_end(
+                cstream,
+                compressed[written..].as_mut_ptr() as *mut libc::c_char,
+                compressed.len() - written,
+                &mut remaining,
+            );
+            assert!(produced >= 0);
+            written += produced as usize;
+        }
+        spreads_zstd_cstream_free(cstream);
+
+        let dstream = spreads_zstd_dstream_new();
+        let mut decompressed = vec![0u8; original.len()];
+        let mut consumed = 0;
+        let mut hint = 0;
+        let produced = spreads_zstd_dstream_decompress_chunk(
+            dstream,
+            compressed.as_ptr() as *const libc::c_char,
+            written,
+            &mut consumed,
+            decompressed.as_mut_ptr() as *mut libc::c_char,
+            decompressed.len(),
+            &mut hint,
+        );
+        spreads_zstd_dstream_free(dstream);
+        assert_eq!(original.len() as libc::c_int, produced);
+        assert_eq!(written, consumed);
+        assert_eq!(0, hint);
+        assert_eq!(original, decompressed);
+    }
+}